@@ -1,12 +1,15 @@
 use anyhow::Result;
-use protocol::{prelude::*, ClientEvent, Message, ServerEvent};
+use protocol::{
+    codec::{self, FrameReader, FrameWriter},
+    prelude::*,
+    AnyCodec, ClientEvent, Codec, Message, Recipient, Room, RoomInfo, ServerEvent,
+};
 use std::{
     io::{self, Write},
     net::ToSocketAddrs,
     process, thread,
 };
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter},
     net::{
         tcp::{OwnedReadHalf, OwnedWriteHalf},
         TcpSocket,
@@ -39,6 +42,9 @@ enum ClientError {
 
     #[error("Empty name not allowed")]
     EmptyName,
+
+    #[error("could not read from the server: {0}")]
+    ServerRead(io::Error),
 }
 
 struct Client {
@@ -64,8 +70,7 @@ impl Client {
         let socket_addr = addr
             .to_socket_addrs()
             .map_err(|e| ClientError::AddrParseFailure(addr.to_string(), e))?
-            .filter(|f| f.is_ipv4())
-            .next()
+            .find(|f| f.is_ipv4())
             .ok_or(ClientError::NoIpV4Addrs)?;
         let socket = TcpSocket::new_v4().map_err(ClientError::SocketCreate)?;
         let tcp_stream = socket
@@ -74,15 +79,23 @@ impl Client {
             .map_err(|e| ClientError::CouldNotConnect(addr.into(), e))?;
         let mut user_rx = read_user_input();
         let (server_rx, server_tx) = tcp_stream.into_split();
-        let server_rx = BufReader::new(server_rx);
-        let mut server_tx = BufWriter::new(server_tx);
-        send_server(ClientEvent::Ident(protocol::User { name }), &mut server_tx).await?;
+        let mut server_rx = FrameReader::new(server_rx, AnyCodec::JsonLines);
+        let mut server_tx = FrameWriter::new(server_tx, AnyCodec::JsonLines);
+        codec::request_codec(&mut server_rx, &mut server_tx, self.config.codec).await?;
+        send_server(ClientEvent::Version(protocol::VERSION), &mut server_tx).await?;
+        send_server(
+            ClientEvent::Ident(protocol::User { name }.into()),
+            &mut server_tx,
+        )
+        .await?;
+        let codec = server_rx.codec();
         let mut server_rx = read_server(server_rx).await;
         loop {
             tokio::select! {
                 event = server_rx.recv() => {
                     let Some(event) = event else { break };
-                    self.handle_server_event(&event).await?;
+                    let event = event.map_err(ClientError::ServerRead)?;
+                    self.handle_server_event(codec, &event).await?;
                 }
                 input = user_rx.recv() => {
                     let Some(input) = input else { break };
@@ -94,45 +107,61 @@ impl Client {
         Ok(())
     }
 
-    async fn handle_server_event(&mut self, input: &str) -> Result<()> {
-        let event = serde_json::from_str::<ServerEvent>(input)?;
-        match event {
+    async fn handle_server_event(&mut self, codec: impl Codec, input: &[u8]) -> Result<()> {
+        let event = codec.decode::<ServerEvent>(input)?;
+        let out = match event {
             ServerEvent::Message(message) => {
                 let Message {
+                    id: _id,
                     from,
                     text,
                     time: _time,
+                    to,
                 } = message;
                 let name = from.name;
-                let out = format!("{name}: {text}\n");
-                write!(&mut self.config.stdout, "{out}")?;
+                match to {
+                    Recipient::Room(room) if room == Room::default() => format!("{name}: {text}\n"),
+                    Recipient::Room(room) => format!("[{}] {name}: {text}\n", room.name),
+                    Recipient::User(_) => format!("(dm) {name}: {text}\n"),
+                }
             }
-        }
+            ServerEvent::Joined { user, room } => format!("* {} joined {}\n", user.name, room.name),
+            ServerEvent::Parted { user, room } => format!("* {} left {}\n", user.name, room.name),
+            ServerEvent::Presence { user, online } => {
+                let status = if online { "online" } else { "offline" };
+                format!("* {} is {status}\n", user.name)
+            }
+            ServerEvent::Rooms(rooms) => {
+                let rooms = rooms
+                    .into_iter()
+                    .map(|RoomInfo { room, users }| {
+                        let users = users.into_iter().map(|user| user.name);
+                        format!("{} ({})", room.name, users.collect::<Vec<_>>().join(", "))
+                    })
+                    .collect::<Vec<_>>();
+                format!("* rooms: {}\n", rooms.join(", "))
+            }
+            // only sent in answer to asking for a codec, before anything else
+            ServerEvent::Codec(_) => return Ok(()),
+        };
+        write!(&mut self.config.stdout, "{out}")?;
         Ok(())
     }
 }
 
-async fn send_server(event: ClientEvent, writer: &mut BufWriter<OwnedWriteHalf>) -> Result<()> {
-    let event = serde_json::to_string(&event)?;
-    writer.write_all(event.as_bytes()).await?;
-    writer.write_all("\n".as_bytes()).await?;
-    writer.flush().await?;
+async fn send_server(event: ClientEvent, writer: &mut FrameWriter<OwnedWriteHalf>) -> Result<()> {
+    writer.write(&event).await?;
     Ok(())
 }
 
-async fn read_server(mut reader: BufReader<OwnedReadHalf>) -> Receiver<String> {
+/// reads the frames of the server's events, until it disconnects or a frame cannot be read
+async fn read_server(mut reader: FrameReader<OwnedReadHalf>) -> Receiver<io::Result<Vec<u8>>> {
     let (tx, rx) = mpsc::channel(1024);
     tokio::spawn(async move {
-        loop {
-            let mut buf = String::new();
-            if reader.read_line(&mut buf).await.is_err() {
-                break;
-            }
-            let buf = buf.trim().to_string();
-            if buf.is_empty() {
-                break;
-            }
-            if tx.send(buf).await.is_err() {
+        while let Some(frame) = reader.read_frame().await.transpose() {
+            // an error ends the stream, and is passed on to say why
+            let failed = frame.is_err();
+            if tx.send(frame).await.is_err() || failed {
                 break;
             }
         }
@@ -178,6 +207,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_harness() {
-        protocol::verify_client(Client::start).await;
+        for codec in [AnyCodec::JsonLines, AnyCodec::MessagePack] {
+            protocol::verify_client(codec, Client::start).await;
+        }
     }
 }
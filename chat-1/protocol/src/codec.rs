@@ -0,0 +1,287 @@
+//! How events are framed and encoded on the wire.
+//!
+//! Every connection starts out in json lines. A client that would rather speak another codec
+//! sends `ClientEvent::Codecs` as its first event, and the server answers with
+//! `ServerEvent::Codec`. Both sides speak the codec of the answer from the next event on, so
+//! clients that never ask keep working as before.
+
+use crate::{ClientEvent, ServerEvent};
+use clap::ValueEnum;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{io, ops::Range};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// the longest frame a codec reads, so that a bad length cannot run the reader out of memory
+pub const MAX_FRAME: usize = 1 << 20;
+
+/// Frames and encodes values
+pub trait Codec {
+    /// The name the codec is negotiated by
+    fn name(&self) -> &'static str;
+
+    /// Appends the frame of the value to the buffer
+    fn encode<T: Serialize>(&self, value: &T, buf: &mut Vec<u8>) -> io::Result<()>;
+
+    /// Finds the frame at the start of the buffer, returning where its payload is and how long
+    /// the whole frame is, or none if the frame is not all there yet
+    fn frame(&self, buf: &[u8]) -> io::Result<Option<(Range<usize>, usize)>>;
+
+    /// Decodes the payload of a frame
+    fn decode<T: DeserializeOwned>(&self, payload: &[u8]) -> io::Result<T>;
+}
+
+/// A line of json per value. Json escapes newlines in strings, so a line is always a value.
+/// Blank lines are skipped.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonLines;
+
+impl Codec for JsonLines {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn encode<T: Serialize>(&self, value: &T, buf: &mut Vec<u8>) -> io::Result<()> {
+        serde_json::to_writer(&mut *buf, value)?;
+        buf.push(b'\n');
+        Ok(())
+    }
+
+    fn frame(&self, buf: &[u8]) -> io::Result<Option<(Range<usize>, usize)>> {
+        let Some(start) = buf.iter().position(|b| !b.is_ascii_whitespace()) else {
+            return Ok(None);
+        };
+        match buf[start..].iter().position(|&b| b == b'\n') {
+            Some(len) => Ok(Some((start..start + len, start + len + 1))),
+            None if buf.len() - start > MAX_FRAME => Err(too_long(buf.len() - start)),
+            None => Ok(None),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(&self, payload: &[u8]) -> io::Result<T> {
+        Ok(serde_json::from_slice(payload)?)
+    }
+}
+
+/// [MessagePack](https://msgpack.org), after the length of the payload as a big endian u32
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MessagePack;
+
+impl Codec for MessagePack {
+    fn name(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn encode<T: Serialize>(&self, value: &T, buf: &mut Vec<u8>) -> io::Result<()> {
+        // named, so that optional and flattened fields work like they do in json
+        let payload = rmp_serde::to_vec_named(value).map_err(invalid)?;
+        let len = u32::try_from(payload.len()).map_err(|_| too_long(payload.len()))?;
+        buf.extend(len.to_be_bytes());
+        buf.extend(payload);
+        Ok(())
+    }
+
+    fn frame(&self, buf: &[u8]) -> io::Result<Option<(Range<usize>, usize)>> {
+        let Some(len) = buf.get(..4) else {
+            return Ok(None);
+        };
+        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        if len > MAX_FRAME {
+            return Err(too_long(len));
+        }
+        if buf.len() < 4 + len {
+            return Ok(None);
+        }
+        Ok(Some((4..4 + len, 4 + len)))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, payload: &[u8]) -> io::Result<T> {
+        rmp_serde::from_slice(payload).map_err(invalid)
+    }
+}
+
+/// The codecs a connection can speak, for choosing one once it is running
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum AnyCodec {
+    #[default]
+    #[value(name = "json")]
+    JsonLines,
+    #[value(name = "msgpack")]
+    MessagePack,
+}
+
+impl AnyCodec {
+    /// The codec of the name, if it is one of ours
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::value_variants()
+            .iter()
+            .copied()
+            .find(|codec| codec.name() == name)
+    }
+}
+
+impl Codec for AnyCodec {
+    fn name(&self) -> &'static str {
+        match self {
+            AnyCodec::JsonLines => JsonLines.name(),
+            AnyCodec::MessagePack => MessagePack.name(),
+        }
+    }
+
+    fn encode<T: Serialize>(&self, value: &T, buf: &mut Vec<u8>) -> io::Result<()> {
+        match self {
+            AnyCodec::JsonLines => JsonLines.encode(value, buf),
+            AnyCodec::MessagePack => MessagePack.encode(value, buf),
+        }
+    }
+
+    fn frame(&self, buf: &[u8]) -> io::Result<Option<(Range<usize>, usize)>> {
+        match self {
+            AnyCodec::JsonLines => JsonLines.frame(buf),
+            AnyCodec::MessagePack => MessagePack.frame(buf),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(&self, payload: &[u8]) -> io::Result<T> {
+        match self {
+            AnyCodec::JsonLines => JsonLines.decode(payload),
+            AnyCodec::MessagePack => MessagePack.decode(payload),
+        }
+    }
+}
+
+/// Reads the frames of a stream. It keeps what it read of a frame that is not all there yet,
+/// so a read that is cancelled loses nothing.
+pub struct FrameReader<R> {
+    reader: R,
+    codec: AnyCodec,
+    buf: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    pub fn new(reader: R, codec: AnyCodec) -> Self {
+        Self {
+            reader,
+            codec,
+            buf: vec![],
+        }
+    }
+
+    pub fn codec(&self) -> AnyCodec {
+        self.codec
+    }
+
+    /// Reads the frames after the ones already read with the codec
+    pub fn set_codec(&mut self, codec: AnyCodec) {
+        self.codec = codec;
+    }
+
+    /// Reads the payload of the next frame, or none once the stream ends. A frame that the end
+    /// cuts off is dropped.
+    pub async fn read_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some((payload, len)) = self.codec.frame(&self.buf)? {
+                let payload = self.buf[payload].to_vec();
+                self.buf.drain(..len);
+                return Ok(Some(payload));
+            }
+            self.buf.reserve(4096);
+            if self.reader.read_buf(&mut self.buf).await? == 0 {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Reads and decodes the next value, or none once the stream ends
+    pub async fn read<T: DeserializeOwned>(&mut self) -> io::Result<Option<T>> {
+        match self.read_frame().await? {
+            Some(payload) => self.codec.decode(&payload).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Writes the frames of values to a stream
+pub struct FrameWriter<W> {
+    writer: W,
+    codec: AnyCodec,
+    buf: Vec<u8>,
+}
+
+impl<W: AsyncWrite + Unpin> FrameWriter<W> {
+    pub fn new(writer: W, codec: AnyCodec) -> Self {
+        Self {
+            writer,
+            codec,
+            buf: vec![],
+        }
+    }
+
+    pub fn codec(&self) -> AnyCodec {
+        self.codec
+    }
+
+    /// Writes the values queued after this with the codec
+    pub fn set_codec(&mut self, codec: AnyCodec) {
+        self.codec = codec;
+    }
+
+    /// Queues the value, to be written with the others on the next flush
+    pub fn queue<T: Serialize>(&mut self, value: &T) -> io::Result<()> {
+        let len = self.buf.len();
+        let res = self.codec.encode(value, &mut self.buf);
+        if res.is_err() {
+            // leave no half of a frame behind
+            self.buf.truncate(len);
+        }
+        res
+    }
+
+    /// Writes the queued values
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.writer.write_all(&self.buf).await?;
+        self.buf.clear();
+        self.writer.flush().await
+    }
+
+    /// Writes the value, and any queued before it
+    pub async fn write<T: Serialize>(&mut self, value: &T) -> io::Result<()> {
+        self.queue(value)?;
+        self.flush().await
+    }
+}
+
+/// Asks the server for the codec, as the first thing on the connection, and switches to what
+/// it answers. Json lines needs no asking.
+pub async fn request_codec<R, W>(
+    reader: &mut FrameReader<R>,
+    writer: &mut FrameWriter<W>,
+    codec: AnyCodec,
+) -> io::Result<AnyCodec>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    if codec == AnyCodec::JsonLines {
+        return Ok(codec);
+    }
+    writer
+        .write(&ClientEvent::Codecs(vec![codec.name().to_string()]))
+        .await?;
+    let answer = match reader.read::<ServerEvent>().await? {
+        Some(ServerEvent::Codec(name)) => AnyCodec::from_name(&name)
+            .ok_or_else(|| invalid(format!("server answered unknown codec {name}")))?,
+        Some(event) => return Err(invalid(format!("server answered {event:?} for codec"))),
+        None => return Err(io::ErrorKind::UnexpectedEof.into()),
+    };
+    reader.set_codec(answer);
+    writer.set_codec(answer);
+    Ok(answer)
+}
+
+fn invalid(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn too_long(len: usize) -> io::Error {
+    invalid(format!("frame of {len} bytes is too long"))
+}
@@ -1,5 +1,7 @@
+pub mod codec;
 pub mod verify;
-pub use verify::verify_client;
+pub use codec::{AnyCodec, Codec};
+pub use verify::{verify_client, verify_server};
 pub mod prelude {
     pub use async_trait::async_trait;
     pub use clap::Parser;
@@ -10,6 +12,7 @@ pub mod prelude {
 }
 use std::{
     io::{self, Write},
+    path::PathBuf,
     sync::{Arc, Mutex},
 };
 
@@ -18,7 +21,16 @@ use prelude::*;
 
 #[derive(Serialize, Deserialize, Clone, Parser)]
 pub struct ServerConfig {
+    /// the address on which to listen (e.g. localhost:8000).
     pub addr: String,
+
+    /// a file to keep the message history in, so that it survives restarts.
+    #[arg(long)]
+    pub history_file: Option<PathBuf>,
+
+    /// how many of the latest messages to keep for clients that reconnect.
+    #[arg(long, default_value_t = 1000)]
+    pub history_len: usize,
 }
 
 #[derive(Parser)]
@@ -30,6 +42,10 @@ pub struct ClientConfig {
     /// the address to which to connect (e.g. localhost:8000).
     pub addr: String,
 
+    /// the codec to speak to the server.
+    #[arg(long, value_enum, default_value_t)]
+    pub codec: AnyCodec,
+
     /// clients should write to Stdout. you can use the write! macro to do this. the verifier will
     /// look at the output written to this to verify the output.
     #[clap(skip)]
@@ -42,8 +58,12 @@ pub struct ClientConfig {
 /// It implements Write, so the expected usage is something like:
 ///
 /// ```no_run
+/// # use std::io::Write;
+/// # struct Solution { config: protocol::ClientConfig }
+/// # impl Solution { fn run(&mut self) -> std::io::Result<()> {
 /// let out = "foobar";
 /// write!(&mut self.config.stdout, "{out}")?;
+/// # Ok(()) } }
 /// ```
 ///
 /// The verification harness will inspect what is written to this `Stdout` as the code runs.
@@ -97,38 +117,154 @@ pub enum Event {
     Server(ServerEvent),
 }
 
+/// The version of the protocol that this crate speaks. Version 1 only has messages to everyone,
+/// and version 2 adds rooms, direct messages and presence.
+pub const VERSION: u32 = 2;
+
 /// ClientEvent is sent by the client
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum ClientEvent {
     /// The client has identified themselves. This ident remains valid for the duration of the
-    /// connection.
-    Ident(User),
+    /// connection. Identified clients are in the default room.
+    Ident(Ident),
 
     /// The client has sent a message
     Message(Message),
+
+    /// The client understands the server events up to this version of the protocol. Clients that
+    /// never send it are sent nothing but messages. It should be sent before `Ident`.
+    Version(u32),
+
+    /// The client joins the room, which is created if it does not exist
+    Join(Room),
+
+    /// The client leaves the room
+    Leave(Room),
+
+    /// The client asks for the rooms and who is in them
+    ListRooms,
+
+    /// The client would rather speak one of the codecs, most wanted first. It can only be the
+    /// first event, which is always in json lines.
+    Codecs(Vec<String>),
 }
 
 /// ServerEvent is sent by the server
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum ServerEvent {
     /// Someone else sent a message
     Message(Message),
+
+    /// The user joined the room
+    Joined { user: User, room: Room },
+
+    /// The user left the room
+    Parted { user: User, room: Room },
+
+    /// The user came online or went offline. A client is told who is online when it identifies.
+    Presence { user: User, online: bool },
+
+    /// The rooms and who is in them, in answer to `ListRooms`
+    Rooms(Vec<RoomInfo>),
+
+    /// The codec that both sides speak from the next event on, in answer to `Codecs`. It is
+    /// json lines if the server speaks none of them.
+    Codec(String),
+}
+
+impl ServerEvent {
+    /// The version of the protocol that the event was added in, which a client must understand
+    /// to be sent it
+    pub fn version(&self) -> u32 {
+        match self {
+            // a codec is only ever sent to clients that asked for one
+            ServerEvent::Message(_) | ServerEvent::Codec(_) => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// The ident of a client
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Ident {
+    #[serde(flatten)]
+    pub user: User,
+    /// The id of the last message the client saw, when it reconnects. The server replays the
+    /// messages after it that the client can see before anything else.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<MessageId>,
+}
+
+impl From<User> for Ident {
+    fn from(user: User) -> Self {
+        Self {
+            user,
+            last_seen: None,
+        }
+    }
 }
 
+/// Messages are numbered by the server in the order it received them
+pub type MessageId = u64;
+
 /// Represents a message in the chat
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Message {
+    /// Set by the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<MessageId>,
     pub from: User,
     pub text: String,
     pub time: Timestamp,
+    /// The room or the user the message is for. Messages of version 1 have none, and are for
+    /// the default room.
+    #[serde(default)]
+    pub to: Recipient,
+}
+
+/// Who a message is for
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum Recipient {
+    /// Everyone in the room
+    Room(Room),
+    /// Only the user, as a direct message
+    User(User),
+}
+
+impl Default for Recipient {
+    fn default() -> Self {
+        Recipient::Room(Room::default())
+    }
 }
 
 /// Identifies a user
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct User {
     pub name: String,
 }
 
+/// Identifies a room
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Room {
+    pub name: String,
+}
+
+/// The default room, which everyone is in when they identify
+impl Default for Room {
+    fn default() -> Self {
+        Self {
+            name: String::from("general"),
+        }
+    }
+}
+
+/// A room and the users in it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RoomInfo {
+    pub room: Room,
+    pub users: Vec<User>,
+}
+
 /// A wrapper around time crate so we can attach methods later on.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Timestamp(OffsetDateTime);
@@ -1,14 +1,23 @@
 use super::*;
+use codec::{request_codec, FrameReader, FrameWriter};
+use std::collections::VecDeque;
 use std::future::Future;
 use std::net::SocketAddr;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::TcpListener;
+use std::time::Duration;
+use std::{env, fs, process};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, timeout};
 
-/// Verifies that the supplied client implements the protocol correctly.
+/// how long the verifier waits for the server to do anything
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Verifies that the supplied client implements the protocol correctly, when it speaks the
+/// codec.
 ///
 /// Expected usage:
 ///
-/// ```no_run
+/// ```ignore
 /// struct Client {
 ///     async fn start(config: ClientConfig) -> Result<()> {
 ///         todo()!
@@ -17,11 +26,11 @@ use tokio::net::TcpListener;
 ///
 /// #[tokio::test]
 /// async fn verify_client() {
-///     protocol::verify(Client::start).await
+///     protocol::verify_client(AnyCodec::JsonLines, Client::start).await
 /// }
 ///
 /// ```
-pub async fn verify_client<Fut>(client: impl Fn(ClientConfig) -> Fut)
+pub async fn verify_client<Fut>(codec: AnyCodec, client: impl Fn(ClientConfig) -> Fut)
 where
     Fut: Future<Output = Result<()>> + Send + 'static,
 {
@@ -31,36 +40,479 @@ where
     let buffer = Stdout::default();
     let stdout: Box<dyn io::Write + Send> = Box::new(buffer.clone());
     let stdout = super::Stdout::from(stdout);
-    let config = ClientConfig { addr, name, stdout };
+    let config = ClientConfig {
+        addr,
+        name,
+        codec,
+        stdout,
+    };
     let client = client(config);
-    let client = tokio::spawn(async move { client.await });
+    let client = tokio::spawn(client);
     let (stream, _) = server.listener.accept().await.unwrap();
-    let (stream_rx, mut stream_tx) = stream.into_split();
-    let mut reader = BufReader::new(stream_rx);
-    let mut buf = String::new();
-    reader.read_line(&mut buf).await.unwrap();
-    let event = serde_json::from_str::<ClientEvent>(&buf).unwrap();
+    let (stream_rx, stream_tx) = stream.into_split();
+    let mut reader = FrameReader::new(stream_rx, AnyCodec::JsonLines);
+    let mut writer = FrameWriter::new(stream_tx, AnyCodec::JsonLines);
+    let mut event = reader.read::<ClientEvent>().await.unwrap().unwrap();
+    // json lines may be asked for, but need not be
+    if let ClientEvent::Codecs(names) = &event {
+        assert_eq!(names.first().map(String::as_str), Some(codec.name()));
+        writer
+            .write(&ServerEvent::Codec(codec.name().to_string()))
+            .await
+            .unwrap();
+        reader.set_codec(codec);
+        writer.set_codec(codec);
+        event = reader.read::<ClientEvent>().await.unwrap().unwrap();
+    } else {
+        assert_eq!(
+            codec,
+            AnyCodec::JsonLines,
+            "client did not ask for the codec"
+        );
+    }
     match event {
-        ClientEvent::Ident(User { name }) => assert_eq!(name, "test-name"),
+        ClientEvent::Version(version) => assert_eq!(version, VERSION),
         _ => panic!("bad event: {event:?}"),
     }
-    let event = ServerEvent::Message(Message {
-        from: User {
-            name: String::from("other-user"),
-        },
-        text: String::from("hi there"),
-        time: Timestamp::default(),
-    });
-    let event = serde_json::to_string(&event).unwrap();
-    let event = format!("{event}\n");
-    stream_tx.write_all(event.as_bytes()).await.unwrap();
-    stream_tx.flush().await.unwrap();
-    drop(stream_tx);
+    let event = reader.read::<ClientEvent>().await.unwrap().unwrap();
+    match event {
+        ClientEvent::Ident(Ident { user, .. }) => assert_eq!(user.name, "test-name"),
+        _ => panic!("bad event: {event:?}"),
+    }
+    let other = || User {
+        name: String::from("other-user"),
+    };
+    let message = |text: &str, to| {
+        ServerEvent::Message(Message {
+            id: None,
+            from: other(),
+            text: String::from(text),
+            time: Timestamp::default(),
+            to,
+        })
+    };
+    // events are sent as json values, which every codec can encode, so that they can be sent
+    // as an older version would
+    let value = |event: ServerEvent| serde_json::to_value(event).unwrap();
+    // a message as version 1 sent it, without whom it is for
+    let mut old = value(message("hi there", Recipient::default()));
+    old["Message"].as_object_mut().unwrap().remove("to");
+    let events = [
+        value(ServerEvent::Presence {
+            user: other(),
+            online: true,
+        }),
+        old,
+        value(ServerEvent::Joined {
+            user: other(),
+            room: rust(),
+        }),
+        value(message("anyone here?", Recipient::Room(rust()))),
+        // a newline must not break the framing
+        value(message(
+            "psst\nover here",
+            Recipient::User(User {
+                name: String::from("test-name"),
+            }),
+        )),
+        value(ServerEvent::Rooms(vec![
+            RoomInfo {
+                room: Room::default(),
+                users: vec![
+                    other(),
+                    User {
+                        name: String::from("test-name"),
+                    },
+                ],
+            },
+            RoomInfo {
+                room: rust(),
+                users: vec![other()],
+            },
+        ])),
+        value(ServerEvent::Parted {
+            user: other(),
+            room: rust(),
+        }),
+        value(ServerEvent::Presence {
+            user: other(),
+            online: false,
+        }),
+    ];
+    for event in events {
+        writer.queue(&event).unwrap();
+    }
+    writer.flush().await.unwrap();
+    drop(writer);
     client.await.unwrap().unwrap();
     let out = buffer.output();
-    assert_eq!(out, "other-user: hi there\n");
+    assert_eq!(
+        out,
+        "* other-user is online\n\
+         other-user: hi there\n\
+         * other-user joined rust\n\
+         [rust] other-user: anyone here?\n\
+         (dm) other-user: psst\nover here\n\
+         * rooms: general (other-user, test-name), rust (other-user)\n\
+         * other-user left rust\n\
+         * other-user is offline\n"
+    );
 }
 
+/// Verifies that the supplied server implements the protocol correctly, by connecting several
+/// clients that chat, disconnect and join while the others carry on.
+///
+/// Expected usage:
+///
+/// ```ignore
+/// struct Server {
+///     async fn start(config: ServerConfig) -> Result<()> {
+///         todo()!
+///     }
+/// }
+///
+/// #[tokio::test]
+/// async fn verify_server() {
+///     protocol::verify_server(Server::start).await
+/// }
+///
+/// ```
+pub async fn verify_server<Fut>(server: impl Fn(ServerConfig) -> Fut)
+where
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    let history_file = env::temp_dir().join(format!("verify-server-{}.history", process::id()));
+    let _ = fs::remove_file(&history_file);
+    let mut config = ServerConfig {
+        addr: free_addr().await,
+        history_file: Some(history_file.clone()),
+        history_len: 100,
+    };
+    let addr = config.addr.clone();
+    let server_task = tokio::spawn(server(config.clone()));
+
+    // clients that send no version are only sent messages
+    let mut clients = vec![];
+    for name in ["alice", "bob", "carol"] {
+        TestClient::join(&addr, user(name), None, AnyCodec::JsonLines, &mut clients).await;
+    }
+    let [alice, bob, carol] = &mut clients[..] else {
+        unreachable!()
+    };
+    alice.send("hi all").await;
+    bob.expect("alice: hi all").await;
+    carol.expect("alice: hi all").await;
+    // nobody hears their own messages back
+    bob.send("hi alice").await;
+    alice.expect("bob: hi alice").await;
+    carol.expect("bob: hi alice").await;
+
+    // clients of the current version are told who is online, whichever codec they speak
+    let msgpack = AnyCodec::MessagePack;
+    TestClient::join(&addr, user("dave"), Some(VERSION), msgpack, &mut clients).await;
+    let dave = &mut clients[3];
+    dave.expect("* alice is online").await;
+    dave.expect("* bob is online").await;
+    dave.expect("* carol is online").await;
+
+    // the others carry on when a client disconnects
+    let carol = clients.remove(2);
+    drop(carol);
+    let [alice, bob, dave] = &mut clients[..] else {
+        unreachable!()
+    };
+    dave.expect("* carol is offline").await;
+    alice.send("carol left").await;
+    bob.expect("alice: carol left").await;
+    dave.expect("alice: carol left").await;
+
+    let json = AnyCodec::JsonLines;
+    TestClient::join(&addr, user("erin"), Some(VERSION), json, &mut clients).await;
+    let [alice, bob, dave, erin] = &mut clients[..] else {
+        unreachable!()
+    };
+    dave.expect("* erin is online").await;
+    erin.expect("* alice is online").await;
+    erin.expect("* bob is online").await;
+    erin.expect("* dave is online").await;
+
+    // rooms only hear from their members
+    erin.send_event(&ClientEvent::Join(rust())).await;
+    erin.expect("* erin joined rust").await;
+    dave.send_event(&ClientEvent::Join(rust())).await;
+    dave.expect("* dave joined rust").await;
+    erin.expect("* dave joined rust").await;
+    erin.send_to("anyone here?", Recipient::Room(rust())).await;
+    dave.expect("[rust] erin: anyone here?").await;
+    dave.send_event(&ClientEvent::ListRooms).await;
+    dave.expect("* rooms: general (alice, bob, dave, erin), rust (dave, erin)")
+        .await;
+    let to = Recipient::User(User {
+        name: String::from("alice"),
+    });
+    erin.send_to("psst", to).await;
+    alice.expect("(dm) erin: psst").await;
+    dave.send_event(&ClientEvent::Leave(rust())).await;
+    dave.expect("* dave left rust").await;
+    erin.expect("* dave left rust").await;
+
+    // nobody heard what was not for them
+    erin.send("bye").await;
+    for client in [alice, bob, dave] {
+        client.expect("erin: bye").await;
+    }
+
+    // a client that reconnects is replayed what it missed, in order, before anything new
+    let dave = clients.remove(2);
+    let last_seen = dave.last_seen;
+    drop(dave);
+    let [alice, bob, erin] = &mut clients[..] else {
+        unreachable!()
+    };
+    erin.expect("* dave is offline").await;
+    alice.send("you missed this").await;
+    erin.expect("alice: you missed this").await;
+    erin.send_to("and this", Recipient::User(user("dave")))
+        .await;
+    erin.send("then this").await;
+    bob.expect("alice: you missed this").await;
+    bob.expect("erin: then this").await;
+    let ident = Ident {
+        user: user("dave"),
+        last_seen,
+    };
+    TestClient::join(&addr, ident, Some(VERSION), msgpack, &mut clients).await;
+    let dave = &mut clients[3];
+    dave.expect("alice: you missed this").await;
+    dave.expect("(dm) erin: and this").await;
+    dave.expect("erin: then this").await;
+    dave.expect("* alice is online").await;
+    dave.expect("* bob is online").await;
+    dave.expect("* erin is online").await;
+    assert!(!server_task.is_finished(), "server stopped");
+    server_task.abort();
+
+    // the history outlives the server, and message ids carry on after it
+    config.addr = free_addr().await;
+    let addr = config.addr.clone();
+    let server_task = tokio::spawn(server(config));
+    let mut clients = vec![];
+    let ident = Ident {
+        user: user("frank"),
+        last_seen,
+    };
+    TestClient::join(&addr, ident, Some(VERSION), msgpack, &mut clients).await;
+    let frank = &mut clients[0];
+    frank.expect("alice: you missed this").await;
+    frank.expect("erin: then this").await;
+    let replayed = frank.last_seen;
+    TestClient::join(&addr, user("gina"), None, json, &mut clients).await;
+    let [frank, gina] = &mut clients[..] else {
+        unreachable!()
+    };
+    gina.send("is anyone still here?").await;
+    frank.expect("* gina is online").await;
+    frank.expect("gina: is anyone still here?").await;
+    assert!(
+        frank.last_seen > replayed,
+        "message ids went back from {replayed:?} to {:?} after a restart",
+        frank.last_seen
+    );
+
+    assert!(!server_task.is_finished(), "server stopped");
+    server_task.abort();
+    let _ = fs::remove_file(&history_file);
+}
+
+/// an address on which nothing listens
+async fn free_addr() -> String {
+    let addr = Server::new().await.addr;
+    format!("127.0.0.1:{}", addr.port())
+}
+
+fn user(name: &str) -> User {
+    User {
+        name: name.to_string(),
+    }
+}
+
+/// A client of the server under verification, which sends and reads events itself
+struct TestClient {
+    name: String,
+    /// the id of the last message the client got
+    last_seen: Option<MessageId>,
+    /// the events read while waiting for a sync
+    pending: VecDeque<ServerEvent>,
+    reader: FrameReader<OwnedReadHalf>,
+    writer: FrameWriter<OwnedWriteHalf>,
+}
+
+impl TestClient {
+    /// Connects and identifies a client that speaks the codec, and waits until the server has
+    /// registered it and the clients that joined before it
+    async fn join(
+        addr: &str,
+        ident: impl Into<Ident>,
+        version: Option<u32>,
+        codec: AnyCodec,
+        clients: &mut Vec<TestClient>,
+    ) {
+        let ident = ident.into();
+        let name = ident.user.name.clone();
+        let stream = timeout(TIMEOUT, async {
+            // the server may still be starting
+            loop {
+                match TcpStream::connect(addr).await {
+                    Ok(stream) => break stream,
+                    Err(_) => sleep(Duration::from_millis(10)).await,
+                }
+            }
+        })
+        .await
+        .expect("could not connect to the server");
+        let (reader, writer) = stream.into_split();
+        let mut client = TestClient {
+            name: name.clone(),
+            last_seen: None,
+            pending: VecDeque::new(),
+            reader: FrameReader::new(reader, AnyCodec::JsonLines),
+            writer: FrameWriter::new(writer, AnyCodec::JsonLines),
+        };
+        let agreed = timeout(TIMEOUT, async {
+            request_codec(&mut client.reader, &mut client.writer, codec).await
+        })
+        .await
+        .unwrap_or_else(|_| panic!("{name} never agreed on a codec"))
+        .unwrap();
+        assert_eq!(agreed, codec, "server did not speak the codec");
+        if let Some(version) = version {
+            client.send_event(&ClientEvent::Version(version)).await;
+        }
+        client.send_event(&ClientEvent::Ident(ident)).await;
+        // the server handles the events of a client in order, so once the first client hears a
+        // sync from this one, this one is registered
+        if let Some(first) = clients.first_mut() {
+            timeout(TIMEOUT, async {
+                loop {
+                    client.send(SYNC).await;
+                    let heard = timeout(Duration::from_millis(50), first.recv_sync(&name)).await;
+                    if heard.is_ok() {
+                        break;
+                    }
+                }
+            })
+            .await
+            .unwrap_or_else(|_| panic!("{} never heard {name} join", first.name));
+        }
+        clients.push(client);
+    }
+
+    async fn send(&mut self, text: &str) {
+        self.send_to(text, Recipient::default()).await;
+    }
+
+    async fn send_to(&mut self, text: &str, to: Recipient) {
+        let message = Message {
+            id: None,
+            from: User {
+                name: self.name.clone(),
+            },
+            text: text.to_string(),
+            time: Timestamp::default(),
+            to,
+        };
+        self.send_event(&ClientEvent::Message(message)).await;
+    }
+
+    async fn send_event(&mut self, event: &ClientEvent) {
+        self.writer.write(event).await.unwrap();
+    }
+
+    /// Reads the next event that is not a sync
+    async fn recv(&mut self) -> ServerEvent {
+        if let Some(event) = self.pending.pop_front() {
+            return event;
+        }
+        loop {
+            match self.recv_any().await {
+                ServerEvent::Message(message) if message.text == SYNC => {}
+                event => break event,
+            }
+        }
+    }
+
+    /// Reads until the sync from the user, keeping the other events for `recv`
+    async fn recv_sync(&mut self, from: &str) {
+        loop {
+            match self.recv_any().await {
+                ServerEvent::Message(message) if message.text == SYNC => {
+                    if message.from.name == from {
+                        break;
+                    }
+                }
+                event => self.pending.push_back(event),
+            }
+        }
+    }
+
+    /// Reads the next event. A frame that was cut off by a timeout is finished by the next call.
+    async fn recv_any(&mut self) -> ServerEvent {
+        let event = self.reader.read::<ServerEvent>().await.unwrap();
+        let event = event.unwrap_or_else(|| panic!("server disconnected {}", self.name));
+        if let ServerEvent::Message(Message { id: Some(id), .. }) = event {
+            self.last_seen = Some(id);
+        }
+        event
+    }
+
+    /// Asserts that the next event is described as expected, like a client would print it
+    async fn expect(&mut self, expected: &str) {
+        let event = timeout(TIMEOUT, self.recv())
+            .await
+            .unwrap_or_else(|_| panic!("{} never got {expected:?}", self.name));
+        assert_eq!(
+            describe(&event),
+            expected,
+            "unexpected event for {}",
+            self.name
+        );
+    }
+}
+
+/// describes the event as the reference client prints it
+fn describe(event: &ServerEvent) -> String {
+    match event {
+        ServerEvent::Message(Message { from, text, to, .. }) => match to {
+            Recipient::Room(room) if *room == Room::default() => format!("{}: {text}", from.name),
+            Recipient::Room(room) => format!("[{}] {}: {text}", room.name, from.name),
+            Recipient::User(_) => format!("(dm) {}: {text}", from.name),
+        },
+        ServerEvent::Joined { user, room } => format!("* {} joined {}", user.name, room.name),
+        ServerEvent::Parted { user, room } => format!("* {} left {}", user.name, room.name),
+        ServerEvent::Presence { user, online } => {
+            let status = if *online { "online" } else { "offline" };
+            format!("* {} is {status}", user.name)
+        }
+        ServerEvent::Rooms(rooms) => {
+            let rooms = rooms.iter().map(|RoomInfo { room, users }| {
+                let users = users.iter().map(|user| user.name.as_str());
+                format!("{} ({})", room.name, users.collect::<Vec<_>>().join(", "))
+            });
+            format!("* rooms: {}", rooms.collect::<Vec<_>>().join(", "))
+        }
+        ServerEvent::Codec(codec) => format!("* codec {codec}"),
+    }
+}
+
+fn rust() -> Room {
+    Room {
+        name: String::from("rust"),
+    }
+}
+
+/// the text of the messages with which clients wait for the server to register them
+const SYNC: &str = "\u{0}sync";
+
 struct Server {
     listener: TcpListener,
     addr: SocketAddr,
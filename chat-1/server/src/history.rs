@@ -0,0 +1,248 @@
+use protocol::{Message, MessageId};
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// The latest messages, numbered in the order they were received. If it has a file, each
+/// message is appended to it as a line of json so the history survives restarts, and the file
+/// is rewritten once it holds twice the messages that are kept.
+pub struct History {
+    messages: VecDeque<Message>,
+    len: usize,
+    next_id: MessageId,
+    file: Option<(PathBuf, File)>,
+    /// how many messages the file holds
+    file_len: usize,
+}
+
+impl History {
+    /// Opens the history, reading the messages in the file if there is one
+    pub fn open(len: usize, path: Option<&Path>) -> io::Result<Self> {
+        let mut history = Self {
+            messages: VecDeque::with_capacity(len),
+            len,
+            next_id: 1,
+            file: None,
+            file_len: 0,
+        };
+        let Some(path) = path else {
+            return Ok(history);
+        };
+        match fs::read(path) {
+            Ok(bytes) => {
+                // a line torn by a crash has no newline, and is cut off so that the next
+                // message starts a line of its own
+                let end = bytes.iter().rposition(|b| *b == b'\n').map_or(0, |n| n + 1);
+                if end < bytes.len() {
+                    OpenOptions::new()
+                        .write(true)
+                        .open(path)?
+                        .set_len(end as u64)?;
+                }
+                for line in bytes[..end].split(|b| *b == b'\n') {
+                    // only that message is lost, as are any that do not decode
+                    let Ok(message) = serde_json::from_slice::<Message>(line) else {
+                        continue;
+                    };
+                    history.file_len += 1;
+                    history.keep(message);
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        history.file = Some((path.to_path_buf(), file));
+        Ok(history)
+    }
+
+    /// Numbers the message and adds it to the history. The message is kept even if it could
+    /// not be written to the file.
+    pub fn push(&mut self, mut message: Message) -> (Message, io::Result<()>) {
+        message.id = Some(self.next_id);
+        self.keep(message.clone());
+        let res = self.write(&message);
+        (message, res)
+    }
+
+    /// The messages after the id, oldest first
+    pub fn since(&self, id: MessageId) -> impl Iterator<Item = &Message> {
+        self.messages
+            .iter()
+            .filter(move |message| message.id.is_some_and(|m| m > id))
+    }
+
+    fn keep(&mut self, message: Message) {
+        if let Some(id) = message.id {
+            self.next_id = self.next_id.max(id + 1);
+        }
+        if self.len == 0 {
+            return;
+        }
+        if self.messages.len() == self.len {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message);
+    }
+
+    fn write(&mut self, message: &Message) -> io::Result<()> {
+        let Some((path, file)) = &mut self.file else {
+            return Ok(());
+        };
+        if self.file_len >= 2 * self.len.max(1) {
+            // rewrite the kept messages and swap the file in whole. without any kept, the
+            // message is still written so that numbering carries on after a restart
+            let tmp = path.with_extension("tmp");
+            let messages = if self.messages.is_empty() {
+                vec![message]
+            } else {
+                self.messages.iter().collect()
+            };
+            let mut buf = vec![];
+            for message in &messages {
+                serde_json::to_writer(&mut buf, message)?;
+                buf.push(b'\n');
+            }
+            let mut tmp_file = File::create(&tmp)?;
+            tmp_file.write_all(&buf)?;
+            tmp_file.sync_all()?;
+            fs::rename(&tmp, &path)?;
+            *file = OpenOptions::new().append(true).open(&path)?;
+            self.file_len = messages.len();
+            return Ok(());
+        }
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        file.write_all(&line)?;
+        self.file_len += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::{Recipient, Timestamp, User};
+    use std::{env, process};
+
+    fn message(text: &str) -> Message {
+        Message {
+            id: None,
+            from: User {
+                name: "alice".to_string(),
+            },
+            text: text.to_string(),
+            time: Timestamp::default(),
+            to: Recipient::default(),
+        }
+    }
+
+    fn texts<'a>(messages: impl Iterator<Item = &'a Message>) -> Vec<(MessageId, &'a str)> {
+        messages.map(|m| (m.id.unwrap(), m.text.as_str())).collect()
+    }
+
+    /// a history file of its own for the test, removed when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("history-{name}-{}", process::id()));
+            let _ = fs::remove_file(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_since() {
+        let mut history = History::open(3, None).unwrap();
+        for text in ["a", "b", "c", "d"] {
+            let (message, res) = history.push(message(text));
+            res.unwrap();
+            assert_eq!(message.text, text);
+        }
+        // only the latest are kept
+        assert_eq!(texts(history.since(0)), vec![(2, "b"), (3, "c"), (4, "d")]);
+        assert_eq!(texts(history.since(3)), vec![(4, "d")]);
+        assert!(history.since(4).next().is_none());
+
+        // without a history, messages are still numbered
+        let mut history = History::open(0, None).unwrap();
+        assert_eq!(history.push(message("a")).0.id, Some(1));
+        assert_eq!(history.push(message("b")).0.id, Some(2));
+        assert!(history.since(0).next().is_none());
+    }
+
+    #[test]
+    fn test_torn_tail() {
+        let file = TempFile::new("torn");
+        let mut history = History::open(10, Some(&file.0)).unwrap();
+        history.push(message("a")).1.unwrap();
+        history.push(message("b")).1.unwrap();
+        drop(history);
+
+        // a crash tore the last line inside a character, and a line before it is garbage
+        let mut bytes = fs::read(&file.0).unwrap();
+        let mut torn = serde_json::to_vec(&message("é")).unwrap();
+        let e = torn.iter().position(|b| *b == 0xc3).unwrap();
+        torn.truncate(e + 1);
+        bytes.splice(0..0, b"not json\n".iter().copied());
+        bytes.extend(torn);
+        fs::write(&file.0, bytes).unwrap();
+
+        let mut history = History::open(10, Some(&file.0)).unwrap();
+        assert_eq!(texts(history.since(0)), vec![(1, "a"), (2, "b")]);
+        assert!(fs::read(&file.0).unwrap().ends_with(b"\n"));
+        history.push(message("c")).1.unwrap();
+        drop(history);
+
+        // the message after the torn one survives the next restart too
+        let history = History::open(10, Some(&file.0)).unwrap();
+        assert_eq!(texts(history.since(0)), vec![(1, "a"), (2, "b"), (3, "c")]);
+    }
+
+    #[test]
+    fn test_compaction() {
+        let file = TempFile::new("compaction");
+        let lines = || fs::read_to_string(&file.0).unwrap().lines().count();
+        let mut history = History::open(2, Some(&file.0)).unwrap();
+        for text in ["a", "b", "c", "d"] {
+            history.push(message(text)).1.unwrap();
+        }
+        assert_eq!(lines(), 4);
+        // the file holds twice the kept messages, so it is rewritten with only those
+        history.push(message("e")).1.unwrap();
+        assert_eq!(lines(), 2);
+        history.push(message("f")).1.unwrap();
+        assert_eq!(lines(), 3);
+        drop(history);
+
+        let mut history = History::open(2, Some(&file.0)).unwrap();
+        assert_eq!(texts(history.since(0)), vec![(5, "e"), (6, "f")]);
+        // numbering carries on from the file
+        assert_eq!(history.push(message("g")).0.id, Some(7));
+    }
+
+    #[test]
+    fn test_compaction_without_history() {
+        let file = TempFile::new("no-history");
+        let mut history = History::open(0, Some(&file.0)).unwrap();
+        for text in ["a", "b", "c"] {
+            history.push(message(text)).1.unwrap();
+        }
+        drop(history);
+
+        // nothing is kept, but numbering still carries on from the file
+        let mut history = History::open(0, Some(&file.0)).unwrap();
+        assert!(history.since(0).next().is_none());
+        assert_eq!(history.push(message("d")).0.id, Some(4));
+    }
+}
@@ -0,0 +1,406 @@
+use anyhow::Result;
+use history::History;
+use protocol::{
+    codec::{FrameReader, FrameWriter},
+    prelude::*,
+    AnyCodec, ClientEvent, Codec, Ident, Message, MessageId, Recipient, Room, RoomInfo,
+    ServerEvent, Timestamp, User,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream,
+    },
+    sync::mpsc::{self, error::TrySendError, Receiver, Sender},
+};
+
+mod history;
+
+/// how many events can wait to be written to a client, on top of the history
+/// it may be replayed. a client that falls further behind misses events rather
+/// than holding up everyone else.
+const CLIENT_BUFFER: usize = 1024;
+
+#[tokio::main]
+async fn main() {
+    let config = protocol::ServerConfig::parse();
+    if let Err(err) = Server::start(config).await {
+        eprintln!("{err:?}");
+        process::exit(1);
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum ServerError {
+    #[error("Could not listen on {0}: {1}")]
+    CouldNotBind(String, io::Error),
+
+    #[error("Could not open history {0}: {1}")]
+    CouldNotOpenHistory(PathBuf, io::Error),
+}
+
+type ClientId = u64;
+
+/// A connected client
+struct Client {
+    /// who the client identified as, if it has yet
+    user: Option<User>,
+    /// the version of the protocol the client understands
+    version: u32,
+    rooms: HashSet<Room>,
+    /// the queue of events to write to the client
+    tx: Sender<ServerEvent>,
+}
+
+/// The connected clients, and the messages they sent
+struct Clients {
+    next_id: ClientId,
+    clients: HashMap<ClientId, Client>,
+    history: History,
+}
+
+impl Clients {
+    fn new(history: History) -> Self {
+        Self {
+            next_id: 0,
+            clients: HashMap::new(),
+            history,
+        }
+    }
+
+    fn connect(&mut self, tx: Sender<ServerEvent>) -> ClientId {
+        self.next_id += 1;
+        let client = Client {
+            user: None,
+            version: 1,
+            rooms: HashSet::new(),
+            tx,
+        };
+        self.clients.insert(self.next_id, client);
+        self.next_id
+    }
+
+    fn disconnect(&mut self, id: ClientId) {
+        let client = self.clients.remove(&id);
+        if let Some(user) = client.and_then(|client| client.user) {
+            self.broadcast(
+                Some(id),
+                ServerEvent::Presence {
+                    user,
+                    online: false,
+                },
+            );
+        }
+    }
+
+    /// handles an event of the client, or says why it was ignored
+    fn handle(&mut self, id: ClientId, event: ClientEvent) -> Result<(), &'static str> {
+        let client = self.clients.get_mut(&id).expect("client not connected");
+        if let ClientEvent::Version(version) = event {
+            client.version = version;
+            return Ok(());
+        }
+        if let ClientEvent::Ident(Ident { user, last_seen }) = event {
+            let renamed = client.user.replace(user.clone());
+            client.rooms.insert(Room::default());
+            if let Some(user) = renamed {
+                self.broadcast(
+                    Some(id),
+                    ServerEvent::Presence {
+                        user,
+                        online: false,
+                    },
+                );
+            }
+            self.broadcast(Some(id), ServerEvent::Presence { user, online: true });
+            if let Some(last_seen) = last_seen {
+                self.replay(id, last_seen);
+            }
+            // tell the client who else is online
+            let users = self.users(|_, client| client.user.is_some());
+            for user in users
+                .into_iter()
+                .filter(|other| self.user(id) != Some(other))
+            {
+                self.send(id, ServerEvent::Presence { user, online: true });
+            }
+            return Ok(());
+        }
+        let user = client.user.clone().ok_or("event before ident")?;
+        match event {
+            ClientEvent::Join(room) => {
+                if client.rooms.insert(room.clone()) {
+                    let members = room.clone();
+                    let event = ServerEvent::Joined { user, room };
+                    self.send_to(event, |_, client| client.rooms.contains(&members));
+                }
+            }
+            ClientEvent::Leave(room) => {
+                if client.rooms.remove(&room) {
+                    let members = room.clone();
+                    let event = ServerEvent::Parted { user, room };
+                    self.send(id, event.clone());
+                    self.send_to(event, |_, client| client.rooms.contains(&members));
+                }
+            }
+            ClientEvent::ListRooms => {
+                let mut rooms: BTreeMap<&Room, BTreeSet<&User>> = BTreeMap::new();
+                for client in self.clients.values() {
+                    for room in &client.rooms {
+                        rooms.entry(room).or_default().extend(&client.user);
+                    }
+                }
+                let rooms = rooms
+                    .into_iter()
+                    .map(|(room, users)| RoomInfo {
+                        room: room.clone(),
+                        users: users.into_iter().cloned().collect(),
+                    })
+                    .collect();
+                self.send(id, ServerEvent::Rooms(rooms));
+            }
+            ClientEvent::Message(Message { text, to, .. }) => {
+                if let Recipient::Room(room) = &to {
+                    if !client.rooms.contains(room) {
+                        return Err("message to a room the client is not in");
+                    }
+                }
+                // messages are from whom the client identified as, as of now
+                let time = Timestamp::default();
+                let message = Message {
+                    id: None,
+                    from: user,
+                    text,
+                    time,
+                    to: to.clone(),
+                };
+                let (message, res) = self.history.push(message);
+                if let Err(err) = res {
+                    eprintln!("could not write message {:?} to history: {err}", message.id);
+                }
+                let event = ServerEvent::Message(message);
+                match to {
+                    Recipient::Room(room) => {
+                        self.send_to(event, |other, client| {
+                            other != id && client.rooms.contains(&room)
+                        });
+                    }
+                    Recipient::User(to) => {
+                        self.send_to(event, |other, client| {
+                            other != id && client.user.as_ref() == Some(&to)
+                        });
+                    }
+                }
+            }
+            ClientEvent::Codecs(_) => return Err("codecs after the first event"),
+            ClientEvent::Ident(_) | ClientEvent::Version(_) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// queues the messages after the id that the client can see, as it sees them now
+    fn replay(&self, id: ClientId, last_seen: MessageId) {
+        let client = &self.clients[&id];
+        let Some(user) = &client.user else {
+            return;
+        };
+        for message in self.history.since(last_seen) {
+            let visible = match &message.to {
+                Recipient::Room(room) => client.rooms.contains(room),
+                Recipient::User(to) => to == user,
+            };
+            if visible && message.from != *user {
+                self.send(id, ServerEvent::Message(message.clone()));
+            }
+        }
+    }
+
+    fn user(&self, id: ClientId) -> Option<&User> {
+        self.clients.get(&id)?.user.as_ref()
+    }
+
+    /// the users of the clients that match, sorted and without duplicates
+    fn users(&self, matches: impl Fn(ClientId, &Client) -> bool) -> BTreeSet<User> {
+        let clients = self
+            .clients
+            .iter()
+            .filter(|(id, client)| matches(**id, client));
+        clients
+            .filter_map(|(_, client)| client.user.clone())
+            .collect()
+    }
+
+    /// queues the event for every identified client but the one given
+    fn broadcast(&self, except: Option<ClientId>, event: ServerEvent) {
+        self.send_to(event, |id, client| {
+            Some(id) != except && client.user.is_some()
+        });
+    }
+
+    fn send_to(&self, event: ServerEvent, matches: impl Fn(ClientId, &Client) -> bool) {
+        let ids = self
+            .clients
+            .iter()
+            .filter(|(id, client)| matches(**id, client));
+        for (id, _) in ids {
+            self.send(*id, event.clone());
+        }
+    }
+
+    /// queues the event for the client, if it understands it
+    fn send(&self, id: ClientId, event: ServerEvent) {
+        let Some(client) = self.clients.get(&id) else {
+            return;
+        };
+        if event.version() > client.version {
+            return;
+        }
+        if let Err(TrySendError::Full(_)) = client.tx.try_send(event) {
+            let name = client.user.as_ref().map_or("", |user| &user.name);
+            eprintln!("client {id} ({name}) is too slow, dropping event");
+        }
+    }
+}
+
+struct Server {
+    config: protocol::ServerConfig,
+    clients: Arc<Mutex<Clients>>,
+}
+
+impl Server {
+    async fn start(config: protocol::ServerConfig) -> Result<()> {
+        let server = Self::new(config)?;
+        server.run().await
+    }
+
+    fn new(config: protocol::ServerConfig) -> Result<Self> {
+        let path = config.history_file.as_deref();
+        let history = History::open(config.history_len, path).map_err(|e| {
+            ServerError::CouldNotOpenHistory(path.unwrap_or(Path::new("")).into(), e)
+        })?;
+        Ok(Self {
+            config,
+            clients: Arc::new(Mutex::new(Clients::new(history))),
+        })
+    }
+
+    async fn run(&self) -> Result<()> {
+        let addr = &self.config.addr;
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| ServerError::CouldNotBind(addr.into(), e))?;
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let clients = self.clients.clone();
+            let buffer = CLIENT_BUFFER + self.config.history_len;
+            tokio::spawn(async move {
+                if let Err(err) = handle_client(stream, peer, buffer, clients).await {
+                    eprintln!("{peer}: {err:?}");
+                }
+            });
+        }
+    }
+}
+
+/// Reads the events of the client until it disconnects, while its writer
+/// sends it the events of the others
+async fn handle_client(
+    stream: TcpStream,
+    peer: SocketAddr,
+    buffer: usize,
+    clients: Arc<Mutex<Clients>>,
+) -> Result<()> {
+    let (client_rx, client_tx) = stream.into_split();
+    let mut reader = FrameReader::new(client_rx, AnyCodec::JsonLines);
+    let mut writer = FrameWriter::new(client_tx, AnyCodec::JsonLines);
+    let mut first = reader.read_frame().await?;
+    // a client that wants another codec asks first, and speaks it after the answer
+    let asked = first.as_ref().map(|frame| reader.codec().decode(frame));
+    if let Some(Ok(ClientEvent::Codecs(names))) = asked {
+        let codec = names.iter().find_map(|name| AnyCodec::from_name(name));
+        let codec = codec.unwrap_or_default();
+        writer
+            .write(&ServerEvent::Codec(codec.name().into()))
+            .await?;
+        reader.set_codec(codec);
+        writer.set_codec(codec);
+        first = None;
+    }
+    let (tx, rx) = mpsc::channel(buffer);
+    // the writer stops once the client is forgotten and its queue is drained
+    tokio::spawn(write_client(rx, writer));
+    let id = clients.lock().expect("lock fail").connect(tx);
+    if let Some(frame) = first {
+        handle_frame(id, peer, reader.codec(), &frame, &clients);
+    }
+    let res = read_client(id, peer, reader, &clients).await;
+    clients.lock().expect("lock fail").disconnect(id);
+    res
+}
+
+async fn read_client(
+    id: ClientId,
+    peer: SocketAddr,
+    mut reader: FrameReader<OwnedReadHalf>,
+    clients: &Mutex<Clients>,
+) -> Result<()> {
+    while let Some(frame) = reader.read_frame().await? {
+        handle_frame(id, peer, reader.codec(), &frame, clients);
+    }
+    Ok(())
+}
+
+fn handle_frame(
+    id: ClientId,
+    peer: SocketAddr,
+    codec: AnyCodec,
+    frame: &[u8],
+    clients: &Mutex<Clients>,
+) {
+    let event = match codec.decode::<ClientEvent>(frame) {
+        Ok(event) => event,
+        Err(err) => {
+            eprintln!("{peer}: ignoring bad event: {err}");
+            return;
+        }
+    };
+    if let Err(err) = clients.lock().expect("lock fail").handle(id, event) {
+        eprintln!("{peer}: ignoring {err}");
+    }
+}
+
+async fn write_client(
+    mut rx: Receiver<ServerEvent>,
+    mut writer: FrameWriter<OwnedWriteHalf>,
+) -> Result<()> {
+    while let Some(mut event) = rx.recv().await {
+        // events that are already queued go out in the same write
+        loop {
+            writer.queue(&event)?;
+            match rx.try_recv() {
+                Ok(next) => event = next,
+                Err(_) => break,
+            }
+        }
+        writer.flush().await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_harness() {
+        protocol::verify_server(Server::start).await;
+    }
+}
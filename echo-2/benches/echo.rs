@@ -0,0 +1,91 @@
+//! Compares the servers under many concurrent connections, each echoing a few lines:
+//!
+//! ```text
+//! cargo bench --bench echo -- [connections] [lines]
+//! ```
+//!
+//! Every connection is open before any of them send, and the threads server gets a worker for
+//! each. Each connection takes two file descriptors, so `ulimit -n` may need raising.
+
+use clap::Parser;
+use echo_2::{Args, Runner};
+use std::{
+    env,
+    net::SocketAddr,
+    thread,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+fn main() {
+    // cargo passes --bench
+    let mut args = env::args().skip(1).filter(|arg| !arg.starts_with("--"));
+    let connections: usize = args
+        .next()
+        .map_or(2000, |n| n.parse().expect("connections"));
+    let lines: usize = args.next().map_or(10, |n| n.parse().expect("lines"));
+    for runtime in ["threads", "tokio"] {
+        let n = connections.to_string();
+        let args = Args::parse_from([
+            "echo-2",
+            "-p",
+            "0",
+            "--runtime",
+            runtime,
+            "--workers",
+            &n,
+            "--max-connections",
+            &n,
+            // connecting thousands of clients can outlast the default
+            "--idle-timeout",
+            "60m",
+        ]);
+        let (runner, shutdown) = Runner::new(args).expect("could not start server");
+        let addr = runner.addr;
+        let server = thread::spawn(move || runner.run().expect("server failed"));
+        let elapsed = load(addr, connections, lines);
+        shutdown.shutdown();
+        server.join().unwrap();
+        let rate = (connections * lines) as f64 / elapsed.as_secs_f64();
+        println!(
+            "{runtime:>8}: {connections} connections x {lines} lines in {elapsed:.2?} ({rate:.0} lines/s)"
+        );
+    }
+}
+
+/// connects the clients, then has them all echo their lines at once
+fn load(addr: SocketAddr, connections: usize, lines: usize) -> Duration {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let mut streams = Vec::with_capacity(connections);
+        // one at a time, so as not to overflow the listen backlog
+        for _ in 0..connections {
+            streams.push(TcpStream::connect(addr).await.expect("could not connect"));
+        }
+        let start = Instant::now();
+        let clients = streams
+            .into_iter()
+            .map(|stream| tokio::spawn(client(stream, lines)));
+        let clients = clients.collect::<Vec<_>>();
+        for client in clients {
+            client.await.unwrap();
+        }
+        start.elapsed()
+    })
+}
+
+async fn client(stream: TcpStream, lines: usize) {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut echo = String::new();
+    for i in 0..lines {
+        let line = format!("line {i}\n");
+        writer.write_all(line.as_bytes()).await.unwrap();
+        echo.clear();
+        reader.read_line(&mut echo).await.unwrap();
+        assert_eq!(echo, line);
+    }
+}
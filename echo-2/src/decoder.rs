@@ -0,0 +1,40 @@
+use crate::Mode;
+use std::str::{self, Utf8Error};
+
+/// Splits the bytes read from a connection into what is echoed. It does no IO itself, so the
+/// blocking and the tokio servers frame input the same way.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    buf: Vec<u8>,
+}
+
+impl Decoder {
+    /// Adds bytes that were read
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Returns the next complete line, with its newline, if there is one
+    pub fn next_line(&mut self) -> Result<Option<String>, Utf8Error> {
+        let Some(n) = self.buf.iter().position(|b| b == &b'\n') else {
+            return Ok(None);
+        };
+        let res = str::from_utf8(&self.buf[0..=n])?.to_string();
+        self.buf.drain(0..=n);
+        Ok(Some(res))
+    }
+
+    /// Returns all of the bytes fed so far
+    pub fn take(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buf)
+    }
+
+    /// Returns the next thing to echo in the mode: a complete line, or whatever bytes there are
+    pub fn decode(&mut self, mode: Mode) -> Result<Option<Vec<u8>>, Utf8Error> {
+        match mode {
+            Mode::Line => Ok(self.next_line()?.map(String::into_bytes)),
+            Mode::Raw if self.buf.is_empty() => Ok(None),
+            Mode::Raw => Ok(Some(self.take())),
+        }
+    }
+}
@@ -0,0 +1,737 @@
+use anyhow::{anyhow, Context, Result};
+use decoder::Decoder;
+use pool::Pool;
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, UdpSocket},
+    str::Utf8Error,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+pub mod decoder;
+mod pool;
+mod tokio_runner;
+
+/// how long a read or an accept waits before checking whether to stop
+const TICK: Duration = Duration::from_millis(200);
+
+/// what a connection over the limit is told before it is closed
+const BUSY: &str = "server busy, try again later\n";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tracing_test::traced_test;
+
+    // every test runs against both servers
+    const RUNTIMES: [&str; 2] = ["threads", "tokio"];
+
+    fn args(runtime: &str, extra: &[&str]) -> Args {
+        let args = ["echo-2", "-p", "0", "--runtime", runtime];
+        Args::parse_from(args.iter().chain(extra))
+    }
+
+    // reads until the stream ends, or times out
+    fn read_to_end(stream: &mut TcpStream) -> String {
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut buf = String::new();
+        stream.read_to_string(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_decoder() {
+        let mut decoder = Decoder::default();
+        decoder.feed(b"Hello");
+        assert_eq!(decoder.decode(Mode::Line).unwrap(), None);
+        decoder.feed(b", World!\nfoo\nbar");
+        assert_eq!(
+            decoder.decode(Mode::Line).unwrap().unwrap(),
+            b"Hello, World!\n"
+        );
+        assert_eq!(decoder.decode(Mode::Line).unwrap().unwrap(), b"foo\n");
+        assert_eq!(decoder.decode(Mode::Line).unwrap(), None);
+        // raw takes whatever is left, lines or not
+        assert_eq!(decoder.decode(Mode::Raw).unwrap().unwrap(), b"bar");
+        assert_eq!(decoder.decode(Mode::Raw).unwrap(), None);
+        decoder.feed(b"\xff\n");
+        assert!(decoder.decode(Mode::Line).is_err());
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_echo_iter() {
+        for runtime in RUNTIMES {
+            let (runner, stop) = Runner::new(args(runtime, &[])).unwrap();
+            let addr = runner.addr;
+            let jh = thread::spawn(move || {
+                runner.run().expect("runner failed");
+            });
+            let stream = TcpStream::connect(addr).unwrap();
+            let timeout = Some(Duration::from_millis(200));
+            let peer_addr = stream.peer_addr().unwrap();
+            let local_addr = stream.local_addr().unwrap();
+            debug!("Connected to {} from {}", peer_addr, local_addr);
+            let mut stream = Stream::new(stream, timeout).unwrap();
+            stream.write("Hello").unwrap();
+            stream.write(", World!\n").unwrap();
+            assert_eq!(
+                stream
+                    .iter()
+                    .next()
+                    .ok_or(anyhow!("expected value"))
+                    .unwrap()
+                    .unwrap(),
+                "Hello, World!\n"
+            );
+            stream.write("foobar\n").unwrap();
+            assert_eq!(
+                stream
+                    .iter()
+                    .next()
+                    .ok_or(anyhow!("expected value"))
+                    .unwrap()
+                    .unwrap(),
+                "foobar\n"
+            );
+            stop.shutdown();
+            jh.join().unwrap();
+        }
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_echo() {
+        for runtime in RUNTIMES {
+            let (runner, stop) = Runner::new(args(runtime, &[])).unwrap();
+            let addr = runner.addr;
+            let jh = thread::spawn(move || {
+                runner.run().expect("runner failed");
+            });
+            let stream = TcpStream::connect(addr).unwrap();
+            let timeout = Some(Duration::from_millis(200));
+            let peer_addr = stream.peer_addr().unwrap();
+            let local_addr = stream.local_addr().unwrap();
+            debug!("Connected to {} from {}", peer_addr, local_addr);
+            let mut stream = Stream::new(stream, timeout).unwrap();
+            stream.write("Hello").unwrap();
+            stream.write(", World!\n").unwrap();
+            loop {
+                stream.poll().unwrap();
+                if let Some(s) = stream.next().unwrap() {
+                    assert_eq!(s, String::from("Hello, World!\n"));
+                    break;
+                }
+            }
+            stream.write("foobar\n").unwrap();
+            loop {
+                stream.poll().unwrap();
+                if let Some(s) = stream.next().unwrap() {
+                    assert_eq!(s, String::from("foobar\n"));
+                    break;
+                }
+            }
+            stop.shutdown();
+            jh.join().unwrap();
+        }
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_max_connections() {
+        for runtime in RUNTIMES {
+            let (runner, stop) = Runner::new(args(runtime, &["--max-connections", "1"])).unwrap();
+            let addr = runner.addr;
+            let jh = thread::spawn(move || {
+                runner.run().expect("runner failed");
+            });
+            let mut first = Stream::new(TcpStream::connect(addr).unwrap(), Some(TICK)).unwrap();
+            first.write("first\n").unwrap();
+            assert_eq!(first.iter().next().unwrap().unwrap(), "first\n");
+            let mut second = TcpStream::connect(addr).unwrap();
+            assert_eq!(read_to_end(&mut second), BUSY);
+            // the first is still served
+            first.write("still here\n").unwrap();
+            assert_eq!(first.iter().next().unwrap().unwrap(), "still here\n");
+            stop.shutdown();
+            jh.join().unwrap();
+        }
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_max_connections_workers() {
+        let extra = ["--workers", "1", "--max-connections", "4"];
+        let (runner, stop) = Runner::new(args("threads", &extra)).unwrap();
+        let addr = runner.addr;
+        let jh = thread::spawn(move || {
+            runner.run().expect("runner failed");
+        });
+        let mut first = Stream::new(TcpStream::connect(addr).unwrap(), Some(TICK)).unwrap();
+        first.write("first\n").unwrap();
+        assert_eq!(first.iter().next().unwrap().unwrap(), "first\n");
+        // there is no worker left to serve it, so it is not left waiting for one
+        let mut second = TcpStream::connect(addr).unwrap();
+        assert_eq!(read_to_end(&mut second), BUSY);
+        drop(first);
+        // the worker is free again once it notices the first is gone
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut third = loop {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.set_read_timeout(Some(TICK * 2)).unwrap();
+            let mut buf = [0; 64];
+            match stream.read(&mut buf) {
+                Ok(_) if Instant::now() < deadline => thread::sleep(TICK),
+                Ok(n) => panic!("still busy: {:?}", String::from_utf8_lossy(&buf[..n])),
+                // a served connection says nothing until it is sent something
+                Err(_) => break Stream::new(stream, Some(TICK)).unwrap(),
+            }
+        };
+        third.write("third\n").unwrap();
+        assert_eq!(third.iter().next().unwrap().unwrap(), "third\n");
+        stop.shutdown();
+        jh.join().unwrap();
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_idle_timeout() {
+        for runtime in RUNTIMES {
+            let (runner, stop) = Runner::new(args(runtime, &["--idle-timeout", "300ms"])).unwrap();
+            let addr = runner.addr;
+            let jh = thread::spawn(move || {
+                runner.run().expect("runner failed");
+            });
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let start = Instant::now();
+            assert_eq!(read_to_end(&mut stream), "");
+            assert!(start.elapsed() >= Duration::from_millis(300));
+            stop.shutdown();
+            jh.join().unwrap();
+        }
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_shutdown_drains() {
+        for runtime in RUNTIMES {
+            let (runner, stop) = Runner::new(args(runtime, &[])).unwrap();
+            let addr = runner.addr;
+            let jh = thread::spawn(move || {
+                runner.run().expect("runner failed");
+            });
+            let mut stream = Stream::new(TcpStream::connect(addr).unwrap(), Some(TICK)).unwrap();
+            stream.write("hello\n").unwrap();
+            assert_eq!(stream.iter().next().unwrap().unwrap(), "hello\n");
+            stream.stream.write_all(b"last words\n").unwrap();
+            stop.shutdown();
+            // the runner waits for the connection, which echoes what it was sent and closes
+            jh.join().unwrap();
+            assert_eq!(read_to_end(&mut stream.stream), "last words\n");
+        }
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_raw_mode() {
+        for runtime in RUNTIMES {
+            let (runner, stop) = Runner::new(args(runtime, &["--mode", "raw"])).unwrap();
+            let addr = runner.addr;
+            let jh = thread::spawn(move || {
+                runner.run().expect("runner failed");
+            });
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(b"no newline").unwrap();
+            let mut buf = [0; 10];
+            stream.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"no newline");
+            stop.shutdown();
+            jh.join().unwrap();
+        }
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_udp() {
+        for runtime in RUNTIMES {
+            let args = args(runtime, &["--protocol", "tcp", "--protocol", "udp"]);
+            let (runner, stop) = Runner::new(args).unwrap();
+            let addr = runner.addr;
+            let jh = thread::spawn(move || {
+                runner.run().expect("runner failed");
+            });
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            socket.set_read_timeout(Some(TICK)).unwrap();
+            let mut buf = [0; 64];
+            // a datagram can be lost, even locally
+            let n = (0..25)
+                .find_map(|_| {
+                    socket.send_to(b"Hello\nWorld", addr).unwrap();
+                    socket.recv(&mut buf).ok()
+                })
+                .expect("no echo");
+            assert_eq!(&buf[..n], b"Hello\nWorld");
+            // and tcp is echoed on the same port
+            let mut stream = Stream::new(TcpStream::connect(addr).unwrap(), Some(TICK)).unwrap();
+            stream.write("tcp too\n").unwrap();
+            assert_eq!(stream.iter().next().unwrap().unwrap(), "tcp too\n");
+            stop.shutdown();
+            jh.join().unwrap();
+        }
+    }
+}
+
+#[derive(clap::Parser, Debug, Clone)]
+pub struct Args {
+    #[arg(short, default_value_t = 8000)]
+    pub port: u32,
+
+    /// the protocols to echo, on the same port.
+    #[arg(long = "protocol", value_enum, default_values_t = [Protocol::Tcp])]
+    pub protocols: Vec<Protocol>,
+
+    /// whether tcp connections are echoed whole lines or bytes as they arrive.
+    #[arg(long, value_enum, default_value_t = Mode::Line)]
+    pub mode: Mode,
+
+    /// what serves the connections.
+    #[arg(long, value_enum, default_value_t = Runtime::Threads)]
+    pub runtime: Runtime,
+
+    /// the threads that serve tcp connections, with the threads runtime. each holds a
+    /// connection while it is open, so connections beyond them are told the server is busy.
+    #[arg(long, default_value_t = 8)]
+    pub workers: usize,
+
+    /// tcp connections beyond this are told the server is busy and closed. the threads runtime
+    /// serves no more than its workers.
+    #[arg(long, default_value_t = 64)]
+    pub max_connections: usize,
+
+    /// how long a tcp connection may send nothing before it is closed (e.g. 500ms, 30s, 5m).
+    #[arg(long, default_value = "30s", value_parser = parse_duration)]
+    pub idle_timeout: Duration,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    /// each datagram is echoed back whole, whatever the mode
+    Udp,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// echo each line once it is complete
+    Line,
+    /// echo bytes as soon as they are read
+    Raw,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Runtime {
+    /// a pool of threads, each serving one connection at a time with blocking io
+    Threads,
+    /// tasks on a tokio runtime
+    Tokio,
+}
+
+/// parses durations like 500ms, 30s or 5m
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let bad = || format!("{s:?} is not a duration like 500ms, 30s or 5m");
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (n, unit) = s.split_at(digits);
+    let n = n.parse::<u64>().map_err(|_| bad())?;
+    let duration = match unit {
+        "ms" => Duration::from_millis(n),
+        "s" => Duration::from_secs(n),
+        "m" => Duration::from_secs(n * 60),
+        _ => return Err(bad()),
+    };
+    if duration.is_zero() {
+        return Err(String::from("the duration must be more than zero"));
+    }
+    Ok(duration)
+}
+
+/// Tells a runner to stop accepting, and its connections to echo what they were sent and close
+#[derive(Clone, Default)]
+pub struct Shutdown(CancellationToken);
+
+impl Shutdown {
+    pub fn shutdown(&self) {
+        self.0.cancel();
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.0.is_cancelled()
+    }
+
+    async fn wait(&self) {
+        self.0.cancelled().await;
+    }
+}
+
+/// Counts a tcp connection for as long as it is open
+struct Connection(Arc<AtomicUsize>);
+
+impl Connection {
+    /// counts the connection, unless there are as many as allowed already
+    fn open(count: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                (n < max).then_some(n + 1)
+            })
+            .ok()?;
+        Some(Self(count.clone()))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub struct Runner {
+    pub addr: SocketAddr,
+    listener: Option<TcpListener>,
+    udp: Option<UdpSocket>,
+    args: Args,
+    connections: Arc<AtomicUsize>,
+    shutdown: Shutdown,
+}
+
+impl Runner {
+    pub fn new(args: Args) -> Result<(Self, Shutdown)> {
+        let mut bind = format!("0.0.0.0:{}", args.port);
+        let listener = if args.protocols.contains(&Protocol::Tcp) {
+            let listener = TcpListener::bind(&bind).context("could not bind")?;
+            // udp shares the port that tcp was given
+            bind = listener.local_addr()?.to_string();
+            Some(listener)
+        } else {
+            None
+        };
+        let udp = if args.protocols.contains(&Protocol::Udp) {
+            Some(UdpSocket::bind(&bind).context("could not bind udp")?)
+        } else {
+            None
+        };
+        let addr = match (&listener, &udp) {
+            (Some(listener), _) => listener.local_addr()?,
+            (None, Some(udp)) => udp.local_addr()?,
+            (None, None) => return Err(anyhow!("no protocols to echo")),
+        };
+        let shutdown = Shutdown::default();
+        let res = Self {
+            addr,
+            listener,
+            udp,
+            args,
+            connections: Arc::new(AtomicUsize::new(0)),
+            shutdown: shutdown.clone(),
+        };
+        Ok((res, shutdown))
+    }
+
+    /// Echoes until shut down, then waits for the connections to drain
+    pub fn run(&self) -> Result<()> {
+        match self.args.runtime {
+            Runtime::Threads => self.run_threads(),
+            Runtime::Tokio => tokio::runtime::Runtime::new()?.block_on(self.run_tokio()),
+        }
+    }
+
+    fn run_threads(&self) -> Result<()> {
+        thread::scope(|scope| {
+            let udp = self
+                .udp
+                .as_ref()
+                .map(|udp| scope.spawn(|| self.run_udp(udp)));
+            let res = self.listener.as_ref().map_or(Ok(()), |l| self.run_tcp(l));
+            let udp_res = udp.map_or(Ok(()), |udp| udp.join().expect("udp panicked"));
+            res.and(udp_res)
+        })
+    }
+
+    fn run_tcp(&self, listener: &TcpListener) -> Result<()> {
+        let pool = Pool::new(self.args.workers);
+        let res = self.accept(listener, &pool);
+        // whether it was asked to or not, the runner is stopping, so connections finish up
+        self.shutdown.shutdown();
+        debug!(
+            "Draining {} connections...",
+            self.connections.load(Ordering::Relaxed)
+        );
+        drop(pool);
+        res
+    }
+
+    fn accept(&self, listener: &TcpListener, pool: &Pool) -> Result<()> {
+        listener.set_nonblocking(true)?;
+        loop {
+            if self.shutdown.is_shutdown() {
+                debug!("Stopping runner...");
+                return Ok(());
+            }
+            let (stream, addr) = match listener.accept() {
+                Ok(res) => res,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(10));
+                    continue;
+                }
+                Err(err) => {
+                    return Err(err.into());
+                }
+            };
+            stream.set_nonblocking(false)?;
+            // a connection that waited for a worker would get no answer until one is free
+            let max = self.args.max_connections.min(self.args.workers.max(1));
+            let Some(connection) = Connection::open(&self.connections, max) else {
+                info!("Rejecting {addr}: {max} connections already");
+                Self::reject(stream);
+                continue;
+            };
+            info!("Client addr: {addr}");
+            let (mode, idle_timeout) = (self.args.mode, self.args.idle_timeout);
+            let shutdown = self.shutdown.clone();
+            pool.execute(move || {
+                report(Self::handle(stream, mode, idle_timeout, &shutdown));
+                drop(connection);
+            });
+        }
+    }
+
+    fn reject(mut stream: TcpStream) {
+        // a client that does not read is not waited for
+        let _ = stream.set_write_timeout(Some(TICK));
+        let _ = stream.write_all(BUSY.as_bytes());
+    }
+
+    fn handle(
+        stream: TcpStream,
+        mode: Mode,
+        idle_timeout: Duration,
+        shutdown: &Shutdown,
+    ) -> Result<()> {
+        // reads wait no longer than a tick, so that a shutdown is noticed
+        let mut stream = Stream::new(stream, Some(idle_timeout.min(TICK)))?;
+        while !shutdown.is_shutdown() {
+            if stream.idle() > idle_timeout {
+                debug!("Closing idle connection");
+                return Ok(());
+            }
+            stream.poll()?;
+            Self::echo(&mut stream, mode)?;
+        }
+        // drain what already arrived before closing
+        stream.poll()?;
+        Self::echo(&mut stream, mode)?;
+        stream.flush()
+    }
+
+    fn echo(stream: &mut Stream, mode: Mode) -> Result<()> {
+        while let Some(bytes) = stream
+            .decoder
+            .decode(mode)
+            .map_err(StreamError::InvalidUTF8)?
+        {
+            stream.write(bytes)?;
+        }
+        Ok(())
+    }
+
+    fn run_udp(&self, socket: &UdpSocket) -> Result<()> {
+        socket.set_read_timeout(Some(TICK))?;
+        let mut buf = vec![0_u8; 65536];
+        while !self.shutdown.is_shutdown() {
+            let (n, addr) = match socket.recv_from(&mut buf) {
+                Ok(res) => res,
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    continue;
+                }
+                // such as a peer that went away, which is no reason to stop
+                Err(err) => {
+                    debug!("UDP: {err}");
+                    continue;
+                }
+            };
+            debug!("Read {n} bytes from {addr}");
+            if let Err(err) = socket.send_to(&buf[..n], addr) {
+                debug!("UDP to {addr}: {err}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// logs how a connection ended. the client closing it is the usual end rather than an error.
+fn report(res: Result<()>) {
+    match res {
+        Err(err) if matches!(err.downcast_ref(), Some(StreamError::Closed)) => {
+            debug!("Client closed");
+        }
+        Err(err) => eprintln!("Handle: {err:?}"),
+        Ok(()) => {}
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum StreamError {
+    #[error("Stream closed")]
+    Closed,
+
+    #[error("IO error: {0}")]
+    IO(#[from] io::Error),
+
+    #[error("Invalid UTF8: {0}")]
+    InvalidUTF8(#[from] Utf8Error),
+}
+
+#[cfg(test)]
+struct StreamIterator<'a> {
+    stream: &'a mut Stream,
+    deadline: Instant,
+}
+
+#[cfg(test)]
+impl<'a> Iterator for StreamIterator<'a> {
+    type Item = Result<String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(err) = self.stream.poll() {
+            return Some(Err(err));
+        }
+        match self.stream.next() {
+            Ok(Some(res)) => Some(Ok(res)),
+            Err(err) => Some(Err(err)),
+            Ok(None) => {
+                if Instant::now() > self.deadline {
+                    Some(Err(anyhow!("no data")))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// A non-blocking wrapper around `TcpStream` that buffers input and output.
+struct Stream {
+    stream: TcpStream,
+    tmp: Vec<u8>,
+    decoder: Decoder,
+    write: Vec<u8>,
+    /// when something was last read
+    read_at: Instant,
+}
+
+impl Stream {
+    fn new(stream: TcpStream, timeout: Option<Duration>) -> Result<Stream> {
+        let tmp = vec![0_u8; 4096];
+        let decoder = Decoder::default();
+        let write = vec![];
+        let mut res = Stream {
+            stream,
+            tmp,
+            decoder,
+            write,
+            read_at: Instant::now(),
+        };
+        res.set_timeout(timeout)?;
+        Ok(res)
+    }
+
+    #[cfg(test)]
+    fn iter(&mut self) -> StreamIterator<'_> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        StreamIterator {
+            stream: self,
+            deadline,
+        }
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.stream.set_read_timeout(timeout)?;
+        self.stream.set_write_timeout(timeout)?;
+        Ok(())
+    }
+
+    // writes the supplied data to the buffer but does not send it immediately
+    fn write(&mut self, input: impl AsRef<[u8]>) -> Result<()> {
+        self.write.write_all(input.as_ref()).map_err(|e| e.into())
+    }
+
+    // returns the next line of input read, if it exists.
+    #[cfg(test)]
+    fn next(&mut self) -> Result<Option<String>> {
+        let line = self.decoder.next_line().map_err(StreamError::InvalidUTF8)?;
+        Ok(line)
+    }
+
+    // how long since anything was read
+    fn idle(&self) -> Duration {
+        self.read_at.elapsed()
+    }
+
+    // sends everything that was written, waiting as long as the timeout for each write
+    fn flush(&mut self) -> Result<()> {
+        self.stream.write_all(&self.write)?;
+        self.write.clear();
+        Ok(())
+    }
+
+    // attempts to read and write once. should be called in a loop.
+    fn poll(&mut self) -> Result<()> {
+        if !self.write.is_empty() {
+            if let Some(n) = self
+                .stream
+                .write(&self.write)
+                .map_or_else(Self::map_poll_err, Self::map_poll_n)?
+            {
+                debug!("Wrote {n} bytes");
+                self.write.drain(0..n);
+            }
+        }
+        if let Some(n) = self
+            .stream
+            .read(&mut self.tmp)
+            .map_or_else(Self::map_poll_err, Self::map_poll_n)?
+        {
+            debug!("Read {n} bytes");
+            self.read_at = Instant::now();
+            self.decoder.feed(&self.tmp[0..n]);
+        }
+        Ok(())
+    }
+
+    fn map_poll_err(err: io::Error) -> Result<Option<usize>> {
+        match err.kind() {
+            io::ErrorKind::WouldBlock => Ok(None),
+            _ => Err(StreamError::IO(err).into()),
+        }
+    }
+
+    fn map_poll_n(n: usize) -> Result<Option<usize>> {
+        if n == 0 {
+            Err(StreamError::Closed.into())
+        } else {
+            Ok(Some(n))
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::{
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A fixed number of threads that run jobs in the order they were queued. Dropping the pool
+/// waits for the queued jobs to finish.
+pub struct Pool {
+    tx: Option<mpsc::Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Pool {
+    pub fn new(size: usize) -> Self {
+        let (tx, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        let workers = (0..size.max(1))
+            .map(|i| {
+                let rx = rx.clone();
+                thread::Builder::new()
+                    .name(format!("worker-{i}"))
+                    .spawn(move || loop {
+                        // the lock is only held while waiting for a job, not while running it
+                        let job = rx.lock().expect("lock fail").recv();
+                        match job {
+                            Ok(job) => job(),
+                            Err(_) => break,
+                        }
+                    })
+                    .expect("could not spawn worker")
+            })
+            .collect();
+        Self {
+            tx: Some(tx),
+            workers,
+        }
+    }
+
+    /// Queues the job for the next free worker
+    pub fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        let tx = self.tx.as_ref().expect("pool stopped");
+        tx.send(Box::new(job)).expect("workers stopped");
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        // workers stop once the queue is closed and empty
+        self.tx.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{
+    decoder::Decoder, report, Connection, Mode, Runner, Shutdown, StreamError, BUSY, TICK,
+};
+use anyhow::Result;
+use std::{io, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, UdpSocket},
+    time::timeout,
+};
+use tokio_util::task::TaskTracker;
+use tracing::{debug, info};
+
+impl Runner {
+    /// Echoes with tasks on the current tokio runtime until shut down, then waits for the
+    /// connections to drain
+    pub async fn run_tokio(&self) -> Result<()> {
+        let udp = match &self.udp {
+            Some(udp) => {
+                udp.set_nonblocking(true)?;
+                let udp = UdpSocket::from_std(udp.try_clone()?)?;
+                Some(tokio::spawn(run_udp(udp, self.shutdown.clone())))
+            }
+            None => None,
+        };
+        let res = match &self.listener {
+            Some(listener) => {
+                listener.set_nonblocking(true)?;
+                let listener = TcpListener::from_std(listener.try_clone()?)?;
+                self.run_tcp_tokio(listener).await
+            }
+            None => Ok(()),
+        };
+        let udp_res = match udp {
+            Some(udp) => udp.await?,
+            None => Ok(()),
+        };
+        res.and(udp_res)
+    }
+
+    async fn run_tcp_tokio(&self, listener: TcpListener) -> Result<()> {
+        let tracker = TaskTracker::new();
+        let res = self.accept_tokio(listener, &tracker).await;
+        // whether it was asked to or not, the runner is stopping, so connections finish up
+        self.shutdown.shutdown();
+        debug!("Draining {} connections...", tracker.len());
+        tracker.close();
+        tracker.wait().await;
+        res
+    }
+
+    async fn accept_tokio(&self, listener: TcpListener, tracker: &TaskTracker) -> Result<()> {
+        loop {
+            let (stream, addr) = tokio::select! {
+                res = listener.accept() => res?,
+                _ = self.shutdown.wait() => {
+                    debug!("Stopping runner...");
+                    return Ok(());
+                }
+            };
+            let max = self.args.max_connections;
+            let Some(connection) = Connection::open(&self.connections, max) else {
+                info!("Rejecting {addr}: {max} connections already");
+                tracker.spawn(reject(stream));
+                continue;
+            };
+            info!("Client addr: {addr}");
+            let (mode, idle_timeout) = (self.args.mode, self.args.idle_timeout);
+            let shutdown = self.shutdown.clone();
+            tracker.spawn(async move {
+                report(handle(stream, mode, idle_timeout, &shutdown).await);
+                drop(connection);
+            });
+        }
+    }
+}
+
+async fn reject(mut stream: TcpStream) {
+    // a client that does not read is not waited for
+    let _ = timeout(TICK, stream.write_all(BUSY.as_bytes())).await;
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    mode: Mode,
+    idle_timeout: Duration,
+    shutdown: &Shutdown,
+) -> Result<()> {
+    let mut decoder = Decoder::default();
+    let mut buf = vec![0_u8; 4096];
+    loop {
+        let read = tokio::select! {
+            read = timeout(idle_timeout, stream.read(&mut buf)) => read,
+            _ = shutdown.wait() => break,
+        };
+        let Ok(n) = read else {
+            debug!("Closing idle connection");
+            return Ok(());
+        };
+        let n = n?;
+        if n == 0 {
+            return Err(StreamError::Closed.into());
+        }
+        debug!("Read {n} bytes");
+        decoder.feed(&buf[0..n]);
+        echo(&mut stream, &mut decoder, mode).await?;
+    }
+    // drain what already arrived before closing
+    if let Ok(n) = timeout(TICK, stream.read(&mut buf)).await {
+        decoder.feed(&buf[0..n?]);
+    }
+    echo(&mut stream, &mut decoder, mode).await
+}
+
+async fn echo(stream: &mut TcpStream, decoder: &mut Decoder, mode: Mode) -> Result<()> {
+    while let Some(bytes) = decoder.decode(mode).map_err(StreamError::InvalidUTF8)? {
+        // a peer that stops reading would otherwise hold the connection open forever
+        timeout(TICK, stream.write_all(&bytes))
+            .await
+            .map_err(io::Error::from)??;
+    }
+    Ok(())
+}
+
+async fn run_udp(socket: UdpSocket, shutdown: Shutdown) -> Result<()> {
+    let mut buf = vec![0_u8; 65536];
+    loop {
+        let res = tokio::select! {
+            res = socket.recv_from(&mut buf) => res,
+            _ = shutdown.wait() => return Ok(()),
+        };
+        let (n, addr) = match res {
+            Ok(res) => res,
+            // such as a peer that went away, which is no reason to stop
+            Err(err) => {
+                debug!("UDP: {err}");
+                continue;
+            }
+        };
+        debug!("Read {n} bytes from {addr}");
+        if let Err(err) = socket.send_to(&buf[0..n], addr).await {
+            debug!("UDP to {addr}: {err}");
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use clap::ValueEnum;
+use regex::Regex;
+use std::borrow::Cow;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// how many lines are looked at to detect the format of a history
+const DETECT_LINES: usize = 100;
+
+/// the shells whose history can be read
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Fish,
+    Zsh,
+}
+
+impl Shell {
+    /// the login shell, from $SHELL
+    pub fn current() -> Option<Shell> {
+        let shell = env::var("SHELL").ok()?;
+        match Path::new(&shell).file_name()?.to_str()? {
+            "bash" => Some(Shell::Bash),
+            "fish" => Some(Shell::Fish),
+            "zsh" => Some(Shell::Zsh),
+            _ => None,
+        }
+    }
+
+    /// where the shell keeps its history by default
+    pub fn history_path(self, home: &Path) -> PathBuf {
+        match self {
+            Shell::Bash => home.join(".bash_history"),
+            Shell::Fish => home.join(".local/share/fish/fish_history"),
+            Shell::Zsh => home.join(".zsh_history"),
+        }
+    }
+
+    /// guesses the shell from the format of the history, which is only
+    /// possible if it records when commands were run
+    pub fn detect(history: &[u8]) -> Option<Shell> {
+        let zsh = Regex::new(r"^: *\d+:\d+;").unwrap();
+        let bash = Regex::new(r"^#\d+$").unwrap();
+        String::from_utf8_lossy(history)
+            .lines()
+            .take(DETECT_LINES)
+            .find_map(|line| {
+                if line.starts_with("- cmd: ") {
+                    Some(Shell::Fish)
+                } else if zsh.is_match(line) {
+                    Some(Shell::Zsh)
+                } else if bash.is_match(line) {
+                    Some(Shell::Bash)
+                } else {
+                    None
+                }
+            })
+    }
+}
+
+/// a command from the history, with when it was run if the format records it
+pub struct Entry {
+    pub cmd: String,
+    pub when: Option<i64>, // seconds since the epoch
+}
+
+/// parses the history in the format of the shell. bytes that are not utf-8
+/// are replaced rather than failing the whole history.
+pub fn parse(shell: Shell, history: &[u8]) -> Vec<Entry> {
+    match shell {
+        Shell::Bash => parse_bash(&String::from_utf8_lossy(history)),
+        Shell::Fish => parse_fish(&String::from_utf8_lossy(history)),
+        Shell::Zsh => parse_zsh(&String::from_utf8_lossy(&unmetafy(history))),
+    }
+}
+
+/// one command per line, preceded by a `#<epoch>` line if HISTTIMEFORMAT is set
+fn parse_bash(history: &str) -> Vec<Entry> {
+    let mut when = None;
+    let mut entries = vec![];
+    for line in history.lines() {
+        if let Some(epoch) = line.strip_prefix('#').and_then(|e| e.parse().ok()) {
+            when = Some(epoch);
+        } else if !line.trim().is_empty() {
+            entries.push(Entry {
+                cmd: line.to_string(),
+                when: when.take(),
+            });
+        }
+    }
+    entries
+}
+
+/// a YAML-like list of `- cmd: <cmd>` items, with `when: <epoch>` and paths
+/// indented below each
+fn parse_fish(history: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = vec![];
+    for line in history.lines() {
+        if let Some(cmd) = line.strip_prefix("- cmd: ") {
+            entries.push(Entry {
+                cmd: unescape_fish(cmd),
+                when: None,
+            });
+        } else if let Some(when) = line.trim_start().strip_prefix("when: ") {
+            if let Some(entry) = entries.last_mut() {
+                entry.when = when.trim().parse().ok();
+            }
+        }
+    }
+    entries
+}
+
+/// fish escapes newlines and backslashes in commands
+fn unescape_fish(cmd: &str) -> String {
+    let mut res = String::with_capacity(cmd.len());
+    let mut chars = cmd.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                res.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                res.push('\\');
+                chars.next();
+            }
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+/// one command per line, as `: <epoch>:<elapsed>;<cmd>` with `EXTENDED_HISTORY`
+/// set. the lines of a multi-line command end with a backslash.
+fn parse_zsh(history: &str) -> Vec<Entry> {
+    let re = Regex::new(r"^: *(\d+):\d+;(.*)$").unwrap();
+    let mut entries: Vec<Entry> = vec![];
+    let mut continued = false;
+    for line in history.lines() {
+        if continued {
+            let entry = entries.last_mut().unwrap();
+            entry.cmd.push('\n');
+            entry.cmd.push_str(line.strip_suffix('\\').unwrap_or(line));
+        } else if let Some(captures) = re.captures(line) {
+            let cmd = &captures[2];
+            entries.push(Entry {
+                cmd: cmd.strip_suffix('\\').unwrap_or(cmd).to_string(),
+                when: captures[1].parse().ok(),
+            });
+        } else if !line.trim().is_empty() {
+            entries.push(Entry {
+                cmd: line.strip_suffix('\\').unwrap_or(line).to_string(),
+                when: None,
+            });
+        } else {
+            continue;
+        }
+        continued = line.ends_with('\\');
+    }
+    entries
+}
+
+/// zsh writes bytes that are special to it as 0x83 followed by the byte
+/// xor 32, which leaves non-ascii characters invalid utf-8
+fn unmetafy(history: &[u8]) -> Cow<'_, [u8]> {
+    const META: u8 = 0x83;
+    if !history.contains(&META) {
+        return Cow::Borrowed(history);
+    }
+    let mut res = Vec::with_capacity(history.len());
+    let mut bytes = history.iter();
+    while let Some(&b) = bytes.next() {
+        match b {
+            META => res.extend(bytes.next().map(|b| b ^ 32)),
+            _ => res.push(b),
+        }
+    }
+    Cow::Owned(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the commands of a history, with when they were run
+    type Parsed<'a> = Vec<(&'a str, Option<i64>)>;
+
+    fn entries(entries: &[Entry]) -> Parsed<'_> {
+        entries.iter().map(|e| (e.cmd.as_str(), e.when)).collect()
+    }
+
+    #[test]
+    fn test_detect() {
+        let cases: [(&str, Option<Shell>); 6] = [
+            ("#1700000000\nls\n", Some(Shell::Bash)),
+            ("- cmd: ls\n  when: 1700000000\n", Some(Shell::Fish)),
+            (": 1700000000:0;ls\n", Some(Shell::Zsh)),
+            (":1700000000:0;ls\n", Some(Shell::Zsh)),
+            ("ls\ncd /tmp\n: 1700000000:0;ls\n", Some(Shell::Zsh)),
+            ("ls\n#comment\ncd /tmp\n", None),
+        ];
+        for (history, shell) in cases {
+            assert_eq!(Shell::detect(history.as_bytes()), shell, "{history:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_bash() {
+        let cases: [(&str, Parsed); 3] = [
+            (
+                "ls -la\n\ncd /tmp\n",
+                vec![("ls -la", None), ("cd /tmp", None)],
+            ),
+            (
+                "#1700000000\ngit status\n#1700000060\nmake\nplain\n",
+                vec![
+                    ("git status", Some(1_700_000_000)),
+                    ("make", Some(1_700_000_060)),
+                    ("plain", None),
+                ],
+            ),
+            // a comment that is not a timestamp is a command
+            ("#todo\n", vec![("#todo", None)]),
+        ];
+        for (history, expected) in cases {
+            assert_eq!(entries(&parse_bash(history)), expected, "{history:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_fish() {
+        let cases: [(&str, Parsed); 3] = [
+            (
+                "- cmd: git status\n  when: 1700000000\n- cmd: cd ~/src\n  when: 1700000060\n  paths:\n    - ~/src\n",
+                vec![("git status", Some(1_700_000_000)), ("cd ~/src", Some(1_700_000_060))],
+            ),
+            // newlines and backslashes are escaped
+            (
+                "- cmd: echo a\\nb \\\\n\n  when: 1700000120\n",
+                vec![("echo a\nb \\n", Some(1_700_000_120))],
+            ),
+            ("- cmd: ls\n- cmd: pwd\n", vec![("ls", None), ("pwd", None)]),
+        ];
+        for (history, expected) in cases {
+            assert_eq!(entries(&parse_fish(history)), expected, "{history:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_zsh() {
+        let cases: [(&str, Parsed); 3] = [
+            (
+                ": 1700000000:0;git status\n: 1700000060:5;make\n",
+                vec![
+                    ("git status", Some(1_700_000_000)),
+                    ("make", Some(1_700_000_060)),
+                ],
+            ),
+            (
+                ": 1700000000:0;for f in *; do\\\necho $f\\\ndone\n: 1700000060:0;ls\n",
+                vec![
+                    ("for f in *; do\necho $f\ndone", Some(1_700_000_000)),
+                    ("ls", Some(1_700_000_060)),
+                ],
+            ),
+            // without EXTENDED_HISTORY
+            ("ls\n\ncd /tmp\n", vec![("ls", None), ("cd /tmp", None)]),
+        ];
+        for (history, expected) in cases {
+            assert_eq!(entries(&parse_zsh(history)), expected, "{history:?}");
+        }
+    }
+
+    #[test]
+    fn test_unmetafy() {
+        // ă is c4 83, and 83 is written as 83 a3
+        let history = b": 1700000000:0;echo \xc4\x83\xa3\n";
+        assert_eq!(
+            unmetafy(history).as_ref(),
+            ": 1700000000:0;echo ă\n".as_bytes()
+        );
+        let parsed = parse(Shell::Zsh, history);
+        assert_eq!(entries(&parsed), vec![("echo ă", Some(1_700_000_000))]);
+        assert!(matches!(unmetafy(b"ls\n"), Cow::Borrowed(_)));
+    }
+}
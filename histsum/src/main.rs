@@ -1,26 +1,59 @@
 #![warn(clippy::all, clippy::pedantic)]
 
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, ValueEnum};
+use history::{Entry, Shell};
 use home::home_dir;
-use regex::Regex;
-use std::collections::HashMap;
-use std::io::{self, BufRead, BufReader};
-use std::path::Path;
-use std::{cmp, env, fs};
+use report::Report;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
 use thiserror::Error;
 
+mod history;
+mod report;
+mod tokenize;
+
 const DEFAULT_TOPK: usize = 20;
 
 fn main() -> Result<(), HError> {
-    let Args { topk } = Args::parse();
-    let dir = home_dir().ok_or(HError::NoHomeDir)?;
-    let path = Path::new(&dir).join(".zsh_history");
-    let file = fs::File::open(path)?;
-    let mut acc = Acc::new(topk);
-    for line in BufReader::new(file).lines() {
-        let line = line?;
-        acc.parse(&line);
+    let args = Args::parse();
+    let (report, cmd) = args.report();
+    let (history, shell) = match &args.file {
+        Some(path) if path.as_os_str() == "-" => {
+            let mut buf = vec![];
+            io::stdin().read_to_end(&mut buf)?;
+            (buf, args.shell)
+        }
+        Some(path) => (fs::read(path)?, args.shell),
+        None => {
+            let shell = args.shell.or_else(Shell::current).unwrap_or(Shell::Zsh);
+            let dir = home_dir().ok_or(HError::NoHomeDir)?;
+            (fs::read(shell.history_path(&dir))?, Some(shell))
+        }
+    };
+    // a format that records timestamps is recognized before the shell that
+    // was guessed for the file, and plain formats are read the same by all
+    let shell = args
+        .shell
+        .or_else(|| Shell::detect(&history))
+        .or(shell)
+        .unwrap_or(Shell::Zsh);
+    let mut entries = history::parse(shell, &history);
+    let over_time = matches!(report, Report::Heatmap | Report::Trend);
+    if args.since.is_some() || args.until.is_some() || over_time {
+        if entries.iter().all(|entry| entry.when.is_none()) {
+            return Err(HError::NoTimestamps);
+        }
+        entries.retain(|entry| args.includes(entry));
+    }
+    let summary = report::summarize(report, &entries, args.topk, args.ngram.into(), cmd);
+    if args.json {
+        println!("{}", summary.json());
+    } else {
+        println!("{summary}");
     }
-    println!("{}", acc.summarize());
     Ok(())
 }
 
@@ -31,73 +64,174 @@ pub enum HError {
 
     #[error("No home dir")]
     NoHomeDir,
+
+    #[error("The history has no timestamps to filter or place commands in time by")]
+    NoTimestamps,
 }
 
-/// arguments to the program
+/// Summarizes the shell history, providing a topk of the most frequently used
+/// commands
+#[derive(Parser)]
+#[command(version, about)]
 struct Args {
+    /// how many commands to show
+    #[arg(default_value_t = DEFAULT_TOPK)]
     topk: usize,
+
+    /// the summary to print: commands, subcommands, pipelines, flags, ngrams,
+    /// heatmap, or trend followed by the command to follow like git or "git push"
+    #[arg(long, num_args = 1..=2, value_names = ["REPORT", "CMD"], default_value = "commands")]
+    report: Vec<String>,
+
+    /// print the summary as json
+    #[arg(long)]
+    json: bool,
+
+    /// how many commands the sequences of the ngrams report have
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u16).range(1..))]
+    ngram: u16,
+
+    /// the shell whose history format is read, detected if not given
+    #[arg(long, value_enum)]
+    shell: Option<Shell>,
+
+    /// the history file, or - for stdin. defaults to the history of the shell
+    #[arg(long, short)]
+    file: Option<PathBuf>,
+
+    /// only count commands run since a time, given as an age like 7d, 12h or
+    /// 30m, or as a date like 2024-01-31 or 2024-01-31T09:00
+    #[arg(long, value_parser = parse_time)]
+    since: Option<i64>,
+
+    /// only count commands run before a time, given like --since
+    #[arg(long, value_parser = parse_time)]
+    until: Option<i64>,
 }
 
 impl Args {
-    fn parse() -> Args {
-        let args = env::args().take(2).collect::<Vec<String>>();
-        let topk = args.get(1).map_or(DEFAULT_TOPK, |arg| {
-            if arg == "-h" || arg == "--help" {
-                eprintln!("Usage: {} [topk]", args.get(0).unwrap());
-                std::process::exit(0);
-            }
-            match arg.parse::<usize>() {
-                Ok(n) => n,
-                Err(e) => {
-                    eprintln!(r#"Failed to parse topk for "{arg}": {e}"#);
-                    std::process::exit(1);
-                }
-            }
-        });
-        Args { topk }
+    /// the report to print, with the command of a trend, exiting with a usage
+    /// error if the command is missing or given to another report
+    fn report(&self) -> (Report, &str) {
+        fn error(msg: String) -> ! {
+            Args::command().error(ErrorKind::InvalidValue, msg).exit()
+        }
+        let report = Report::from_str(&self.report[0], true)
+            .unwrap_or_else(|_| error(format!("unknown report '{}'", self.report[0])));
+        match (report, self.report.get(1)) {
+            (Report::Trend, Some(cmd)) => (report, cmd),
+            (Report::Trend, None) => error("the trend report needs a command".to_string()),
+            (_, Some(cmd)) => error(format!("unexpected command '{cmd}' for the report")),
+            (_, None) => (report, ""),
+        }
+    }
+
+    /// whether the entry was run in the time window
+    fn includes(&self, entry: &Entry) -> bool {
+        entry.when.is_some_and(|when| {
+            self.since.is_none_or(|since| when >= since)
+                && self.until.is_none_or(|until| when < until)
+        })
     }
 }
 
-/// Acc accumulates the results of parsing the history file
-/// and summarizes them when printed
-struct Acc {
-    topk: usize,                // how many entries to display
-    cmds: HashMap<String, u32>, // lookup for command counts
-    re: Regex,                  // to parse the line
+/// parses a time into seconds since the epoch, either as an age in seconds,
+/// minutes, hours, days or weeks, or as a date or time in the local timezone
+fn parse_time(time: &str) -> Result<i64, String> {
+    let unit = match time.chars().last() {
+        Some('s') => Some(1),
+        Some('m') => Some(60),
+        Some('h') => Some(60 * 60),
+        Some('d') => Some(24 * 60 * 60),
+        Some('w') => Some(7 * 24 * 60 * 60),
+        _ => None,
+    };
+    if let Some(unit) = unit {
+        if let Ok(n) = time[..time.len() - 1].parse::<i64>() {
+            return n
+                .checked_mul(unit)
+                .and_then(|age| Local::now().timestamp().checked_sub(age))
+                .ok_or_else(|| format!("{time} is out of range"));
+        }
+    }
+    let local = NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(time, "%Y-%m-%d")
+                .map(|date| date.and_hms_opt(0, 0, 0).unwrap())
+        })
+        .map_err(|_| format!("expected an age like 7d or a date like 2024-01-31, got {time}"))?;
+    Local
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|time| time.timestamp())
+        .ok_or_else(|| format!("{time} does not exist in the local timezone"))
 }
 
-impl Acc {
-    fn new(topk: usize) -> Self {
-        Self {
-            topk,
-            cmds: HashMap::default(),
-            re: Regex::new(r"^(: \d+:\d;)? *(.*)$").unwrap(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(date: &str) -> i64 {
+        let time = NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S").unwrap();
+        Local.from_local_datetime(&time).unwrap().timestamp()
+    }
+
+    #[test]
+    fn test_parse_time_relative() {
+        let cases = [
+            ("30s", 30),
+            ("30m", 30 * 60),
+            ("12h", 12 * 60 * 60),
+            ("7d", 7 * 24 * 60 * 60),
+            ("2w", 14 * 24 * 60 * 60),
+        ];
+        for (age, secs) in cases {
+            let now = Local::now().timestamp();
+            let time = parse_time(age).unwrap();
+            // the clock may tick between the two
+            assert!((now - secs - time).abs() <= 1, "{age}");
+        }
+        for age in ["99999999999999w", "-9223372036854775807s"] {
+            assert!(parse_time(age).is_err(), "{age}");
         }
     }
-    fn parse(&mut self, line: &str) {
-        if let Some(captures) = self.re.captures(line) {
-            if let Some(found) = captures.get(2) {
-                found.as_str().split(' ').take(1).for_each(|cmd| {
-                    *self.cmds.entry(cmd.to_string()).or_insert(0) += 1;
-                });
-            }
+
+    #[test]
+    fn test_parse_time_absolute() {
+        let cases = [
+            ("2024-01-31", "2024-01-31T00:00:00"),
+            ("2024-01-31T09:00", "2024-01-31T09:00:00"),
+            ("2024-01-31T09:00:30", "2024-01-31T09:00:30"),
+        ];
+        for (date, expected) in cases {
+            assert_eq!(parse_time(date), Ok(local(expected)), "{date}");
+        }
+        for bad in ["", "d", "7y", "yesterday", "2024-13-01", "31/01/2024"] {
+            assert!(parse_time(bad).is_err(), "{bad}");
         }
     }
-    fn summarize(&self) -> String {
-        // convert the lookup to a vector and sort it
-        let mut res: Vec<(&String, &u32)> = self.cmds.iter().collect();
-        res.sort_by(|x, y| y.1.cmp(x.1));
-        // take the topk
-        res = res.into_iter().take(self.topk).collect::<Vec<_>>();
-        // figure out padding for the topk results to make printing nice
-        let max_len = res.iter().fold(0, |mx, s| cmp::max(mx, s.0.len()));
-        // map the tuples into formatted strings and join them
-        res.iter()
-            .map(|(string, count)| {
-                let padding = " ".repeat(max_len - string.len());
-                format!("{string}{padding} : {count}")
-            })
-            .collect::<Vec<String>>()
-            .join("\n")
+
+    #[test]
+    fn test_since_until() {
+        let entry = |when| Entry {
+            cmd: "ls".to_string(),
+            when,
+        };
+        let args =
+            Args::try_parse_from(["histsum", "--since", "2024-01-01", "--until", "2024-02-01"])
+                .unwrap();
+        assert!(args.includes(&entry(Some(local("2024-01-01T00:00:00")))));
+        assert!(args.includes(&entry(Some(local("2024-01-31T23:59:59")))));
+        assert!(!args.includes(&entry(Some(local("2023-12-31T23:59:59")))));
+        // the end of the window is not part of it
+        assert!(!args.includes(&entry(Some(local("2024-02-01T00:00:00")))));
+        assert!(!args.includes(&entry(None)));
+
+        let args = Args::try_parse_from(["histsum", "--since", "1h"]).unwrap();
+        let now = Local::now().timestamp();
+        assert!(args.includes(&entry(Some(now))));
+        assert!(!args.includes(&entry(Some(now - 2 * 60 * 60))));
+        assert!(Args::try_parse_from(["histsum", "--until", "soon"]).is_err());
     }
 }
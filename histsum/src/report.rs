@@ -0,0 +1,548 @@
+use crate::history::Entry;
+use crate::tokenize::{self, Command};
+use chrono::{Datelike, Days, Local, NaiveDate, TimeZone, Timelike};
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use std::cmp;
+use std::collections::HashMap;
+use std::fmt;
+
+/// how many flags are shown for each command
+const FLAGS_PER_COMMAND: usize = 5;
+
+/// how wide the bar of the busiest week of a trend is
+const TREND_WIDTH: u32 = 40;
+
+/// the shades of the heatmap, from no commands to the busiest hour
+const SHADES: [&str; 5] = ["  ", "░░", "▒▒", "▓▓", "██"];
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// the summaries of a history
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Report {
+    /// the commands run most, counting each command of a pipeline or list
+    Commands,
+    /// the subcommands run most, like `git push`
+    Subcommands,
+    /// the pipelines of two or more commands run most
+    Pipelines,
+    /// the flags passed most to each of the commands passed flags most
+    Flags,
+    /// the sequences of commands run one after the other most
+    Ngrams,
+    /// the commands run in each hour of each day of the week
+    Heatmap,
+    /// the uses of a command, or of a subcommand like `git push`, each week
+    Trend,
+}
+
+/// keys with their counts, most counted first
+type Counts = Vec<(String, u32)>;
+
+/// the result of a report, printed as text or converted to json
+pub enum Summary {
+    Counts(Counts),
+    /// each command with the total of its flags and its most common flags
+    Flags(Vec<(String, u32, Counts)>),
+    /// the counts by weekday from monday, then by hour
+    Heatmap(Box<[[u32; 24]; 7]>),
+    /// the counts by the monday starting each week
+    Trend(Vec<(NaiveDate, u32)>),
+}
+
+/// summarizes the commands of the entries, in order, as the report. the
+/// trend report counts the uses of `cmd`, and the reports over time skip
+/// the entries without timestamps.
+pub fn summarize(
+    report: Report,
+    entries: &[Entry],
+    topk: usize,
+    ngram: usize,
+    cmd: &str,
+) -> Summary {
+    match report {
+        Report::Heatmap => return heatmap(entries),
+        Report::Trend => return trend(entries, cmd),
+        _ => {}
+    }
+    let pipelines = entries
+        .iter()
+        .flat_map(|entry| tokenize::parse(&entry.cmd))
+        .collect::<Vec<_>>();
+    let cmds = || pipelines.iter().flatten();
+    let mut acc = Acc::new(topk);
+    match report {
+        Report::Commands => cmds().for_each(|cmd| acc.add(&cmd.name)),
+        Report::Subcommands => cmds().filter_map(subcommand).for_each(|sub| acc.add(&sub)),
+        Report::Pipelines => pipelines
+            .iter()
+            .filter(|pipeline| pipeline.len() > 1)
+            .for_each(|pipeline| {
+                let names = pipeline.iter().map(|cmd| cmd.name.as_str());
+                acc.add(&names.collect::<Vec<_>>().join(" | "));
+            }),
+        Report::Flags => return summarize_flags(cmds(), topk),
+        Report::Heatmap | Report::Trend => unreachable!(),
+        Report::Ngrams => {
+            let names = cmds().map(|cmd| cmd.name.as_str()).collect::<Vec<_>>();
+            names
+                .windows(ngram)
+                .for_each(|names| acc.add(&names.join(" -> ")));
+        }
+    }
+    Summary::Counts(acc.top())
+}
+
+/// the command and its first argument, if that looks like a subcommand
+/// rather than a flag, path or value
+fn subcommand(cmd: &Command) -> Option<String> {
+    let arg = cmd.args.first()?;
+    let mut chars = arg.chars();
+    let word = chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    word.then(|| format!("{} {arg}", cmd.name))
+}
+
+/// the flags of the commands passed flags most, with their most common flags
+fn summarize_flags<'a>(cmds: impl Iterator<Item = &'a Command>, topk: usize) -> Summary {
+    let mut flags: HashMap<&str, Acc> = HashMap::new();
+    let mut totals = Acc::new(topk);
+    for cmd in cmds {
+        // the value of --flag=value is not part of the flag
+        let args = cmd.args.iter().take_while(|arg| *arg != "--");
+        for flag in args.filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+            let flag = flag.split_once('=').map_or(flag.as_str(), |(flag, _)| flag);
+            let acc = flags
+                .entry(&cmd.name)
+                .or_insert_with(|| Acc::new(FLAGS_PER_COMMAND));
+            acc.add(flag);
+            totals.add(&cmd.name);
+        }
+    }
+    let top = totals.top().into_iter();
+    Summary::Flags(
+        top.map(|(cmd, total)| {
+            let flags = flags[cmd.as_str()].top();
+            (cmd, total, flags)
+        })
+        .collect(),
+    )
+}
+
+/// the local time of the entry, if it has one
+fn local_time(entry: &Entry) -> Option<chrono::DateTime<Local>> {
+    Local.timestamp_opt(entry.when?, 0).single()
+}
+
+fn heatmap(entries: &[Entry]) -> Summary {
+    let mut counts = [[0; 24]; 7];
+    for time in entries.iter().filter_map(local_time) {
+        counts[time.weekday().num_days_from_monday() as usize][time.hour() as usize] += 1;
+    }
+    Summary::Heatmap(Box::new(counts))
+}
+
+/// the weekly uses of the command, from the week of the first entry to the
+/// week of the last so that the weeks before it was first used show too
+fn trend(entries: &[Entry], target: &str) -> Summary {
+    let mut weeks: Vec<(NaiveDate, u32)> = vec![];
+    let mut times = entries.iter().filter_map(local_time).collect::<Vec<_>>();
+    times.sort();
+    let Some(first) = times.first().map(|time| week(time.date_naive())) else {
+        return Summary::Trend(weeks);
+    };
+    let last = week(times[times.len() - 1].date_naive());
+    let mut monday = first;
+    while monday <= last {
+        weeks.push((monday, 0));
+        monday = monday + Days::new(7);
+    }
+    for entry in entries {
+        let Some(time) = local_time(entry) else {
+            continue;
+        };
+        let uses = tokenize::parse(&entry.cmd)
+            .iter()
+            .flatten()
+            .filter(|cmd| {
+                // a target with an argument is matched against the subcommand
+                if target.contains(' ') {
+                    subcommand(cmd).is_some_and(|sub| sub == target)
+                } else {
+                    cmd.name == target
+                }
+            })
+            .count();
+        let i = (week(time.date_naive()) - first).num_weeks();
+        weeks[usize::try_from(i).unwrap()].1 += u32::try_from(uses).unwrap();
+    }
+    Summary::Trend(weeks)
+}
+
+/// the monday starting the week of the date
+fn week(date: NaiveDate) -> NaiveDate {
+    date - Days::new(date.weekday().num_days_from_monday().into())
+}
+
+impl Summary {
+    /// the summary as json: a list of objects with the name or the time
+    /// they count, and the count
+    pub fn json(&self) -> Value {
+        let counts = |counts: &[(String, u32)]| -> Vec<Value> {
+            counts
+                .iter()
+                .map(|(name, count)| json!({ "name": name, "count": count }))
+                .collect()
+        };
+        match self {
+            Summary::Counts(top) => json!(counts(top)),
+            Summary::Flags(top) => top
+                .iter()
+                .map(|(cmd, total, flags)| {
+                    json!({ "name": cmd, "count": total, "flags": counts(flags) })
+                })
+                .collect(),
+            Summary::Heatmap(days) => WEEKDAYS
+                .iter()
+                .zip(days.iter())
+                .flat_map(|(day, hours)| {
+                    hours.iter().enumerate().map(move |(hour, count)| {
+                        json!({ "weekday": day, "hour": hour, "count": count })
+                    })
+                })
+                .collect(),
+            Summary::Trend(weeks) => weeks
+                .iter()
+                .map(|(week, count)| json!({ "week": week.to_string(), "count": count }))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = match self {
+            Summary::Counts(top) => {
+                let keys = top
+                    .iter()
+                    .map(|(key, count)| (key.clone(), count.to_string()));
+                padded(keys.collect())
+            }
+            Summary::Flags(top) => {
+                let keys = top.iter().map(|(cmd, _, flags)| {
+                    let flags = flags
+                        .iter()
+                        .map(|(flag, count)| format!("{flag} ({count})"))
+                        .collect::<Vec<_>>();
+                    (cmd.clone(), flags.join(", "))
+                });
+                padded(keys.collect())
+            }
+            Summary::Heatmap(days) => {
+                let max = days.iter().flatten().copied().max().unwrap_or(0);
+                let hours = (0..24).map(|hour| format!("{hour:02}"));
+                let mut lines = vec![format!("    {}", hours.collect::<Vec<_>>().join(" "))];
+                for (day, hours) in WEEKDAYS.iter().zip(days.iter()) {
+                    let shades = hours.iter().map(|count| SHADES[shade(*count, max)]);
+                    let total = hours.iter().sum::<u32>();
+                    let shades = shades.collect::<Vec<_>>().join(" ");
+                    lines.push(format!("{day} {shades} {total}"));
+                }
+                lines
+            }
+            Summary::Trend(weeks) => {
+                let max = weeks.iter().map(|(_, count)| *count).max().unwrap_or(0);
+                let keys = weeks.iter().map(|(week, count)| {
+                    let width = (count * TREND_WIDTH).checked_div(max).unwrap_or(0);
+                    let bar = "█".repeat(width as usize);
+                    (
+                        week.to_string(),
+                        format!("{bar} {count}").trim_start().to_string(),
+                    )
+                });
+                padded(keys.collect())
+            }
+        };
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// the shade of a count, where any use is shaded and the busiest is darkest
+fn shade(count: u32, max: u32) -> usize {
+    if count == 0 {
+        return 0;
+    }
+    let levels = u32::try_from(SHADES.len() - 1).unwrap();
+    ((count * levels).div_ceil(max)) as usize
+}
+
+/// the keys and their values as lines, with the keys padded to line up
+fn padded(keys: Vec<(String, String)>) -> Vec<String> {
+    // figure out padding for the keys to make printing nice
+    let max_len = keys
+        .iter()
+        .fold(0, |mx, s| cmp::max(mx, s.0.chars().count()));
+    keys.into_iter()
+        .map(|(key, value)| {
+            let padding = " ".repeat(max_len - key.chars().count());
+            format!("{key}{padding} : {value}")
+        })
+        .collect()
+}
+
+/// Acc accumulates the counts of the keys of a report
+struct Acc {
+    topk: usize,                // how many entries to display
+    keys: HashMap<String, u32>, // lookup for key counts
+}
+
+impl Acc {
+    fn new(topk: usize) -> Self {
+        Self {
+            topk,
+            keys: HashMap::default(),
+        }
+    }
+    fn add(&mut self, key: &str) {
+        *self.keys.entry(key.to_string()).or_insert(0) += 1;
+    }
+    /// the topk keys with their counts, most counted first
+    fn top(&self) -> Counts {
+        // convert the lookup to a vector and sort it
+        let mut res: Vec<(&String, &u32)> = self.keys.iter().collect();
+        res.sort_by(|x, y| y.1.cmp(x.1).then(x.0.cmp(y.0)));
+        // take the topk
+        res.into_iter()
+            .take(self.topk)
+            .map(|(key, count)| (key.clone(), *count))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(cmds: &[&str]) -> Vec<Entry> {
+        cmds.iter()
+            .map(|cmd| Entry {
+                cmd: (*cmd).to_string(),
+                when: None,
+            })
+            .collect()
+    }
+
+    fn counts(report: Report, cmds: &[&str], topk: usize, ngram: usize) -> Counts {
+        let Summary::Counts(top) = summarize(report, &entries(cmds), topk, ngram, "") else {
+            panic!("{report:?} does not count");
+        };
+        top
+    }
+
+    /// an entry run at a local time, like 2024-01-01T09:30, which is a monday
+    fn at(time: &str, cmd: &str) -> Entry {
+        let time = chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M").unwrap();
+        Entry {
+            cmd: cmd.to_string(),
+            when: Some(Local.from_local_datetime(&time).unwrap().timestamp()),
+        }
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    fn owned(counts: &[(&str, u32)]) -> Counts {
+        counts
+            .iter()
+            .map(|(key, count)| ((*key).to_string(), *count))
+            .collect()
+    }
+
+    #[test]
+    fn test_commands() {
+        let cmds = [
+            "sudo git push",
+            "FOO=1 make && make test",
+            "git log | head",
+            "ls",
+        ];
+        assert_eq!(
+            counts(Report::Commands, &cmds, 3, 2),
+            owned(&[("git", 2), ("make", 2), ("head", 1)])
+        );
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let cmds = [
+            "git push",
+            "git push origin main",
+            "sudo git push",
+            "git status",
+            "git -C x status",
+            "cargo build --release",
+            "ls /tmp",
+            "docker Run",
+        ];
+        assert_eq!(
+            counts(Report::Subcommands, &cmds, 10, 2),
+            owned(&[("git push", 3), ("cargo build", 1), ("git status", 1)])
+        );
+    }
+
+    #[test]
+    fn test_pipelines() {
+        let cmds = [
+            "git log | head",
+            "git log --oneline | head -n 3",
+            "ls",
+            "cat f | sort | uniq -c; make",
+        ];
+        assert_eq!(
+            counts(Report::Pipelines, &cmds, 10, 2),
+            owned(&[("git | head", 2), ("cat | sort | uniq", 1)])
+        );
+    }
+
+    #[test]
+    fn test_flags() {
+        let cmds = [
+            "ls -la",
+            "ls -la /tmp",
+            "ls -h",
+            "grep -r --include=*.rs foo",
+            "git commit -m msg -- -x",
+            "echo - x",
+        ];
+        let Summary::Flags(top) = summarize(Report::Flags, &entries(&cmds), 10, 2, "") else {
+            panic!("not flags");
+        };
+        assert_eq!(
+            top,
+            vec![
+                ("ls".to_string(), 3, owned(&[("-la", 2), ("-h", 1)])),
+                ("grep".to_string(), 2, owned(&[("--include", 1), ("-r", 1)])),
+                ("git".to_string(), 1, owned(&[("-m", 1)])),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ngrams() {
+        let cmds = ["cd src", "ls", "cd src", "ls", "make && make test"];
+        assert_eq!(
+            counts(Report::Ngrams, &cmds, 10, 2),
+            owned(&[
+                ("cd -> ls", 2),
+                ("ls -> cd", 1),
+                ("ls -> make", 1),
+                ("make -> make", 1)
+            ])
+        );
+        assert_eq!(
+            counts(Report::Ngrams, &cmds, 1, 3),
+            owned(&[("cd -> ls -> cd", 1)])
+        );
+        assert!(counts(Report::Ngrams, &cmds, 10, 7).is_empty());
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let entries = [
+            at("2024-01-01T09:30", "ls"),
+            at("2024-01-01T09:59", "ls"),
+            at("2024-01-08T09:00", "ls"),
+            at("2024-01-03T00:05", "ls"),
+            at("2024-01-07T23:00", "ls"),
+            Entry {
+                cmd: "ls".to_string(),
+                when: None,
+            },
+        ];
+        let Summary::Heatmap(days) = summarize(Report::Heatmap, &entries, 10, 2, "") else {
+            panic!("not a heatmap");
+        };
+        // monday 9:00, wednesday 0:00 and sunday 23:00
+        assert_eq!(days[0][9], 3);
+        assert_eq!(days[2][0], 1);
+        assert_eq!(days[6][23], 1);
+        assert_eq!(days.iter().flatten().sum::<u32>(), 5);
+    }
+
+    #[test]
+    fn test_trend() {
+        let entries = [
+            at("2024-01-21T10:00", "git push && git push"),
+            at("2024-01-01T10:00", "git status"),
+            at("2024-01-03T10:00", "git log | grep fix"),
+            at("2024-01-17T10:00", "ls"),
+            Entry {
+                cmd: "git push".to_string(),
+                when: None,
+            },
+        ];
+        let trend = |cmd| {
+            let Summary::Trend(weeks) = summarize(Report::Trend, &entries, 10, 2, cmd) else {
+                panic!("not a trend");
+            };
+            weeks
+        };
+        // every week from the first entry to the last, by the monday starting it
+        assert_eq!(
+            trend("git"),
+            vec![
+                (date("2024-01-01"), 2),
+                (date("2024-01-08"), 0),
+                (date("2024-01-15"), 2)
+            ]
+        );
+        assert_eq!(
+            trend("git push"),
+            vec![
+                (date("2024-01-01"), 0),
+                (date("2024-01-08"), 0),
+                (date("2024-01-15"), 2)
+            ]
+        );
+        let Summary::Trend(weeks) = summarize(Report::Trend, &[], 10, 2, "git") else {
+            panic!("not a trend");
+        };
+        assert!(weeks.is_empty());
+    }
+
+    #[test]
+    fn test_json() {
+        let summary = Summary::Counts(owned(&[("git", 2), ("ls", 1)]));
+        assert_eq!(
+            summary.json(),
+            json!([{ "name": "git", "count": 2 }, { "name": "ls", "count": 1 }])
+        );
+
+        let summary = Summary::Flags(vec![("ls".to_string(), 3, owned(&[("-la", 3)]))]);
+        assert_eq!(
+            summary.json(),
+            json!([{ "name": "ls", "count": 3, "flags": [{ "name": "-la", "count": 3 }] }])
+        );
+
+        let mut days = [[0; 24]; 7];
+        days[0][9] = 2;
+        days[6][23] = 1;
+        let json = Summary::Heatmap(Box::new(days)).json();
+        let hours = json.as_array().unwrap();
+        assert_eq!(hours.len(), 7 * 24);
+        assert_eq!(hours[0], json!({ "weekday": "Mon", "hour": 0, "count": 0 }));
+        assert_eq!(hours[9], json!({ "weekday": "Mon", "hour": 9, "count": 2 }));
+        assert_eq!(
+            hours[7 * 24 - 1],
+            json!({ "weekday": "Sun", "hour": 23, "count": 1 })
+        );
+
+        let summary = Summary::Trend(vec![(date("2024-01-01"), 2), (date("2024-01-08"), 0)]);
+        assert_eq!(
+            summary.json(),
+            json!([{ "week": "2024-01-01", "count": 2 }, { "week": "2024-01-08", "count": 0 }])
+        );
+    }
+}
@@ -0,0 +1,344 @@
+/// a command with its arguments, without the variables assigned before it
+/// and the commands like sudo that it is run through
+#[derive(Debug, PartialEq, Eq)]
+pub struct Command {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+/// commands joined by pipes
+pub type Pipeline = Vec<Command>;
+
+/// commands that run the command that follows them, with their options
+/// that take an argument
+const WRAPPERS: [(&str, &[&str]); 11] = [
+    ("builtin", &[]),
+    ("command", &[]),
+    ("doas", &["-u", "-C"]),
+    ("env", &["-u", "-C", "-S"]),
+    ("exec", &["-a"]),
+    ("nice", &["-n"]),
+    ("nohup", &[]),
+    (
+        "sudo",
+        &["-u", "-g", "-C", "-D", "-h", "-p", "-r", "-t", "-U"],
+    ),
+    ("time", &["-f", "-o"]),
+    ("watch", &["-n", "-d"]),
+    ("xargs", &["-I", "-n", "-P", "-L", "-d", "-s", "-E", "-a"]),
+];
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Pipe,
+    Separator, // ; & && || newlines and parentheses
+    Redirect,  // followed by the word it redirects to
+}
+
+/// splits a command line into the pipelines it runs
+pub fn parse(line: &str) -> Vec<Pipeline> {
+    let mut pipelines = vec![];
+    let mut pipeline = vec![];
+    let mut words = vec![];
+    let mut tokens = lex(line).into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) => words.push(word),
+            Token::Redirect => {
+                tokens.next();
+            }
+            Token::Pipe => pipeline.extend(command(&mut words)),
+            Token::Separator => {
+                pipeline.extend(command(&mut words));
+                if !pipeline.is_empty() {
+                    pipelines.push(std::mem::take(&mut pipeline));
+                }
+            }
+        }
+    }
+    pipeline.extend(command(&mut words));
+    if !pipeline.is_empty() {
+        pipelines.push(pipeline);
+    }
+    pipelines
+}
+
+/// the command that the words run, if any
+fn command(words: &mut Vec<String>) -> Option<Command> {
+    let words = std::mem::take(words);
+    let mut words = words.into_iter().peekable();
+    loop {
+        let word = words.next()?;
+        if is_assignment(&word) {
+            continue;
+        }
+        match word.as_str() {
+            // a loop header runs nothing itself
+            "for" | "select" | "case" => return None,
+            "if" | "then" | "else" | "elif" | "while" | "until" | "do" | "!" | "{" | "}" | "fi"
+            | "done" | "esac" => continue,
+            _ => {}
+        }
+        if let Some((_, with_arg)) = WRAPPERS.iter().find(|(name, _)| *name == word) {
+            while let Some(flag) = words.next_if(|w| w.starts_with('-') || is_assignment(w)) {
+                if with_arg.contains(&flag.as_str()) {
+                    words.next();
+                }
+            }
+            continue;
+        }
+        return Some(Command {
+            name: word,
+            args: words.collect(),
+        });
+    }
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// splits the line into words and operators, removing quotes and escapes.
+/// command substitutions are kept whole in the word they are part of.
+fn lex(line: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut word = String::new();
+    // whether a word was started, which an empty quoted string does
+    let mut in_word = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => end_word(&mut tokens, &mut word, &mut in_word),
+            '\n' | ';' | '(' | ')' => {
+                end_word(&mut tokens, &mut word, &mut in_word);
+                tokens.push(Token::Separator);
+            }
+            '|' => {
+                end_word(&mut tokens, &mut word, &mut in_word);
+                if chars.next_if_eq(&'|').is_some() {
+                    tokens.push(Token::Separator);
+                } else {
+                    chars.next_if_eq(&'&');
+                    tokens.push(Token::Pipe);
+                }
+            }
+            '&' => {
+                end_word(&mut tokens, &mut word, &mut in_word);
+                if chars.next_if_eq(&'>').is_some() {
+                    chars.next_if_eq(&'>');
+                    tokens.push(Token::Redirect);
+                } else {
+                    chars.next_if_eq(&'&');
+                    tokens.push(Token::Separator);
+                }
+            }
+            '<' | '>' => {
+                // the descriptor that is redirected is not a word
+                if word.chars().all(|c| c.is_ascii_digit()) {
+                    word.clear();
+                    in_word = false;
+                }
+                end_word(&mut tokens, &mut word, &mut in_word);
+                while chars
+                    .next_if(|c| matches!(c, '<' | '>' | '&' | '|'))
+                    .is_some()
+                {}
+                tokens.push(Token::Redirect);
+            }
+            '#' if !in_word => while chars.next_if(|c| *c != '\n').is_some() {},
+            '\'' => {
+                in_word = true;
+                word.extend(chars.by_ref().take_while(|c| *c != '\''));
+            }
+            '"' => {
+                in_word = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' if chars.peek().is_some_and(|c| "\"\\$`\n".contains(*c)) => {
+                            word.extend(chars.next().filter(|c| *c != '\n'));
+                        }
+                        _ => word.push(c),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                // a line continuation
+                Some('\n') | None => {}
+                Some(c) => {
+                    in_word = true;
+                    word.push(c);
+                }
+            },
+            '$' if chars.peek() == Some(&'(') => {
+                in_word = true;
+                word.push(c);
+                let mut depth = 0;
+                for c in chars.by_ref() {
+                    word.push(c);
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            '`' => {
+                in_word = true;
+                word.push(c);
+                word.extend(chars.by_ref().take_while(|c| *c != '`'));
+                word.push(c);
+            }
+            _ => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    end_word(&mut tokens, &mut word, &mut in_word);
+    tokens
+}
+
+fn end_word(tokens: &mut Vec<Token>, word: &mut String, in_word: &mut bool) {
+    if *in_word {
+        tokens.push(Token::Word(std::mem::take(word)));
+        *in_word = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(name: &str, args: &[&str]) -> Command {
+        Command {
+            name: name.to_string(),
+            args: args.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn test_quotes_and_escapes() {
+        let cases = [
+            ("echo 'a | b' \"c && d\"", cmd("echo", &["a | b", "c && d"])),
+            ("echo 'it'\\''s'", cmd("echo", &["it's"])),
+            (
+                r#"echo "say \"hi\" \$HOME \n""#,
+                cmd("echo", &[r#"say "hi" $HOME \n"#]),
+            ),
+            (r"echo a\ b \;", cmd("echo", &["a b", ";"])),
+            ("echo '' \"\"", cmd("echo", &["", ""])),
+            ("echo a\\\nb", cmd("echo", &["ab"])),
+            (
+                "echo $(date | tr a b) `whoami`",
+                cmd("echo", &["$(date | tr a b)", "`whoami`"]),
+            ),
+            ("ls # a | comment", cmd("ls", &[])),
+            ("echo a#b", cmd("echo", &["a#b"])),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse(line), vec![vec![expected]], "{line}");
+        }
+    }
+
+    #[test]
+    fn test_pipes_and_lists() {
+        let cases = [
+            (
+                "git log | head -n 5",
+                vec![vec![cmd("git", &["log"]), cmd("head", &["-n", "5"])]],
+            ),
+            (
+                "make |& tee log",
+                vec![vec![cmd("make", &[]), cmd("tee", &["log"])]],
+            ),
+            (
+                "make && ./run; echo done",
+                vec![
+                    vec![cmd("make", &[])],
+                    vec![cmd("./run", &[])],
+                    vec![cmd("echo", &["done"])],
+                ],
+            ),
+            (
+                "test -f x || touch x & wait",
+                vec![
+                    vec![cmd("test", &["-f", "x"])],
+                    vec![cmd("touch", &["x"])],
+                    vec![cmd("wait", &[])],
+                ],
+            ),
+            (
+                "(cd /tmp && ls)\npwd",
+                vec![
+                    vec![cmd("cd", &["/tmp"])],
+                    vec![cmd("ls", &[])],
+                    vec![cmd("pwd", &[])],
+                ],
+            ),
+            (
+                "for f in *.rs; do wc -l $f; done",
+                vec![vec![cmd("wc", &["-l", "$f"])]],
+            ),
+            ("; ;", vec![]),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse(line), expected, "{line}");
+        }
+    }
+
+    #[test]
+    fn test_redirects() {
+        let cases = [
+            ("ls > out.txt 2>&1", vec![cmd("ls", &[])]),
+            ("cmd 2>/dev/null arg", vec![cmd("cmd", &["arg"])]),
+            ("echo x &> log", vec![cmd("echo", &["x"])]),
+            (
+                "cat < in.txt | sort >> out",
+                vec![cmd("cat", &[]), cmd("sort", &[])],
+            ),
+            ("echo a2>b", vec![cmd("echo", &["a2"])]),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse(line), vec![expected], "{line}");
+        }
+    }
+
+    #[test]
+    fn test_assignments_and_wrappers() {
+        let cases = [
+            ("FOO=1 BAR=x make test", cmd("make", &["test"])),
+            ("sudo apt install vim", cmd("apt", &["install", "vim"])),
+            (
+                "sudo -u root -E systemctl restart nginx",
+                cmd("systemctl", &["restart", "nginx"]),
+            ),
+            ("env -u HOME FOO=1 cargo run", cmd("cargo", &["run"])),
+            ("nohup time -f %e xargs -n 1 rm -f", cmd("rm", &["-f"])),
+            ("command -v git", cmd("git", &[])),
+            (
+                "watch -n 5 kubectl get pods",
+                cmd("kubectl", &["get", "pods"]),
+            ),
+            // not assignments
+            ("./x=1 a", cmd("./x=1", &["a"])),
+            (
+                "git config user.name=x",
+                cmd("git", &["config", "user.name=x"]),
+            ),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse(line), vec![vec![expected]], "{line}");
+        }
+        // only assignments, or only a wrapper, run nothing
+        assert!(parse("FOO=1").is_empty());
+        assert!(parse("sudo").is_empty());
+    }
+}
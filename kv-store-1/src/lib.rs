@@ -0,0 +1,1201 @@
+#![allow(dead_code)]
+use std::{
+    borrow::Borrow,
+    cell::RefCell,
+    collections::{btree_map::Entry, BTreeMap, BTreeSet, HashSet},
+    fmt, io, mem,
+    ops::{Bound, RangeBounds},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use wal::{Op, Wal};
+
+pub mod resp;
+pub mod server;
+mod wal;
+
+#[test]
+fn test_db() {
+    let mut db = Db::default();
+    db.set("key1", "val1").unwrap();
+    assert_eq!(db.get("key1"), Some("val1"));
+    db.delete("key1").unwrap();
+    assert_eq!(db.get("key1"), None);
+    assert_eq!(db.get("key_ne"), None);
+}
+
+#[test]
+fn test_tx() {
+    let mut db = Db::default();
+    db.set("key0", "val0").unwrap();
+    assert_eq!(db.get("key0"), Some("val0"));
+    db.begin();
+    assert_eq!(db.get("key0"), Some("val0"));
+    db.set("key1", "val1").unwrap();
+    assert_eq!(db.get("key1"), Some("val1"));
+    db.commit().unwrap();
+    assert_eq!(db.get("key1"), Some("val1"));
+}
+
+#[test]
+fn test_tx_2() {
+    let mut db = Db::default();
+    db.begin();
+    db.set("key2", "val2").unwrap();
+    assert_eq!(db.get("key2"), Some("val2"));
+    db.rollback();
+    assert_eq!(db.get("key2"), None);
+}
+
+#[test]
+fn test_tx_3() {
+    let mut db = Db::default();
+    db.set("key3", "val3").unwrap();
+    assert_eq!(db.get("key3"), Some("val3"));
+    db.begin();
+    db.delete("key3").unwrap();
+    assert_eq!(db.get("key3"), None);
+    db.rollback();
+    assert_eq!(db.get("key3"), Some("val3"));
+}
+
+#[test]
+fn test_tx_4() {
+    let mut db = Db::default();
+    db.set("key3", "val3").unwrap();
+    assert_eq!(db.get("key3"), Some("val3"));
+    db.begin();
+    db.delete("key3").unwrap();
+    assert_eq!(db.get("key3"), None);
+    db.commit().unwrap();
+    assert_eq!(db.get("key3"), None);
+}
+
+#[test]
+fn test_tx_5() {
+    let mut db = Db::default();
+    db.begin();
+    db.begin();
+    db.set("key1", "val1").unwrap();
+    assert_eq!(db.get("key1"), Some("val1"));
+    db.commit().unwrap();
+    db.rollback();
+    assert_eq!(db.get("key1"), None);
+}
+
+#[test]
+fn test_tx_6() {
+    let mut db = Db::default();
+    db.set("key1", "val1").unwrap();
+    assert_eq!(db.get("key1"), Some("val1"));
+    db.begin();
+    db.delete("key1").unwrap();
+    assert_eq!(db.get("key1"), None);
+    db.rollback();
+    assert_eq!(db.get("key1"), Some("val1"));
+}
+
+#[test]
+fn test_tx_7() {
+    let mut db = Db::default();
+    db.set("key1", "val1").unwrap();
+    db.begin();
+    db.begin();
+    db.delete("key1").unwrap();
+    db.commit().unwrap();
+    assert_eq!(db.get("key1"), None);
+    db.commit().unwrap();
+    assert_eq!(db.get("key1"), None);
+}
+
+#[test]
+fn test_snapshot() {
+    let mut db = Db::default();
+    let mut other = Session::default();
+    db.set("key1", "val1").unwrap();
+    db.begin();
+    assert_eq!(db.get("key2"), None);
+    db.session(&mut other, |db| {
+        db.set("key1", "val2").unwrap();
+        db.set("key2", "val2").unwrap();
+    });
+    // the transaction keeps reading the store as it was at its begin
+    assert_eq!(db.get("key1"), Some("val1"));
+    assert_eq!(db.get("key2"), None);
+    db.rollback();
+    assert_eq!(db.get("key1"), Some("val2"));
+    assert_eq!(db.get("key2"), Some("val2"));
+}
+
+#[test]
+fn test_conflict() {
+    let mut db = Db::default();
+    let mut other = Session::default();
+    db.begin();
+    db.set("key1", "val1").unwrap();
+    db.session(&mut other, |db| {
+        db.begin();
+        db.set("key1", "other").unwrap();
+        db.commit().unwrap();
+    });
+    assert!(matches!(db.commit(), Err(Error::Conflict)));
+    assert!(!db.in_tx());
+    assert_eq!(db.get("key1"), Some("other"));
+
+    // transactions that touch other keys commit
+    db.begin();
+    db.set("key2", "val2").unwrap();
+    db.session(&mut other, |db| db.set("key3", "val3").unwrap());
+    db.commit().unwrap();
+    assert_eq!(db.get("key2"), Some("val2"));
+}
+
+#[test]
+fn test_write_skew() {
+    // each transaction reads the key the other writes, so either order of
+    // the two would see the write of the other one
+    let mut db = Db::default();
+    let mut other = Session::default();
+    db.begin();
+    db.session(&mut other, |db| db.begin());
+    assert_eq!(db.get("key1"), None);
+    db.set("key2", "val2").unwrap();
+    db.session(&mut other, |db| {
+        assert_eq!(db.get("key2"), None);
+        db.set("key1", "val1").unwrap();
+        db.commit().unwrap();
+    });
+    assert!(matches!(db.commit(), Err(Error::Conflict)));
+    assert_eq!(db.get("key2"), None);
+}
+
+#[test]
+fn test_versions_dropped() {
+    let mut db = Db::default();
+    let mut other = Session::default();
+    db.set("key1", "val1").unwrap();
+    db.session(&mut other, |db| db.begin());
+    db.set("key1", "val2").unwrap();
+    db.delete("key1").unwrap();
+    assert_eq!(db.store.vals[&Key::from("key1")].len(), 3);
+    db.close(other);
+    db.set("key1", "val3").unwrap();
+    assert_eq!(db.store.vals[&Key::from("key1")].len(), 1);
+    db.delete("key1").unwrap();
+    assert!(db.store.vals.is_empty());
+}
+
+#[test]
+fn test_scan() {
+    let mut db = Db::default();
+    for key in ["b", "user:2", "a", "user:1", "user:3", "c"] {
+        db.set(key, key).unwrap();
+    }
+    fn keys<'a>(vals: Vec<(&'a str, &str)>) -> Vec<&'a str> {
+        vals.into_iter().map(|(key, _)| key).collect()
+    }
+    assert_eq!(keys(db.scan("a".."c")), ["a", "b"]);
+    assert_eq!(keys(db.scan("b"..="user:1")), ["b", "c", "user:1"]);
+    assert_eq!(keys(db.scan(.."b")), ["a"]);
+    assert_eq!(keys(db.scan("c".."a")), Vec::<&str>::new());
+    assert_eq!(
+        db.prefix("user:"),
+        [
+            ("user:1", "user:1"),
+            ("user:2", "user:2"),
+            ("user:3", "user:3")
+        ]
+    );
+
+    // the writes of each transaction are merged over the ones below
+    db.begin();
+    db.delete("user:1").unwrap();
+    db.set("user:4", "new").unwrap();
+    db.begin();
+    db.set("user:1", "again").unwrap();
+    db.delete("user:2").unwrap();
+    db.set("user:0", "new").unwrap();
+    assert_eq!(
+        db.prefix("user:"),
+        [
+            ("user:0", "new"),
+            ("user:1", "again"),
+            ("user:3", "user:3"),
+            ("user:4", "new")
+        ]
+    );
+    db.rollback();
+    assert_eq!(
+        db.prefix("user:"),
+        [
+            ("user:2", "user:2"),
+            ("user:3", "user:3"),
+            ("user:4", "new")
+        ]
+    );
+    db.commit().unwrap();
+    assert_eq!(
+        keys(db.scan(..)),
+        ["a", "b", "c", "user:2", "user:3", "user:4"]
+    );
+}
+
+#[test]
+fn test_scan_conflict() {
+    let mut db = Db::default();
+    let mut other = Session::default();
+    db.set("user:1", "val1").unwrap();
+    db.begin();
+    assert_eq!(db.prefix("user:").len(), 1);
+    db.set("count", "1").unwrap();
+    db.session(&mut other, |db| db.set("users", "val").unwrap());
+    db.commit().unwrap();
+
+    // a key that appears in a range that was scanned is a conflict
+    db.begin();
+    assert_eq!(db.prefix("user:").len(), 1);
+    db.set("count", "1").unwrap();
+    db.session(&mut other, |db| db.set("user:2", "val2").unwrap());
+    assert!(matches!(db.commit(), Err(Error::Conflict)));
+}
+
+// a db with a clock that the test moves
+fn test_clock() -> (Db, Arc<AtomicU64>) {
+    let now = Arc::new(AtomicU64::new(1_000));
+    let clock = now.clone();
+    let db = Db::default().with_clock(move || clock.load(Ordering::Relaxed));
+    (db, now)
+}
+
+#[test]
+fn test_ttl() {
+    let (mut db, now) = test_clock();
+    let ms = Duration::from_millis;
+    db.set_ex("key1", "val1", ms(100)).unwrap();
+    db.set("key2", "val2").unwrap();
+    assert_eq!(db.ttl("key1"), Some(ms(100)));
+    assert_eq!(db.ttl("key2"), None);
+    assert!(db.expire("key2", ms(200)).unwrap());
+    assert!(!db.expire("key3", ms(200)).unwrap());
+
+    now.fetch_add(50, Ordering::Relaxed);
+    assert_eq!(db.get("key1"), Some("val1"));
+    assert_eq!(db.ttl("key1"), Some(ms(50)));
+    now.fetch_add(50, Ordering::Relaxed);
+    assert_eq!(db.get("key1"), None);
+    assert_eq!(db.ttl("key1"), None);
+    assert_eq!(db.scan(..), [("key2", "val2")]);
+
+    // setting a key again clears its ttl
+    db.set("key2", "val2").unwrap();
+    now.fetch_add(200, Ordering::Relaxed);
+    assert_eq!(db.get("key2"), Some("val2"));
+}
+
+#[test]
+fn test_ttl_tx() {
+    let (mut db, now) = test_clock();
+    let ms = Duration::from_millis;
+    db.set("key1", "val1").unwrap();
+    db.begin();
+    db.expire("key1", ms(100)).unwrap();
+    db.set_ex("key2", "val2", ms(100)).unwrap();
+    assert_eq!(db.ttl("key1"), Some(ms(100)));
+    db.rollback();
+    assert_eq!(db.ttl("key1"), None);
+    assert_eq!(db.get("key2"), None);
+
+    db.begin();
+    db.expire("key1", ms(100)).unwrap();
+    db.begin();
+    db.set_ex("key2", "val2", ms(100)).unwrap();
+    db.commit().unwrap();
+    db.commit().unwrap();
+    assert_eq!(db.ttl("key1"), Some(ms(100)));
+    assert_eq!(db.ttl("key2"), Some(ms(100)));
+    now.fetch_add(100, Ordering::Relaxed);
+    assert_eq!(db.scan(..), []);
+}
+
+#[test]
+fn test_sweep() {
+    let (mut db, now) = test_clock();
+    let mut other = Session::default();
+    let ms = Duration::from_millis;
+    db.set("key1", "old").unwrap();
+    db.set_ex("key2", "val2", ms(100)).unwrap();
+    db.session(&mut other, |db| db.begin());
+    db.set_ex("key1", "new", ms(100)).unwrap();
+    db.set_ex("key3", "val3", ms(300)).unwrap();
+    assert_eq!(db.sweep(), 0);
+
+    now.fetch_add(100, Ordering::Relaxed);
+    assert_eq!(db.sweep(), 2);
+    assert_eq!(db.get("key1"), None);
+    // the snapshot still reads the value from before the expiring set
+    db.session(&mut other, |db| {
+        assert_eq!(db.get("key1"), Some("old"));
+        // which changed since, so it conflicts
+        assert!(matches!(db.commit(), Err(Error::Conflict)));
+    });
+    db.set("key4", "val4").unwrap();
+    now.fetch_add(200, Ordering::Relaxed);
+    assert_eq!(db.sweep(), 1);
+    db.set("key1", "val1").unwrap();
+    assert_eq!(keys_stored(&db), ["key1", "key4"]);
+}
+
+// the keys that have versions in the store
+fn keys_stored(db: &Db) -> Vec<&str> {
+    db.store.vals.keys().map(|key| &*key.0).collect()
+}
+
+// xorshift, so that a failing case can be rerun from its seed
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+// what a client did in a transaction, with what it read
+#[derive(Debug)]
+enum Step {
+    Get(&'static str, Option<String>),
+    Scan(Vec<(String, String)>),
+    Set(&'static str, String),
+    Delete(&'static str),
+    Begin,
+    Commit,
+    Rollback,
+}
+
+#[test]
+fn test_serializable() {
+    const KEYS: [&str; 3] = ["key1", "key2", "key3"];
+    let (mut commits, mut conflicts) = (0, 0);
+    for seed in 1..=500u64 {
+        let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let mut db = Db::default();
+        let mut clients = (0..3)
+            .map(|_| (Session::default(), 0, vec![]))
+            .collect::<Vec<_>>();
+        // the steps of the committed transactions, in commit order
+        let mut committed = vec![];
+        for i in 0..60 {
+            let (session, depth, steps) = &mut clients[rng.below(3)];
+            let key = KEYS[rng.below(KEYS.len())];
+            db.session(session, |db| match rng.below(8) {
+                0 => steps.push(Step::Get(key, db.get(key).map(String::from))),
+                1 => {
+                    let scan = db.scan("key1".."key3");
+                    let scan = scan.iter().map(|(k, v)| (k.to_string(), v.to_string()));
+                    steps.push(Step::Scan(scan.collect()));
+                }
+                2 | 3 => {
+                    db.set(key, i.to_string().as_str()).unwrap();
+                    steps.push(Step::Set(key, i.to_string()));
+                }
+                4 => {
+                    db.delete(key).unwrap();
+                    steps.push(Step::Delete(key));
+                }
+                5 => {
+                    db.begin();
+                    *depth += 1;
+                    if *depth > 1 {
+                        steps.push(Step::Begin);
+                    }
+                }
+                6 if *depth > 1 => {
+                    db.commit().unwrap();
+                    *depth -= 1;
+                    steps.push(Step::Commit);
+                }
+                7 if *depth > 1 => {
+                    db.rollback();
+                    *depth -= 1;
+                    steps.push(Step::Rollback);
+                }
+                6 if *depth == 1 => {
+                    *depth = 0;
+                    let steps = mem::take(steps);
+                    match db.commit() {
+                        Ok(()) => committed.push(steps),
+                        Err(Error::Conflict) => conflicts += 1,
+                        Err(err) => panic!("{err}"),
+                    }
+                }
+                _ if *depth == 1 => {
+                    db.rollback();
+                    *depth = 0;
+                    steps.clear();
+                }
+                _ => {}
+            });
+            // a step outside of a transaction commits on its own
+            if *depth == 0 && !steps.is_empty() {
+                committed.push(mem::take(steps));
+            }
+        }
+        commits += committed.len();
+
+        // running the committed transactions one after the other reads and
+        // leaves the same values
+        let mut stack = vec![BTreeMap::new()];
+        for (n, tx) in committed.iter().enumerate() {
+            stack.push(BTreeMap::new());
+            for step in tx {
+                let get = |key: &str| {
+                    let layer = stack.iter().rev().find_map(|layer| layer.get(key));
+                    layer.cloned().flatten()
+                };
+                match step {
+                    Step::Get(key, val) => {
+                        assert_eq!(val, &get(key), "seed {seed}, transaction {n}: {tx:?}");
+                    }
+                    Step::Scan(vals) => {
+                        let expected = ["key1", "key2"]
+                            .into_iter()
+                            .filter_map(|key| Some((key.to_string(), get(key)?)))
+                            .collect::<Vec<_>>();
+                        assert_eq!(vals, &expected, "seed {seed}, transaction {n}: {tx:?}");
+                    }
+                    Step::Set(key, val) => {
+                        stack.last_mut().unwrap().insert(*key, Some(val.clone()));
+                    }
+                    Step::Delete(key) => {
+                        stack.last_mut().unwrap().insert(*key, None);
+                    }
+                    Step::Begin => stack.push(BTreeMap::new()),
+                    Step::Commit => {
+                        let layer = stack.pop().unwrap();
+                        stack.last_mut().unwrap().extend(layer);
+                    }
+                    Step::Rollback => {
+                        stack.pop();
+                    }
+                }
+            }
+            let layer = stack.pop().unwrap();
+            stack.last_mut().unwrap().extend(layer);
+        }
+        for (session, _, _) in clients {
+            db.close(session);
+        }
+        for key in KEYS {
+            let expected = stack[0].get(key).cloned().flatten();
+            assert_eq!(db.get(key).map(String::from), expected, "seed {seed}");
+        }
+    }
+    // the cases are not all trivial
+    assert!(commits > 5000 && conflicts > 100, "{commits} {conflicts}");
+}
+
+// a directory for the files of a test, empty at the start
+fn test_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("kv-store-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+// the values of the keys used by the durability tests
+fn state(db: &Db) -> Vec<Option<String>> {
+    ["key1", "key2", "key3"]
+        .iter()
+        .map(|key| db.get(*key).map(String::from))
+        .collect()
+}
+
+#[test]
+fn test_recover() {
+    let dir = test_dir("recover");
+    let mut db = Db::open(&dir).unwrap();
+    db.set("key1", "val1").unwrap();
+    db.begin();
+    db.set("key2", "val2").unwrap();
+    db.delete("key1").unwrap();
+    db.commit().unwrap();
+    db.begin();
+    db.set("key3", "val3").unwrap();
+    db.rollback();
+    let expected = state(&db);
+    drop(db);
+
+    let db = Db::open(&dir).unwrap();
+    assert_eq!(state(&db), expected);
+    assert_eq!(state(&db), vec![None, Some("val2".to_string()), None]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_recover_torn_log() {
+    let dir = test_dir("torn");
+    let log_len = || std::fs::metadata(dir.join("wal.log")).unwrap().len();
+    let mut db = Db::open(&dir).unwrap();
+    // the state after each commit and the length of the log it ends at
+    let mut commits = vec![(0, state(&db))];
+    let mut commit = |db: &Db| commits.push((log_len(), state(db)));
+    db.set("key1", "val1").unwrap();
+    commit(&db);
+    db.set("key2", "val2").unwrap();
+    commit(&db);
+    db.begin();
+    db.set("key3", "val3").unwrap();
+    db.delete("key1").unwrap();
+    db.commit().unwrap();
+    commit(&db);
+    db.delete("key2").unwrap();
+    commit(&db);
+    db.begin();
+    db.set("key1", "again").unwrap();
+    db.begin();
+    db.set("key2", "nested").unwrap();
+    db.commit().unwrap();
+    db.commit().unwrap();
+    commit(&db);
+    drop(db);
+
+    // a crash can cut the log anywhere
+    let log = std::fs::read(dir.join("wal.log")).unwrap();
+    let crashed = test_dir("torn-crashed");
+    for cut in 0..=log.len() {
+        std::fs::create_dir_all(&crashed).unwrap();
+        std::fs::write(crashed.join("wal.log"), &log[..cut]).unwrap();
+        let (len, expected) = commits
+            .iter()
+            .rev()
+            .find(|(len, _)| *len <= cut as u64)
+            .unwrap();
+        let mut db = Db::open(&crashed).unwrap();
+        assert_eq!(&state(&db), expected, "cut at {cut}");
+
+        // the torn commit is truncated, so later commits are not lost
+        let truncated = std::fs::metadata(crashed.join("wal.log")).unwrap().len();
+        assert_eq!(truncated, *len, "cut at {cut}");
+        db.set("key3", "after").unwrap();
+        drop(db);
+        let db = Db::open(&crashed).unwrap();
+        assert_eq!(db.get("key3"), Some("after"), "cut at {cut}");
+        std::fs::remove_dir_all(&crashed).unwrap();
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_compact() {
+    let dir = test_dir("compact");
+    let mut db = Db::open(&dir).unwrap();
+    db.compact_after(100);
+    for i in 0..50 {
+        db.set(format!("key{}", i % 4).as_str(), i.to_string().as_str())
+            .unwrap();
+        db.delete("key3").unwrap();
+    }
+    let log_len = std::fs::metadata(dir.join("wal.log")).unwrap().len();
+    assert!(log_len < 100 + 30, "log is {log_len} bytes");
+    assert!(dir.join("snapshot").exists());
+    let expected = state(&db);
+    assert_eq!(
+        expected,
+        vec![Some("49".to_string()), Some("46".to_string()), None]
+    );
+    drop(db);
+
+    let mut db = Db::open(&dir).unwrap();
+    assert_eq!(state(&db), expected);
+    assert_eq!(db.get("key0"), Some("48"));
+
+    // the log is replayed over a snapshot that already has its writes if
+    // there was a crash before it was emptied
+    let log = std::fs::read(dir.join("wal.log")).unwrap();
+    db.compact().unwrap();
+    drop(db);
+    std::fs::write(dir.join("wal.log"), log).unwrap();
+    let db = Db::open(&dir).unwrap();
+    assert_eq!(state(&db), expected);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_recover_ttl() {
+    let dir = test_dir("ttl");
+    let (_, now) = test_clock();
+    let open = || {
+        let clock = now.clone();
+        let db = Db::open(&dir).unwrap();
+        db.with_clock(move || clock.load(Ordering::Relaxed))
+    };
+    let mut db = open();
+    db.set_ex("key1", "val1", Duration::from_millis(100))
+        .unwrap();
+    db.set_ex("key2", "val2", Duration::from_millis(300))
+        .unwrap();
+    drop(db);
+
+    let mut db = open();
+    assert_eq!(db.ttl("key1"), Some(Duration::from_millis(100)));
+    now.fetch_add(200, Ordering::Relaxed);
+    // keys that expired are left out of the snapshot
+    db.compact().unwrap();
+    drop(db);
+    let db = open();
+    assert_eq!(keys_stored(&db), ["key2"]);
+    assert_eq!(db.ttl("key2"), Some(Duration::from_millis(100)));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Why a commit failed
+#[derive(Debug)]
+pub enum Error {
+    /// A key the transaction read or wrote, or a key in a range it scanned,
+    /// was committed by another transaction after its snapshot was taken, so
+    /// it was rolled back
+    Conflict,
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Conflict => write!(f, "transaction conflicts with a concurrent commit"),
+            Error::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// A store of committed values, and the open transactions of the current
+/// session. Each session's transaction reads from a snapshot of the store
+/// taken at its outermost begin.
+///
+/// A key can expire at a deadline, in milliseconds since the unix epoch. An
+/// expired key is hidden from every read as soon as it expires, whatever the
+/// snapshot, and its values are removed by `sweep`.
+pub struct Db {
+    store: Storage,
+    tx: Option<Tx>,
+    // the versions of the store that open transactions read from, with the
+    // number of transactions reading each
+    snapshots: BTreeMap<u64, usize>,
+    wal: Option<Wal>, // None if nothing is persisted
+    clock: Box<dyn Fn() -> u64 + Send>,
+}
+
+impl Default for Db {
+    fn default() -> Self {
+        Self {
+            store: Storage::default(),
+            tx: None,
+            snapshots: BTreeMap::new(),
+            wal: None,
+            clock: Box::new(|| {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+                now.as_millis() as u64
+            }),
+        }
+    }
+}
+
+impl Db {
+    /// Opens a db that persists its commits in the directory, recovering
+    /// what was committed before.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let mut store = Storage::default();
+        let wal = Wal::open(dir, |op| {
+            let write = match op {
+                Op::Set(key, val) => (key.into(), Record::Value(val.into(), None)),
+                Op::SetEx(key, val, at) => (key.into(), Record::Value(val.into(), Some(at))),
+                Op::Delete(key) => (key.into(), Record::Tombstone),
+            };
+            store.commit([write], None);
+        })?;
+        Ok(Self {
+            store,
+            wal: Some(wal),
+            ..Self::default()
+        })
+    }
+
+    /// Replaces the clock that expiry is measured with, which returns the
+    /// milliseconds since the unix epoch
+    pub fn with_clock(mut self, clock: impl Fn() -> u64 + Send + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// The size the log can grow to before it is compacted into a snapshot
+    pub fn compact_after(&mut self, len: u64) {
+        if let Some(wal) = &mut self.wal {
+            wal.compact_after(len);
+        }
+    }
+
+    /// Writes the committed state as a snapshot and empties the log
+    pub fn compact(&mut self) -> io::Result<()> {
+        let Some(wal) = &mut self.wal else {
+            return Ok(());
+        };
+        let now = (self.clock)();
+        let state = self
+            .store
+            .latest()
+            .filter(|(_, record)| record.to_val(now).is_some())
+            .map(|(key, record)| record.op(key));
+        wal.compact(state)
+    }
+
+    // persists writes that are committed to the store. the log is compacted
+    // first, so that an error means the writes did not happen.
+    fn log(&mut self, ops: &[Op]) -> io::Result<()> {
+        match &self.wal {
+            Some(wal) if !ops.is_empty() => {
+                if wal.needs_compaction() {
+                    self.compact()?;
+                }
+            }
+            _ => return Ok(()),
+        }
+        self.wal.as_mut().unwrap().append(ops)
+    }
+
+    pub fn begin(&mut self) {
+        match &mut self.tx {
+            Some(tx) => tx.layers.push(BTreeMap::new()),
+            None => {
+                let snapshot = self.store.version;
+                *self.snapshots.entry(snapshot).or_default() += 1;
+                self.tx = Some(Tx {
+                    snapshot,
+                    reads: RefCell::default(),
+                    scans: RefCell::default(),
+                    layers: vec![BTreeMap::new()],
+                });
+            }
+        }
+    }
+
+    /// Commits the innermost transaction into the one it is nested in, or
+    /// into the store. An outermost transaction that conflicts is rolled
+    /// back; one that fails to be persisted is still open.
+    pub fn commit(&mut self) -> Result<(), Error> {
+        let mut tx = self.tx.take().expect("commit without a transaction");
+        if tx.layers.len() > 1 {
+            let layer = tx.layers.pop().unwrap();
+            tx.layers.last_mut().unwrap().extend(layer);
+            self.tx = Some(tx);
+            return Ok(());
+        }
+        let writes = tx.layers.pop().unwrap();
+        let changed = writes
+            .keys()
+            .chain(tx.reads.get_mut().iter())
+            .any(|key| self.store.changed_since(key, tx.snapshot));
+        let phantom =
+            (tx.scans.get_mut().iter()).any(|scan| self.store.changed_in(scan, tx.snapshot));
+        if changed || phantom {
+            self.release(tx.snapshot);
+            return Err(Error::Conflict);
+        }
+        let ops = writes
+            .iter()
+            .map(|(key, record)| record.op(key))
+            .collect::<Vec<_>>();
+        if let Err(err) = self.log(&ops) {
+            tx.layers.push(writes);
+            self.tx = Some(tx);
+            return Err(err.into());
+        }
+        self.release(tx.snapshot);
+        self.store.commit(writes, self.oldest_snapshot());
+        Ok(())
+    }
+
+    pub fn rollback(&mut self) {
+        let Some(tx) = &mut self.tx else {
+            return;
+        };
+        tx.layers.pop();
+        if tx.layers.is_empty() {
+            let snapshot = tx.snapshot;
+            self.tx = None;
+            self.release(snapshot);
+        }
+    }
+
+    /// Whether a transaction is open
+    pub fn in_tx(&self) -> bool {
+        self.tx.is_some()
+    }
+
+    /// Runs f with the transactions of the session in place of those of the
+    /// db, so that each client of a shared db has its own stack.
+    pub fn session<T>(&mut self, session: &mut Session, f: impl FnOnce(&mut Db) -> T) -> T {
+        mem::swap(&mut self.tx, &mut session.tx);
+        let res = f(self);
+        mem::swap(&mut self.tx, &mut session.tx);
+        res
+    }
+
+    /// Rolls back what the session did not commit
+    pub fn close(&mut self, session: Session) {
+        if let Some(tx) = session.tx {
+            self.release(tx.snapshot);
+        }
+    }
+
+    fn release(&mut self, snapshot: u64) {
+        if let Entry::Occupied(mut count) = self.snapshots.entry(snapshot) {
+            *count.get_mut() -= 1;
+            if *count.get() == 0 {
+                count.remove();
+            }
+        }
+    }
+
+    fn oldest_snapshot(&self) -> Option<u64> {
+        self.snapshots.keys().next().copied()
+    }
+
+    /// Removes the values that have expired, returning how many keys expired
+    pub fn sweep(&mut self) -> usize {
+        let (now, oldest) = ((self.clock)(), self.oldest_snapshot());
+        self.store.sweep(now, oldest)
+    }
+
+    pub fn get(&self, key: impl Into<Key>) -> Option<&str> {
+        let val = self.find(key.into())?.to_val((self.clock)())?;
+        Some(&val.0)
+    }
+
+    /// The time until the key expires, None if it does not exist or does not
+    /// expire
+    pub fn ttl(&self, key: impl Into<Key>) -> Option<Duration> {
+        let now = (self.clock)();
+        match self.find(key.into())? {
+            Record::Value(_, Some(at)) if *at > now => Some(Duration::from_millis(at - now)),
+            _ => None,
+        }
+    }
+
+    // the record of the key in the innermost transaction that wrote it, or
+    // in the snapshot
+    fn find(&self, key: Key) -> Option<&Record> {
+        let Some(tx) = &self.tx else {
+            return self.store.get(&key, self.store.version);
+        };
+        for layer in tx.layers.iter().rev() {
+            if let Some(record) = layer.get(&key) {
+                return Some(record);
+            }
+        }
+        let record = self.store.get(&key, tx.snapshot);
+        tx.reads.borrow_mut().insert(key);
+        record
+    }
+
+    /// The keys in the range and their values, in order
+    pub fn scan<'a>(&self, range: impl RangeBounds<&'a str>) -> Vec<(&str, &str)> {
+        let range = (range.start_bound().cloned(), range.end_bound().cloned());
+        self.range(range, "")
+    }
+
+    /// The keys that start with the prefix and their values, in order
+    pub fn prefix(&self, prefix: &str) -> Vec<(&str, &str)> {
+        self.range((Bound::Included(prefix), Bound::Unbounded), prefix)
+    }
+
+    // the snapshot with the writes of each transaction on top of it, in the
+    // range and starting with the prefix
+    fn range(&self, range: (Bound<&str>, Bound<&str>), prefix: &str) -> Vec<(&str, &str)> {
+        let scan = Scan::new(range, prefix);
+        let (version, layers) = match &self.tx {
+            Some(tx) => (tx.snapshot, &tx.layers[..]),
+            None => (self.store.version, &[][..]),
+        };
+        let mut merged = self
+            .store
+            .range(&scan)
+            .filter_map(|(key, versions)| Some((key, Storage::at(versions, version)?)))
+            .collect::<BTreeMap<_, _>>();
+        for layer in layers {
+            merged.extend(scan.of(layer));
+        }
+        if let Some(tx) = &self.tx {
+            tx.scans.borrow_mut().push(scan);
+        }
+        let now = (self.clock)();
+        merged
+            .into_iter()
+            .filter_map(|(key, record)| Some((key.0.as_ref(), record.to_val(now)?.0.as_ref())))
+            .collect()
+    }
+
+    pub fn set(&mut self, key: impl Into<Key>, val: impl Into<Value>) -> io::Result<()> {
+        self.write(key.into(), Record::Value(val.into(), None))
+    }
+
+    /// Sets the value of the key until the ttl has passed
+    pub fn set_ex(
+        &mut self,
+        key: impl Into<Key>,
+        val: impl Into<Value>,
+        ttl: Duration,
+    ) -> io::Result<()> {
+        let at = (self.clock)().saturating_add(ttl.as_millis() as u64);
+        self.write(key.into(), Record::Value(val.into(), Some(at)))
+    }
+
+    /// Makes the key expire once the ttl has passed, returning whether it
+    /// exists
+    pub fn expire(&mut self, key: impl Into<Key>, ttl: Duration) -> io::Result<bool> {
+        let key = key.into();
+        let Some(val) = self.get(key.clone()).map(Value::from) else {
+            return Ok(false);
+        };
+        self.set_ex(key, val, ttl)?;
+        Ok(true)
+    }
+
+    pub fn delete(&mut self, key: impl Into<Key>) -> io::Result<()> {
+        self.write(key.into(), Record::Tombstone)
+    }
+
+    // a write outside of a transaction is committed on its own
+    fn write(&mut self, key: Key, record: Record) -> io::Result<()> {
+        if let Some(tx) = &mut self.tx {
+            tx.layers.last_mut().unwrap().insert(key, record);
+            return Ok(());
+        }
+        self.log(&[record.op(&key)])?;
+        self.store.commit([(key, record)], self.oldest_snapshot());
+        Ok(())
+    }
+}
+
+/// The open transactions of one client of a db
+#[derive(Default)]
+pub struct Session {
+    tx: Option<Tx>,
+}
+
+/// An outermost transaction and the transactions nested in it
+struct Tx {
+    snapshot: u64,
+    // the keys and ranges read from the store, which must not change before
+    // the commit for the transaction to be serializable
+    reads: RefCell<HashSet<Key>>,
+    scans: RefCell<Vec<Scan>>,
+    // the writes of each nested transaction, a tombstone for a delete
+    layers: Vec<BTreeMap<Key, Record>>,
+}
+
+/// The keys in a range that start with a prefix
+struct Scan {
+    start: Bound<Key>,
+    end: Bound<Key>,
+    prefix: Key,
+}
+
+impl Scan {
+    fn new((start, end): (Bound<&str>, Bound<&str>), prefix: &str) -> Self {
+        Self {
+            start: start.map(Key::from),
+            end: end.map(Key::from),
+            prefix: Key::from(prefix),
+        }
+    }
+
+    // the entries of the map in the scan
+    fn of<'a, V>(&self, map: &'a BTreeMap<Key, V>) -> impl Iterator<Item = (&'a Key, &'a V)> {
+        let start = self.start.as_ref().map(|key| &*key.0);
+        let end = self.end.as_ref().map(|key| &*key.0);
+        // a range that ends before it starts makes BTreeMap::range panic
+        let empty = match (start, end) {
+            (Bound::Excluded(s), Bound::Excluded(e)) => s >= e,
+            (Bound::Included(s) | Bound::Excluded(s), Bound::Included(e) | Bound::Excluded(e)) => {
+                s > e
+            }
+            _ => false,
+        };
+        let prefix = self.prefix.clone();
+        (!empty)
+            .then(|| map.range::<str, _>((start, end)))
+            .into_iter()
+            .flatten()
+            .take_while(move |(key, _)| key.0.starts_with(&*prefix.0))
+    }
+}
+
+#[derive(Hash, Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
+pub struct Key(Arc<str>);
+
+impl From<&str> for Key {
+    fn from(value: &str) -> Self {
+        Key(Arc::from(value))
+    }
+}
+
+impl Borrow<str> for Key {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+enum Record {
+    /// A value and when it expires
+    Value(Value, Option<u64>),
+    Tombstone,
+}
+
+impl Record {
+    // the value, None if it is deleted or has expired
+    fn to_val(&self, now: u64) -> Option<&Value> {
+        match self {
+            Record::Value(val, at) if at.is_none_or(|at| at > now) => Some(val),
+            _ => None,
+        }
+    }
+
+    fn op<'a>(&'a self, key: &'a Key) -> Op<'a> {
+        match self {
+            Record::Value(val, None) => Op::Set(&key.0, &val.0),
+            Record::Value(val, Some(at)) => Op::SetEx(&key.0, &val.0, *at),
+            Record::Tombstone => Op::Delete(&key.0),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Value(Arc<str>);
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value(Arc::from(value))
+    }
+}
+
+/// The committed versions of each key. Every commit creates a version of
+/// the store, and the versions of a key are kept, oldest first, for as long
+/// as a snapshot may read them.
+#[derive(Default)]
+struct Storage {
+    version: u64,
+    vals: BTreeMap<Key, Vec<(u64, Record)>>,
+    // the keys that were written with an expiry, by when they expire
+    expiries: BTreeSet<(u64, Key)>,
+}
+
+impl Storage {
+    // the record of the key in a version of the store
+    fn get(&self, key: &Key, version: u64) -> Option<&Record> {
+        Self::at(self.vals.get(key)?, version)
+    }
+
+    fn at(versions: &[(u64, Record)], version: u64) -> Option<&Record> {
+        let (_, record) = versions.iter().rev().find(|(v, _)| *v <= version)?;
+        Some(record)
+    }
+
+    fn range<'a>(&'a self, scan: &Scan) -> impl Iterator<Item = (&'a Key, &'a Vec<(u64, Record)>)> {
+        scan.of(&self.vals)
+    }
+
+    fn changed_since(&self, key: &Key, version: u64) -> bool {
+        self.vals
+            .get(key)
+            .is_some_and(|versions| Self::changed(versions, version))
+    }
+
+    fn changed_in(&self, scan: &Scan, version: u64) -> bool {
+        self.range(scan)
+            .any(|(_, versions)| Self::changed(versions, version))
+    }
+
+    fn changed(versions: &[(u64, Record)], version: u64) -> bool {
+        versions.last().is_some_and(|(v, _)| *v > version)
+    }
+
+    // the current records
+    fn latest(&self) -> impl Iterator<Item = (&Key, &Record)> {
+        self.vals
+            .iter()
+            .filter_map(|(key, versions)| Some((key, &versions.last()?.1)))
+    }
+
+    // creates a version of the store with the writes
+    fn commit(&mut self, writes: impl IntoIterator<Item = (Key, Record)>, oldest: Option<u64>) {
+        self.version += 1;
+        for (key, record) in writes {
+            if let Record::Value(_, Some(at)) = record {
+                self.expiries.insert((at, key.clone()));
+            }
+            self.vals
+                .entry(key.clone())
+                .or_default()
+                .push((self.version, record));
+            self.prune(&key, oldest);
+        }
+    }
+
+    // replaces the values that have expired with tombstones. an expired
+    // value is hidden from every snapshot, so this is not a new version.
+    fn sweep(&mut self, now: u64, oldest: Option<u64>) -> usize {
+        let mut expired = 0;
+        while let Some((at, key)) = self.expiries.first().cloned() {
+            if at > now {
+                break;
+            }
+            self.expiries.pop_first();
+            let Some(versions) = self.vals.get_mut(&key) else {
+                continue;
+            };
+            let mut swept = false;
+            for (_, record) in versions.iter_mut() {
+                if let Record::Value(_, Some(at)) = record {
+                    if *at <= now {
+                        *record = Record::Tombstone;
+                        swept = true;
+                    }
+                }
+            }
+            expired += swept as usize;
+            self.prune(&key, oldest);
+        }
+        expired
+    }
+
+    // drops the versions of the key that are older than what the oldest
+    // snapshot reads
+    fn prune(&mut self, key: &Key, oldest: Option<u64>) {
+        let oldest = oldest.unwrap_or(self.version);
+        let Some(versions) = self.vals.get_mut(key) else {
+            return;
+        };
+        let visible = versions
+            .iter()
+            .rposition(|(v, _)| *v <= oldest)
+            .unwrap_or(0);
+        versions.drain(..visible);
+        if let [(v, Record::Tombstone)] = versions[..] {
+            if v <= oldest {
+                self.vals.remove(key);
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::io::{self, BufRead, Write};
+
+// bounds on what a client can make the server allocate
+const MAX_ARGS: usize = 1 << 20;
+const MAX_BULK: usize = 64 << 20;
+
+/// A reply to a command
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Reply {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<String>),
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    pub fn ok() -> Self {
+        Reply::Simple("OK".into())
+    }
+
+    pub fn error(msg: impl Into<String>) -> Self {
+        Reply::Error(msg.into())
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        match self {
+            Reply::Simple(s) => write!(w, "+{s}\r\n"),
+            Reply::Error(s) => write!(w, "-{s}\r\n"),
+            Reply::Integer(n) => write!(w, ":{n}\r\n"),
+            Reply::Bulk(None) => write!(w, "$-1\r\n"),
+            Reply::Bulk(Some(s)) => write!(w, "${}\r\n{s}\r\n", s.len()),
+            Reply::Array(replies) => {
+                write!(w, "*{}\r\n", replies.len())?;
+                replies.iter().try_for_each(|reply| reply.write(w))
+            }
+        }
+    }
+}
+
+/// Reads the arguments of the next command, which is either an array of bulk
+/// strings or an inline command separated by spaces. None is returned at the
+/// end of the stream and an InvalidData error if the command is malformed.
+/// A blank line is an empty command.
+pub fn read_command(r: &mut impl BufRead) -> io::Result<Option<Vec<Vec<u8>>>> {
+    let Some(line) = read_line(r)? else {
+        return Ok(None);
+    };
+    let Some(count) = line.strip_prefix(b"*") else {
+        let args = line
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .map(<[u8]>::to_vec)
+            .collect();
+        return Ok(Some(args));
+    };
+    let count = parse_len(count, MAX_ARGS)?.unwrap_or(0);
+    let mut args = Vec::with_capacity(count.min(1024));
+    for _ in 0..count {
+        let line = read_line(r)?.ok_or_else(|| protocol_error("unexpected end of stream"))?;
+        let len = line
+            .strip_prefix(b"$")
+            .ok_or_else(|| protocol_error("expected '$'"))?;
+        let len = parse_len(len, MAX_BULK)?.ok_or_else(|| protocol_error("null bulk string"))?;
+        let mut arg = vec![0; len + 2];
+        r.read_exact(&mut arg).map_err(|err| match err.kind() {
+            io::ErrorKind::UnexpectedEof => protocol_error("unexpected end of stream"),
+            _ => err,
+        })?;
+        if !arg.ends_with(b"\r\n") {
+            return Err(protocol_error("expected CRLF after bulk string"));
+        }
+        arg.truncate(len);
+        args.push(arg);
+    }
+    Ok(Some(args))
+}
+
+// a line without its line ending, None at the end of the stream
+fn read_line(r: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut line = vec![];
+    // an inline command has no length to bound it
+    io::Read::take(&mut *r, MAX_BULK as u64).read_until(b'\n', &mut line)?;
+    match line.last() {
+        None => Ok(None),
+        Some(b'\n') => {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            Ok(Some(line))
+        }
+        Some(_) if line.len() == MAX_BULK => Err(protocol_error("line too long")),
+        Some(_) => Err(protocol_error("unexpected end of stream")),
+    }
+}
+
+// a length, None if it is negative
+fn parse_len(len: &[u8], max: usize) -> io::Result<Option<usize>> {
+    let len = std::str::from_utf8(len)
+        .ok()
+        .and_then(|len| len.parse::<i64>().ok())
+        .ok_or_else(|| protocol_error("invalid length"))?;
+    match usize::try_from(len) {
+        Ok(len) if len > max => Err(protocol_error("length too large")),
+        Ok(len) => Ok(Some(len)),
+        Err(_) => Ok(None),
+    }
+}
+
+fn protocol_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Protocol error: {msg}"))
+}
+
+#[test]
+fn test_read_command() {
+    let mut r = &b"*2\r\n$3\r\nGET\r\n$4\r\nk\r\ny\r\nPING  hi\r\n\r\n*0\r\n"[..];
+    assert_eq!(
+        read_command(&mut r).unwrap(),
+        Some(vec![b"GET".to_vec(), b"k\r\ny".to_vec()])
+    );
+    assert_eq!(
+        read_command(&mut r).unwrap(),
+        Some(vec![b"PING".to_vec(), b"hi".to_vec()])
+    );
+    assert_eq!(read_command(&mut r).unwrap(), Some(vec![]));
+    assert_eq!(read_command(&mut r).unwrap(), Some(vec![]));
+    assert_eq!(read_command(&mut r).unwrap(), None);
+    for bad in [
+        &b"*1\r\n:1\r\n"[..],
+        b"*x\r\n",
+        b"*1\r\n$1\r\nab\r\n",
+        b"*1\r\n$3\r\nab",
+    ] {
+        let err = read_command(&mut &bad[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{bad:?}");
+    }
+}
+
+#[test]
+fn test_write_reply() {
+    let reply = Reply::Array(vec![
+        Reply::ok(),
+        Reply::error("ERR no"),
+        Reply::Integer(-1),
+        Reply::Bulk(Some("a\r\nb".into())),
+        Reply::Bulk(None),
+    ]);
+    let mut buf = vec![];
+    reply.write(&mut buf).unwrap();
+    assert_eq!(
+        buf,
+        b"*5\r\n+OK\r\n-ERR no\r\n:-1\r\n$4\r\na\r\nb\r\n$-1\r\n"
+    );
+}
@@ -0,0 +1,325 @@
+use crate::{
+    resp::{self, Reply},
+    Db, Error, Session,
+};
+use std::{
+    io::{self, BufReader, BufWriter, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+// how often keys that expired are removed
+const SWEEP_EVERY: Duration = Duration::from_millis(100);
+
+/// A command of a client
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Ping(Option<String>),
+    Get(String),
+    Set(String, String, Option<Duration>),
+    Del(Vec<String>),
+    Expire(String, Duration),
+    Ttl(String),
+    Begin,
+    Commit,
+    Rollback,
+    Multi,
+    Exec,
+    Discard,
+}
+
+impl Command {
+    fn parse(args: Vec<Vec<u8>>) -> Result<Self, String> {
+        let mut args = args
+            .into_iter()
+            .map(String::from_utf8)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| "ERR arguments must be valid utf-8".to_string())?
+            .into_iter();
+        let name = args.next().unwrap_or_default().to_ascii_uppercase();
+        let arity = |ok: bool| match ok {
+            true => Ok(()),
+            false => Err(format!(
+                "ERR wrong number of arguments for '{}' command",
+                name.to_ascii_lowercase()
+            )),
+        };
+        let cmd = match name.as_str() {
+            "PING" => {
+                arity(args.len() <= 1)?;
+                Command::Ping(args.next())
+            }
+            "GET" => {
+                arity(args.len() == 1)?;
+                Command::Get(args.next().unwrap())
+            }
+            "SET" => {
+                arity(args.len() >= 2)?;
+                let (key, val) = (args.next().unwrap(), args.next().unwrap());
+                let ttl = match (args.next(), args.next(), args.next()) {
+                    (None, _, _) => None,
+                    (Some(unit), Some(ttl), None) => {
+                        let ttl = parse_int(&ttl)?;
+                        if ttl <= 0 {
+                            return Err("ERR invalid expire time in 'set' command".into());
+                        }
+                        match unit.to_ascii_uppercase().as_str() {
+                            "EX" => Some(Duration::from_secs(ttl as u64)),
+                            "PX" => Some(Duration::from_millis(ttl as u64)),
+                            _ => return Err("ERR syntax error".into()),
+                        }
+                    }
+                    _ => return Err("ERR syntax error".into()),
+                };
+                Command::Set(key, val, ttl)
+            }
+            "DEL" => {
+                arity(args.len() > 0)?;
+                Command::Del(args.collect())
+            }
+            "EXPIRE" => {
+                arity(args.len() == 2)?;
+                let key = args.next().unwrap();
+                // a ttl that is not positive expires the key now
+                let ttl = parse_int(&args.next().unwrap())?.max(0);
+                Command::Expire(key, Duration::from_secs(ttl as u64))
+            }
+            "TTL" => {
+                arity(args.len() == 1)?;
+                Command::Ttl(args.next().unwrap())
+            }
+            "BEGIN" | "COMMIT" | "ROLLBACK" | "MULTI" | "EXEC" | "DISCARD" => {
+                arity(args.len() == 0)?;
+                match name.as_str() {
+                    "BEGIN" => Command::Begin,
+                    "COMMIT" => Command::Commit,
+                    "ROLLBACK" => Command::Rollback,
+                    "MULTI" => Command::Multi,
+                    "EXEC" => Command::Exec,
+                    _ => Command::Discard,
+                }
+            }
+            _ => return Err(format!("ERR unknown command '{name}'")),
+        };
+        Ok(cmd)
+    }
+
+    fn run(self, db: &mut Db) -> Reply {
+        let res = match self {
+            Command::Ping(None) => Ok(Reply::Simple("PONG".into())),
+            Command::Ping(msg) => Ok(Reply::Bulk(msg)),
+            Command::Get(key) => Ok(Reply::Bulk(db.get(key.as_str()).map(String::from))),
+            Command::Set(key, val, None) => db
+                .set(key.as_str(), val.as_str())
+                .map(|_| Reply::ok())
+                .map_err(Error::from),
+            Command::Set(key, val, Some(ttl)) => db
+                .set_ex(key.as_str(), val.as_str(), ttl)
+                .map(|_| Reply::ok())
+                .map_err(Error::from),
+            Command::Del(keys) => (|| {
+                let mut deleted = 0;
+                for key in keys {
+                    if db.get(key.as_str()).is_some() {
+                        db.delete(key.as_str())?;
+                        deleted += 1;
+                    }
+                }
+                Ok(Reply::Integer(deleted))
+            })(),
+            Command::Expire(key, ttl) => db
+                .expire(key.as_str(), ttl)
+                .map(|exists| Reply::Integer(exists as i64))
+                .map_err(Error::from),
+            // -2 for a key that does not exist and -1 for one that does
+            // not expire, with the ttl rounded to the nearest second
+            Command::Ttl(key) => Ok(Reply::Integer(match db.ttl(key.as_str()) {
+                Some(ttl) => ((ttl.as_millis() + 500) / 1000) as i64,
+                None if db.get(key.as_str()).is_some() => -1,
+                None => -2,
+            })),
+            Command::Begin => {
+                db.begin();
+                Ok(Reply::ok())
+            }
+            Command::Commit if !db.in_tx() => Ok(Reply::error("ERR COMMIT without BEGIN")),
+            Command::Commit => db.commit().map(|_| Reply::ok()),
+            Command::Rollback if !db.in_tx() => Ok(Reply::error("ERR ROLLBACK without BEGIN")),
+            Command::Rollback => {
+                db.rollback();
+                Ok(Reply::ok())
+            }
+            // handled by the connection
+            Command::Multi | Command::Exec | Command::Discard => {
+                Ok(Reply::error("ERR command not allowed here"))
+            }
+        };
+        res.unwrap_or_else(|err| Reply::error(format!("ERR {err}")))
+    }
+}
+
+fn parse_int(arg: &str) -> Result<i64, String> {
+    arg.parse()
+        .map_err(|_| "ERR value is not an integer or out of range".into())
+}
+
+/// Serves the db to the clients that connect to the listener, each with its
+/// own stack of transactions over the committed store. This never returns.
+pub fn serve(listener: TcpListener, db: Db) {
+    let db = Arc::new(Mutex::new(db));
+    let sweeper = db.clone();
+    thread::spawn(move || loop {
+        thread::sleep(SWEEP_EVERY);
+        sweeper.lock().unwrap().sweep();
+    });
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("accept failed: {err}");
+                continue;
+            }
+        };
+        let db = db.clone();
+        thread::spawn(move || {
+            let mut conn = Connection::new(&db);
+            if let Err(err) = conn.handle(stream) {
+                eprintln!("connection failed: {err}");
+            }
+            db.lock().unwrap().close(conn.session);
+        });
+    }
+}
+
+/// The commands queued by MULTI
+#[derive(Default)]
+struct Multi {
+    cmds: Vec<Command>,
+    // a command was rejected, so EXEC discards the queue
+    aborted: bool,
+}
+
+struct Connection<'a> {
+    db: &'a Mutex<Db>,
+    session: Session,
+    multi: Option<Multi>,
+}
+
+impl<'a> Connection<'a> {
+    fn new(db: &'a Mutex<Db>) -> Self {
+        Self {
+            db,
+            session: Session::default(),
+            multi: None,
+        }
+    }
+
+    fn handle(&mut self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+        loop {
+            let args = match resp::read_command(&mut reader) {
+                Ok(Some(args)) => args,
+                Ok(None) => return writer.flush(),
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                    Reply::error(format!("ERR {err}")).write(&mut writer)?;
+                    return writer.flush();
+                }
+                Err(err) => return Err(err),
+            };
+            if args.is_empty() {
+                continue;
+            }
+            self.command(args).write(&mut writer)?;
+            // pipelined commands are answered together
+            if reader.buffer().is_empty() {
+                writer.flush()?;
+            }
+        }
+    }
+
+    fn command(&mut self, args: Vec<Vec<u8>>) -> Reply {
+        let cmd = match Command::parse(args) {
+            Ok(cmd) => cmd,
+            Err(msg) => {
+                if let Some(multi) = &mut self.multi {
+                    multi.aborted = true;
+                }
+                return Reply::Error(msg);
+            }
+        };
+        match (cmd, &mut self.multi) {
+            (Command::Multi, Some(_)) => Reply::error("ERR MULTI calls can not be nested"),
+            (Command::Multi, None) => {
+                self.multi = Some(Multi::default());
+                Reply::ok()
+            }
+            (Command::Exec, None) => Reply::error("ERR EXEC without MULTI"),
+            (Command::Exec, Some(_)) => {
+                let multi = self.multi.take().unwrap();
+                if multi.aborted {
+                    Reply::error("EXECABORT Transaction discarded because of previous errors.")
+                } else {
+                    Reply::Array(self.run(multi.cmds))
+                }
+            }
+            (Command::Discard, None) => Reply::error("ERR DISCARD without MULTI"),
+            (Command::Discard, Some(_)) => {
+                self.multi = None;
+                Reply::ok()
+            }
+            (cmd, Some(multi)) => {
+                multi.cmds.push(cmd);
+                Reply::Simple("QUEUED".into())
+            }
+            (cmd, None) => self.run(vec![cmd]).pop().unwrap(),
+        }
+    }
+
+    // runs the commands without commands of other connections in between
+    fn run(&mut self, cmds: Vec<Command>) -> Vec<Reply> {
+        let mut db = self.db.lock().unwrap();
+        db.session(&mut self.session, |db| {
+            cmds.into_iter().map(|cmd| cmd.run(db)).collect()
+        })
+    }
+}
+
+#[test]
+fn test_parse() {
+    let parse =
+        |args: &[&str]| Command::parse(args.iter().map(|a| a.as_bytes().to_vec()).collect());
+    assert_eq!(parse(&["get", "k"]), Ok(Command::Get("k".into())));
+    assert_eq!(
+        parse(&["Del", "a", "b"]),
+        Ok(Command::Del(vec!["a".into(), "b".into()]))
+    );
+    assert_eq!(parse(&["PING"]), Ok(Command::Ping(None)));
+    assert_eq!(
+        parse(&["SET", "k", "v", "px", "1500"]),
+        Ok(Command::Set(
+            "k".into(),
+            "v".into(),
+            Some(Duration::from_millis(1500))
+        ))
+    );
+    assert_eq!(
+        parse(&["SET", "k", "v", "EX"]),
+        Err("ERR syntax error".into())
+    );
+    assert_eq!(
+        parse(&["SET", "k", "v", "EX", "0"]),
+        Err("ERR invalid expire time in 'set' command".into())
+    );
+    assert_eq!(
+        parse(&["EXPIRE", "k", "x"]),
+        Err("ERR value is not an integer or out of range".into())
+    );
+    assert_eq!(
+        parse(&["SET", "k"]),
+        Err("ERR wrong number of arguments for 'set' command".into())
+    );
+    assert_eq!(parse(&["FOO"]), Err("ERR unknown command 'FOO'".into()));
+}
@@ -0,0 +1,320 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// A committed write, as it is written to the log
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Op<'a> {
+    Set(&'a str, &'a str),
+    /// A set that expires at a deadline, in milliseconds since the epoch
+    SetEx(&'a str, &'a str, u64),
+    Delete(&'a str),
+}
+
+const SET: u8 = 0;
+const DELETE: u8 = 1;
+const SET_EX: u8 = 2;
+
+/// An append-only log of committed writes. Each commit is one record holding
+/// all of its writes, so a commit is recovered entirely or not at all:
+///
+///   record := len u32 | crc32 of payload u32 | payload
+///   payload := count u32 | op*
+///   op := 0 | key | val, 1 | key, or 2 | key | val | deadline u64, where
+///         key and val are len u32 | bytes
+///
+/// Compaction writes the state as a snapshot and starts an empty log.
+pub struct Wal {
+    dir: PathBuf,
+    log: File,
+    len: u64,
+    compact_after: u64,
+    /// the log may hold a torn record after `len`, from an append that failed
+    torn: bool,
+    /// the next append writes only this many bytes and fails
+    #[cfg(test)]
+    short_write: Option<usize>,
+}
+
+impl Wal {
+    /// Opens the log in the directory, creating it if needed, and replays the
+    /// snapshot and then the log. A record that was torn by a crash, and
+    /// anything after it, is truncated.
+    pub fn open(dir: impl AsRef<Path>, mut apply: impl FnMut(Op)) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        match fs::read(dir.join("snapshot")) {
+            Ok(snapshot) => {
+                // snapshots are renamed into place, so they are never torn
+                if replay(&snapshot, &mut apply) != snapshot.len() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "corrupt snapshot",
+                    ));
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        let mut log = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(dir.join("wal.log"))?;
+        let mut buf = vec![];
+        log.read_to_end(&mut buf)?;
+        let len = replay(&buf, &mut apply) as u64;
+        if len < buf.len() as u64 {
+            log.set_len(len)?;
+            log.sync_all()?;
+        }
+        Ok(Self {
+            dir,
+            log,
+            len,
+            compact_after: 1 << 20,
+            torn: false,
+            #[cfg(test)]
+            short_write: None,
+        })
+    }
+
+    /// The size of the log that triggers compaction
+    pub fn compact_after(&mut self, len: u64) {
+        self.compact_after = len;
+    }
+
+    pub fn needs_compaction(&self) -> bool {
+        self.len >= self.compact_after
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Durably appends the writes of a commit. If it fails, whatever part of
+    /// the record was written is truncated, so that later commits are not
+    /// written after a torn record that recovery would stop at.
+    pub fn append(&mut self, ops: &[Op]) -> io::Result<()> {
+        if self.torn {
+            self.truncate()?;
+        }
+        let record = encode(ops);
+        if let Err(err) = self.write(&record) {
+            self.torn = true;
+            // if this fails too, the next append tries again
+            let _ = self.truncate();
+            return Err(err);
+        }
+        self.len += record.len() as u64;
+        Ok(())
+    }
+
+    fn write(&mut self, record: &[u8]) -> io::Result<()> {
+        #[cfg(test)]
+        if let Some(n) = self.short_write.take() {
+            self.log.write_all(&record[..n])?;
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "short write"));
+        }
+        self.log.write_all(record)?;
+        self.log.sync_data()
+    }
+
+    // cuts the log back to the records that were appended
+    fn truncate(&mut self) -> io::Result<()> {
+        self.log.set_len(self.len)?;
+        self.log.sync_data()?;
+        self.torn = false;
+        Ok(())
+    }
+
+    /// Replaces the snapshot with the given state and empties the log. A
+    /// crash before the log is emptied replays it over the new snapshot,
+    /// which leaves the same state.
+    pub fn compact<'a>(&mut self, state: impl Iterator<Item = Op<'a>>) -> io::Result<()> {
+        let tmp = self.dir.join("snapshot.tmp");
+        let mut w = BufWriter::new(File::create(&tmp)?);
+        for op in state {
+            w.write_all(&encode(&[op]))?;
+        }
+        w.into_inner()?.sync_all()?;
+        fs::rename(&tmp, self.dir.join("snapshot"))?;
+        File::open(&self.dir)?.sync_all()?;
+        self.log.set_len(0)?;
+        self.log.sync_all()?;
+        self.len = 0;
+        self.torn = false;
+        Ok(())
+    }
+}
+
+fn encode(ops: &[Op]) -> Vec<u8> {
+    fn put(buf: &mut Vec<u8>, s: &str) {
+        buf.extend((s.len() as u32).to_le_bytes());
+        buf.extend(s.as_bytes());
+    }
+    let mut payload = vec![];
+    payload.extend((ops.len() as u32).to_le_bytes());
+    for op in ops {
+        match op {
+            Op::Set(key, val) => {
+                payload.push(SET);
+                put(&mut payload, key);
+                put(&mut payload, val);
+            }
+            Op::SetEx(key, val, at) => {
+                payload.push(SET_EX);
+                put(&mut payload, key);
+                put(&mut payload, val);
+                payload.extend(at.to_le_bytes());
+            }
+            Op::Delete(key) => {
+                payload.push(DELETE);
+                put(&mut payload, key);
+            }
+        }
+    }
+    let mut record = Vec::with_capacity(payload.len() + 8);
+    record.extend((payload.len() as u32).to_le_bytes());
+    record.extend(crc32(&payload).to_le_bytes());
+    record.extend(payload);
+    record
+}
+
+// applies the ops of each complete record, returning the length of the
+// records that were applied
+fn replay(buf: &[u8], apply: &mut impl FnMut(Op)) -> usize {
+    let mut pos = 0;
+    while let Some((ops, len)) = decode(&buf[pos..]) {
+        ops.into_iter().for_each(&mut *apply);
+        pos += len;
+    }
+    pos
+}
+
+// the ops of the record at the start of the buffer and its length, None if
+// it is incomplete or corrupt
+fn decode(buf: &[u8]) -> Option<(Vec<Op<'_>>, usize)> {
+    let mut r = Reader(buf);
+    let len = r.u32()? as usize;
+    let crc = r.u32()?;
+    let payload = r.bytes(len)?;
+    if crc32(payload) != crc {
+        return None;
+    }
+    let mut r = Reader(payload);
+    let count = r.u32()?;
+    let mut ops = vec![];
+    for _ in 0..count {
+        let op = match r.u8()? {
+            SET => Op::Set(r.str()?, r.str()?),
+            DELETE => Op::Delete(r.str()?),
+            SET_EX => Op::SetEx(r.str()?, r.str()?, r.u64()?),
+            _ => return None,
+        };
+        ops.push(op);
+    }
+    if !r.0.is_empty() {
+        return None;
+    }
+    Some((ops, len + 8))
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> Option<&'a str> {
+        let len = self.u32()? as usize;
+        std::str::from_utf8(self.bytes(len)?).ok()
+    }
+}
+
+// crc-32/iso-hdlc, as used by zip and ethernet
+fn crc32(buf: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in buf {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+}
+
+#[test]
+fn test_decode() {
+    let ops = [
+        Op::Set("key1", "val1"),
+        Op::Delete("key2"),
+        Op::Set("", ""),
+        Op::SetEx("key3", "val3", u64::MAX),
+    ];
+    let record = encode(&ops);
+    assert_eq!(decode(&record), Some((ops.to_vec(), record.len())));
+    for len in 0..record.len() {
+        assert_eq!(decode(&record[..len]), None);
+    }
+    let mut flipped = record.clone();
+    *flipped.last_mut().unwrap() ^= 1;
+    assert_eq!(decode(&flipped), None);
+}
+
+#[test]
+fn test_append_short_write() {
+    let dir = crate::test_dir("short-write");
+    let mut wal = Wal::open(&dir, |_| {}).unwrap();
+    wal.append(&[Op::Set("key1", "val1")]).unwrap();
+    wal.short_write = Some(5);
+    assert!(wal.append(&[Op::Set("key2", "val2")]).is_err());
+    wal.append(&[Op::Delete("key1"), Op::Set("key3", "val3")])
+        .unwrap();
+    drop(wal);
+
+    // the commits on either side of the failed one are recovered
+    let mut ops = vec![];
+    let wal = Wal::open(&dir, |op| ops.push(format!("{op:?}"))).unwrap();
+    assert_eq!(
+        ops,
+        [
+            r#"Set("key1", "val1")"#,
+            r#"Delete("key1")"#,
+            r#"Set("key3", "val3")"#
+        ]
+    );
+    assert_eq!(
+        wal.len(),
+        std::fs::metadata(dir.join("wal.log")).unwrap().len()
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,207 @@
+use kv_store_1::{server, Db};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+// starts a server with an empty in-memory db
+fn start() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || server::serve(listener, Db::default()));
+    addr
+}
+
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Client {
+    fn connect(addr: &str) -> Self {
+        let stream = TcpStream::connect(addr).unwrap();
+        Self {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+        }
+    }
+
+    fn send(&mut self, args: &[&str]) {
+        let mut buf = format!("*{}\r\n", args.len());
+        for arg in args {
+            buf += &format!("${}\r\n{arg}\r\n", arg.len());
+        }
+        self.writer.write_all(buf.as_bytes()).unwrap();
+    }
+
+    // sends the command and reads its raw reply
+    fn call(&mut self, args: &[&str]) -> String {
+        self.send(args);
+        self.reply()
+    }
+
+    fn reply(&mut self) -> String {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        let len = line[1..].trim_end().parse::<i64>().unwrap_or(-1);
+        match line.as_bytes()[0] {
+            b'$' if len >= 0 => {
+                let mut bulk = vec![0; len as usize + 2];
+                self.reader.read_exact(&mut bulk).unwrap();
+                line + std::str::from_utf8(&bulk).unwrap()
+            }
+            b'*' => (0..len).fold(line, |line, _| line + &self.reply()),
+            _ => line,
+        }
+    }
+}
+
+#[test]
+fn test_commands() {
+    let mut c = Client::connect(&start());
+    assert_eq!(c.call(&["PING"]), "+PONG\r\n");
+    assert_eq!(c.call(&["GET", "key1"]), "$-1\r\n");
+    assert_eq!(c.call(&["SET", "key1", "val1"]), "+OK\r\n");
+    assert_eq!(c.call(&["set", "key2", "a\r\nb"]), "+OK\r\n");
+    assert_eq!(c.call(&["GET", "key1"]), "$4\r\nval1\r\n");
+    assert_eq!(c.call(&["GET", "key2"]), "$4\r\na\r\nb\r\n");
+    assert_eq!(c.call(&["DEL", "key1", "key2", "key3"]), ":2\r\n");
+    assert_eq!(c.call(&["GET", "key1"]), "$-1\r\n");
+    assert_eq!(
+        c.call(&["GET"]),
+        "-ERR wrong number of arguments for 'get' command\r\n"
+    );
+    assert_eq!(c.call(&["INCR", "key1"]), "-ERR unknown command 'INCR'\r\n");
+}
+
+#[test]
+fn test_inline_and_pipelined() {
+    let mut c = Client::connect(&start());
+    c.writer
+        .write_all(b"SET key1 val1\r\nGET key1\r\n*1\r\n$4\r\nPING\r\n")
+        .unwrap();
+    assert_eq!(c.reply(), "+OK\r\n");
+    assert_eq!(c.reply(), "$4\r\nval1\r\n");
+    assert_eq!(c.reply(), "+PONG\r\n");
+}
+
+#[test]
+fn test_protocol_error() {
+    let mut c = Client::connect(&start());
+    c.writer.write_all(b"*1\r\n:1\r\n").unwrap();
+    assert_eq!(c.reply(), "-ERR Protocol error: expected '$'\r\n");
+    // the connection is closed
+    assert_eq!(c.reader.read(&mut [0]).unwrap(), 0);
+}
+
+#[test]
+fn test_multi() {
+    let addr = start();
+    let (mut c1, mut c2) = (Client::connect(&addr), Client::connect(&addr));
+    assert_eq!(c1.call(&["EXEC"]), "-ERR EXEC without MULTI\r\n");
+    assert_eq!(c1.call(&["MULTI"]), "+OK\r\n");
+    assert_eq!(
+        c1.call(&["MULTI"]),
+        "-ERR MULTI calls can not be nested\r\n"
+    );
+    assert_eq!(c1.call(&["SET", "key1", "val1"]), "+QUEUED\r\n");
+    assert_eq!(c1.call(&["GET", "key1"]), "+QUEUED\r\n");
+    // nothing runs before EXEC
+    assert_eq!(c2.call(&["GET", "key1"]), "$-1\r\n");
+    assert_eq!(c1.call(&["EXEC"]), "*2\r\n+OK\r\n$4\r\nval1\r\n");
+    assert_eq!(c2.call(&["GET", "key1"]), "$4\r\nval1\r\n");
+
+    assert_eq!(c1.call(&["MULTI"]), "+OK\r\n");
+    assert_eq!(c1.call(&["DEL", "key1"]), "+QUEUED\r\n");
+    assert_eq!(c1.call(&["DISCARD"]), "+OK\r\n");
+    assert_eq!(c1.call(&["GET", "key1"]), "$4\r\nval1\r\n");
+
+    // a rejected command discards the queue
+    assert_eq!(c1.call(&["MULTI"]), "+OK\r\n");
+    assert_eq!(c1.call(&["DEL", "key1"]), "+QUEUED\r\n");
+    assert_eq!(
+        c1.call(&["SET", "key1"]),
+        "-ERR wrong number of arguments for 'set' command\r\n"
+    );
+    assert_eq!(
+        c1.call(&["EXEC"]),
+        "-EXECABORT Transaction discarded because of previous errors.\r\n"
+    );
+    assert_eq!(c1.call(&["GET", "key1"]), "$4\r\nval1\r\n");
+}
+
+#[test]
+fn test_transactions() {
+    let addr = start();
+    let (mut c1, mut c2) = (Client::connect(&addr), Client::connect(&addr));
+    assert_eq!(c1.call(&["COMMIT"]), "-ERR COMMIT without BEGIN\r\n");
+    assert_eq!(c1.call(&["ROLLBACK"]), "-ERR ROLLBACK without BEGIN\r\n");
+    assert_eq!(c1.call(&["SET", "key1", "val1"]), "+OK\r\n");
+    assert_eq!(c1.call(&["BEGIN"]), "+OK\r\n");
+    assert_eq!(c1.call(&["SET", "key2", "val2"]), "+OK\r\n");
+    assert_eq!(c1.call(&["BEGIN"]), "+OK\r\n");
+    assert_eq!(c1.call(&["DEL", "key1"]), ":1\r\n");
+    assert_eq!(c1.call(&["SET", "key3", "val3"]), "+OK\r\n");
+    assert_eq!(c1.call(&["ROLLBACK"]), "+OK\r\n");
+    assert_eq!(c1.call(&["GET", "key3"]), "$-1\r\n");
+    assert_eq!(c1.call(&["BEGIN"]), "+OK\r\n");
+    assert_eq!(c1.call(&["DEL", "key1"]), ":1\r\n");
+    assert_eq!(c1.call(&["COMMIT"]), "+OK\r\n");
+    assert_eq!(c1.call(&["GET", "key1"]), "$-1\r\n");
+    // the open transaction is invisible to other connections
+    assert_eq!(c2.call(&["GET", "key1"]), "$4\r\nval1\r\n");
+    assert_eq!(c2.call(&["GET", "key2"]), "$-1\r\n");
+    assert_eq!(c1.call(&["COMMIT"]), "+OK\r\n");
+    assert_eq!(c2.call(&["GET", "key1"]), "$-1\r\n");
+    assert_eq!(c2.call(&["GET", "key2"]), "$4\r\nval2\r\n");
+}
+
+#[test]
+fn test_disconnect_rolls_back() {
+    let addr = start();
+    let mut c1 = Client::connect(&addr);
+    assert_eq!(c1.call(&["BEGIN"]), "+OK\r\n");
+    assert_eq!(c1.call(&["SET", "key1", "val1"]), "+OK\r\n");
+    drop(c1);
+    let mut c2 = Client::connect(&addr);
+    assert_eq!(c2.call(&["GET", "key1"]), "$-1\r\n");
+    assert_eq!(c2.call(&["COMMIT"]), "-ERR COMMIT without BEGIN\r\n");
+}
+
+#[test]
+fn test_conflict() {
+    let addr = start();
+    let (mut c1, mut c2) = (Client::connect(&addr), Client::connect(&addr));
+    assert_eq!(c1.call(&["SET", "key1", "val1"]), "+OK\r\n");
+    assert_eq!(c1.call(&["BEGIN"]), "+OK\r\n");
+    assert_eq!(c2.call(&["BEGIN"]), "+OK\r\n");
+    assert_eq!(c1.call(&["SET", "key1", "c1"]), "+OK\r\n");
+    assert_eq!(c2.call(&["SET", "key1", "c2"]), "+OK\r\n");
+    assert_eq!(c2.call(&["COMMIT"]), "+OK\r\n");
+    // c1 still reads its snapshot until its commit fails
+    assert_eq!(c1.call(&["DEL", "key2"]), ":0\r\n");
+    assert_eq!(
+        c1.call(&["COMMIT"]),
+        "-ERR transaction conflicts with a concurrent commit\r\n"
+    );
+    assert_eq!(c1.call(&["COMMIT"]), "-ERR COMMIT without BEGIN\r\n");
+    assert_eq!(c1.call(&["GET", "key1"]), "$2\r\nc2\r\n");
+}
+
+#[test]
+fn test_expiry() {
+    let mut c = Client::connect(&start());
+    assert_eq!(c.call(&["SET", "key1", "val1", "PX", "100"]), "+OK\r\n");
+    assert_eq!(c.call(&["SET", "key2", "val2"]), "+OK\r\n");
+    assert_eq!(c.call(&["TTL", "key1"]), ":0\r\n");
+    assert_eq!(c.call(&["TTL", "key2"]), ":-1\r\n");
+    assert_eq!(c.call(&["TTL", "key3"]), ":-2\r\n");
+    assert_eq!(c.call(&["EXPIRE", "key2", "10"]), ":1\r\n");
+    assert_eq!(c.call(&["EXPIRE", "key3", "10"]), ":0\r\n");
+    assert_eq!(c.call(&["TTL", "key2"]), ":10\r\n");
+    thread::sleep(std::time::Duration::from_millis(150));
+    assert_eq!(c.call(&["GET", "key1"]), "$-1\r\n");
+    assert_eq!(c.call(&["EXPIRE", "key2", "0"]), ":1\r\n");
+    assert_eq!(c.call(&["GET", "key2"]), "$-1\r\n");
+}
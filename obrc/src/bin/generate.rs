@@ -0,0 +1,24 @@
+use std::{
+    env,
+    io::{self, BufWriter, Write},
+};
+
+/// Writes measurements to stdout:
+///
+///   generate <rows> [stations] [seed] > measurements.txt
+fn main() -> io::Result<()> {
+    let mut args = env::args().skip(1).map(|arg| arg.parse::<u64>());
+    let mut arg = |default: Option<u64>| match (args.next(), default) {
+        (Some(Ok(n)), _) | (None, Some(n)) => n,
+        _ => {
+            eprintln!("usage: generate <rows> [stations] [seed]");
+            std::process::exit(2);
+        }
+    };
+    let rows = arg(None);
+    let stations = arg(Some(413));
+    let seed = arg(Some(1));
+    let mut out = BufWriter::new(io::stdout().lock());
+    obrc::generate::generate(rows, stations as usize, seed, &mut out)?;
+    out.flush()
+}
@@ -0,0 +1,82 @@
+use std::io::{self, Write};
+
+/// Stations with their mean temperature, in tenths. More stations than these
+/// are named after them with a number.
+const STATIONS: [(&str, i64); 40] = [
+    ("Abha", 180),
+    ("Abidjan", 260),
+    ("Accra", 264),
+    ("Addis Ababa", 160),
+    ("Adelaide", 173),
+    ("Alexandria", 200),
+    ("Amsterdam", 102),
+    ("Anchorage", 28),
+    ("Athens", 192),
+    ("Bangkok", 286),
+    ("Berlin", 103),
+    ("Bogotá", 156),
+    ("Cairo", 214),
+    ("Chicago", 98),
+    ("Dakar", 240),
+    ("Dublin", 98),
+    ("Helsinki", 59),
+    ("Honolulu", 254),
+    ("İzmir", 179),
+    ("Jakarta", 267),
+    ("Kraków", 83),
+    ("Lagos", 268),
+    ("London", 113),
+    ("Madrid", 150),
+    ("Moscow", 58),
+    ("Mumbai", 271),
+    ("Nairobi", 178),
+    ("Oslo", 57),
+    ("Paris", 123),
+    ("Reykjavík", 43),
+    ("São Paulo", 199),
+    ("Seoul", 125),
+    ("Singapore", 270),
+    ("Stockholm", 66),
+    ("Sydney", 177),
+    ("Tokyo", 154),
+    ("Toronto", 94),
+    ("Ulaanbaatar", -4),
+    ("Yakutsk", -88),
+    ("Zürich", 93),
+];
+
+/// Writes `rows` lines of `station;temperature` for `stations` stations, which
+/// are the same for the same seed
+pub fn generate(rows: u64, stations: usize, seed: u64, out: &mut impl Write) -> io::Result<()> {
+    let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1);
+    let stations = (0..stations.max(1))
+        .map(|i| {
+            let (name, mean) = STATIONS[i % STATIONS.len()];
+            match i / STATIONS.len() {
+                0 => (name.to_string(), mean),
+                n => (format!("{name} {n}"), mean + rng.below(200) as i64 - 100),
+            }
+        })
+        .collect::<Vec<_>>();
+    for _ in 0..rows {
+        let (name, mean) = &stations[rng.below(stations.len() as u64) as usize];
+        // the sum of a few uniform draws is roughly normal around the mean
+        let noise = (0..4).map(|_| rng.below(201) as i64 - 100).sum::<i64>();
+        let temp = (mean + noise).clamp(-999, 999);
+        let sign = if temp < 0 { "-" } else { "" };
+        writeln!(out, "{name};{sign}{}.{}", temp.abs() / 10, temp.abs() % 10)?;
+    }
+    Ok(())
+}
+
+/// A xorshift generator, which is plenty for test data
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+}
@@ -0,0 +1,165 @@
+use std::{cmp, fmt, thread};
+use table::Table;
+
+pub mod generate;
+mod table;
+
+/// The measurements of a station, in tenths of a degree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub min: i16,
+    pub max: i16,
+    pub sum: i64,
+    pub count: u32,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            min: i16::MAX,
+            max: i16::MIN,
+            sum: 0,
+            count: 0,
+        }
+    }
+}
+
+impl Stats {
+    #[inline]
+    fn add(&mut self, temp: i16) {
+        self.min = cmp::min(self.min, temp);
+        self.max = cmp::max(self.max, temp);
+        self.sum += i64::from(temp);
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Stats) {
+        self.min = cmp::min(self.min, other.min);
+        self.max = cmp::max(self.max, other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+
+    /// The mean in tenths, rounded half up like the reference implementation
+    pub fn mean(&self) -> i64 {
+        let count = i64::from(self.count);
+        (2 * self.sum + count).div_euclid(2 * count)
+    }
+}
+
+/// Formats as min/mean/max with one decimal
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (min, max) = (i64::from(self.min), i64::from(self.max));
+        write!(f, "{}/{}/{}", Tenths(min), Tenths(self.mean()), Tenths(max))
+    }
+}
+
+struct Tenths(i64);
+
+impl fmt::Display for Tenths {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        write!(f, "{sign}{}.{}", self.0.abs() / 10, self.0.abs() % 10)
+    }
+}
+
+/// Aggregates the `station;temperature` lines of the input on the threads,
+/// returning the stations sorted by name. Temperatures have exactly one
+/// decimal and are within -99.9 and 99.9.
+///
+/// # Panics
+///
+/// If a line is not of that form.
+pub fn aggregate(input: &[u8], threads: usize) -> Vec<(String, Stats)> {
+    let tables = thread::scope(|s| {
+        let handles = chunks(input, threads)
+            .into_iter()
+            .map(|chunk| s.spawn(|| process(chunk)))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    let mut merged = Table::new();
+    for table in tables {
+        for (name, hash, stats) in table.into_iter() {
+            merged.get_mut(name, hash).merge(&stats);
+        }
+    }
+    let mut stations = merged
+        .into_iter()
+        .map(|(name, _, stats)| (String::from_utf8_lossy(name).into_owned(), stats))
+        .collect::<Vec<_>>();
+    stations.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    stations
+}
+
+/// Formats the stations as `{name=min/mean/max, ...}`
+pub fn report(stations: &[(String, Stats)]) -> String {
+    let stations = stations
+        .iter()
+        .map(|(name, stats)| format!("{name}={stats}"))
+        .collect::<Vec<_>>();
+    format!("{{{}}}", stations.join(", "))
+}
+
+/// Splits the input into about `n` chunks of whole lines
+fn chunks(input: &[u8], n: usize) -> Vec<&[u8]> {
+    // at least a byte, so there are no more chunks than lines
+    let size = cmp::max(input.len() / cmp::max(n, 1), 1);
+    let mut chunks = vec![];
+    let mut rest = input;
+    while !rest.is_empty() {
+        let end = match rest
+            .get(size..)
+            .and_then(|r| r.iter().position(|&b| b == b'\n'))
+        {
+            Some(i) => size + i + 1,
+            None => rest.len(),
+        };
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+fn process(mut chunk: &[u8]) -> Table<'_> {
+    let mut table = Table::new();
+    while !chunk.is_empty() {
+        let mut hash = 0;
+        let mut i = 0;
+        loop {
+            match chunk.get(i) {
+                Some(b';') => break,
+                Some(&b) => hash = table::hash(hash, b),
+                None => panic!("line without a ';'"),
+            }
+            i += 1;
+        }
+        let (temp, len) = parse_temp(&chunk[i + 1..]);
+        table.get_mut(&chunk[..i], hash).add(temp);
+        // skip the newline, which the last line may not have
+        chunk = chunk.get(i + 1 + len + 1..).unwrap_or_default();
+    }
+    table
+}
+
+/// Parses a temperature like `-12.3` into tenths, returning how many bytes it
+/// took. It is only ever one or two digits, a dot and one digit.
+#[inline]
+fn parse_temp(bytes: &[u8]) -> (i16, usize) {
+    let (sign, digits) = match bytes {
+        [b'-', rest @ ..] => (-1, rest),
+        _ => (1, bytes),
+    };
+    let digit = |b: u8| i16::from(b.wrapping_sub(b'0'));
+    let (tenths, len) = match *digits {
+        [a, b'.', b, ..] => (digit(a) * 10 + digit(b), 3),
+        [a, b, b'.', c, ..] => (digit(a) * 100 + digit(b) * 10 + digit(c), 4),
+        _ => panic!("invalid temperature {:?}", String::from_utf8_lossy(bytes)),
+    };
+    (sign * tenths, len + bytes.len() - digits.len())
+}
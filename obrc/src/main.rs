@@ -1,25 +1,19 @@
-use std::{
-    env, fs,
-    io::{BufRead, BufReader},
-};
+use memmap2::{Advice, Mmap};
+use std::{env, fs, thread};
 
 fn main() {
-    let file = fs::OpenOptions::new()
-        .read(true)
-        .open(env::var("OBRC_FILE").expect("no OBRC_FILE env var set"))
-        .expect("could not open file");
-    let mut file = BufReader::new(file);
-    let mut buf = String::new();
-    let mut count = 0;
-    loop {
-        let _n = match file.read_line(&mut buf) {
-            Ok(0) => break,
-            Ok(n) => n,
-            Err(err) => panic!("read failed: {err}"),
-        };
-        count += 1;
-        let sep = buf.find(';').expect("no sep");
-        buf.clear();
-    }
-    println!("Total: {count}");
+    let path = env::args()
+        .nth(1)
+        .or_else(|| env::var("OBRC_FILE").ok())
+        .expect("no file given and no OBRC_FILE env var set");
+    let file = fs::File::open(path).expect("could not open file");
+    // the file must not be truncated while it is mapped
+    let input = unsafe { Mmap::map(&file) }.expect("could not map file");
+    // it is read once from start to end, so read ahead aggressively
+    input
+        .advise(Advice::Sequential)
+        .expect("could not advise on the map");
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let stations = obrc::aggregate(&input, threads);
+    println!("{}", obrc::report(&stations));
 }
@@ -0,0 +1,87 @@
+use crate::Stats;
+
+const INITIAL_BITS: u32 = 14;
+
+/// An open-addressing hash map from station names, borrowed from the input,
+/// to their stats. The caller hashes the name while it scans for the `;`
+/// that ends it, so a lookup only compares names whose hashes are equal.
+pub struct Table<'a> {
+    slots: Vec<Option<Slot<'a>>>,
+    bits: u32,
+    len: usize,
+}
+
+struct Slot<'a> {
+    hash: u64,
+    name: &'a [u8],
+    stats: Stats,
+}
+
+/// Adds a byte of a name to its hash
+#[inline]
+pub fn hash(hash: u64, byte: u8) -> u64 {
+    (hash.rotate_left(5) ^ u64::from(byte)).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95)
+}
+
+impl<'a> Table<'a> {
+    pub fn new() -> Self {
+        Self {
+            slots: (0..1 << INITIAL_BITS).map(|_| None).collect(),
+            bits: INITIAL_BITS,
+            len: 0,
+        }
+    }
+
+    /// The stats of the name, inserting empty stats if it is new
+    #[inline]
+    pub fn get_mut(&mut self, name: &'a [u8], hash: u64) -> &mut Stats {
+        let mut i = self.index(hash);
+        loop {
+            match &self.slots[i] {
+                Some(slot) if slot.hash == hash && slot.name == name => break,
+                Some(_) => i = (i + 1) & (self.slots.len() - 1),
+                None => {
+                    // keep probe sequences short by keeping the table half empty
+                    if (self.len + 1) * 2 > self.slots.len() {
+                        self.grow();
+                        return self.get_mut(name, hash);
+                    }
+                    self.len += 1;
+                    self.slots[i] = Some(Slot {
+                        hash,
+                        name,
+                        stats: Stats::default(),
+                    });
+                    break;
+                }
+            }
+        }
+        &mut self.slots[i].as_mut().unwrap().stats
+    }
+
+    /// The names and their stats, in no particular order
+    pub fn into_iter(self) -> impl Iterator<Item = (&'a [u8], u64, Stats)> {
+        self.slots
+            .into_iter()
+            .flatten()
+            .map(|slot| (slot.name, slot.hash, slot.stats))
+    }
+
+    // the high bits of the hash, spread by a fibonacci multiply
+    fn index(&self, hash: u64) -> usize {
+        (hash.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> (64 - self.bits)) as usize
+    }
+
+    fn grow(&mut self) {
+        let slots = std::mem::take(&mut self.slots);
+        self.bits += 1;
+        self.slots = (0..1 << self.bits).map(|_| None).collect();
+        for slot in slots.into_iter().flatten() {
+            let mut i = self.index(slot.hash);
+            while self.slots[i].is_some() {
+                i = (i + 1) & (self.slots.len() - 1);
+            }
+            self.slots[i] = Some(slot);
+        }
+    }
+}
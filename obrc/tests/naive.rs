@@ -0,0 +1,71 @@
+use obrc::{aggregate, generate::generate, report};
+use std::collections::BTreeMap;
+
+// the obvious implementation, with floats and a std map
+fn naive(input: &str) -> String {
+    let mut stations: BTreeMap<&str, (f64, f64, i64, u32)> = BTreeMap::new();
+    for line in input.lines() {
+        let (name, temp) = line.split_once(';').unwrap();
+        let temp = temp.parse::<f64>().unwrap();
+        let stats = stations.entry(name).or_insert((f64::MAX, f64::MIN, 0, 0));
+        stats.0 = stats.0.min(temp);
+        stats.1 = stats.1.max(temp);
+        // summing floats drifts off the halves that the mean rounds at
+        stats.2 += (temp * 10.0).round() as i64;
+        stats.3 += 1;
+    }
+    let stations = stations
+        .into_iter()
+        .map(|(name, (min, max, sum, count))| {
+            // round half up, like Math.round in the reference implementation
+            let mean = (sum as f64 / f64::from(count) + 0.5).floor() / 10.0;
+            format!("{name}={min:.1}/{:.1}/{max:.1}", mean + 0.0)
+        })
+        .collect::<Vec<_>>();
+    format!("{{{}}}", stations.join(", "))
+}
+
+fn input(rows: u64, stations: usize, seed: u64) -> String {
+    let mut buf = vec![];
+    generate(rows, stations, seed, &mut buf).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+#[test]
+fn test_naive() {
+    for (rows, stations, seed) in [
+        (1, 1, 1),
+        (1000, 10, 2),
+        (100_000, 413, 3),
+        (200_000, 10_000, 4),
+    ] {
+        let input = input(rows, stations, seed);
+        let expected = naive(&input);
+        for threads in [1, 3, 8] {
+            let actual = report(&aggregate(input.as_bytes(), threads));
+            assert_eq!(
+                actual, expected,
+                "rows {rows} stations {stations} threads {threads}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_report() {
+    let input = "b;-0.5\na;1.0\nb;12.3\na;-99.9\nc;99.9\nb;-0.6";
+    assert_eq!(
+        report(&aggregate(input.as_bytes(), 4)),
+        "{a=-99.9/-49.4/1.0, b=-0.6/3.7/12.3, c=99.9/99.9/99.9}"
+    );
+    assert_eq!(report(&aggregate(b"", 4)), "{}");
+    // means round half up
+    assert_eq!(
+        report(&aggregate(b"a;-0.1\na;0.0\n", 1)),
+        "{a=-0.1/0.0/0.0}"
+    );
+    assert_eq!(
+        report(&aggregate(b"a;-0.2\na;-0.1\n", 1)),
+        "{a=-0.2/-0.1/-0.1}"
+    );
+}
@@ -10,35 +10,104 @@ pub enum Tick {
     Continue,
 }
 
-#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
-enum Focus {
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Focus {
     #[default]
     Tables,
     Table,
+    // text is typed into these, so their keys cannot be bound
+    #[serde(skip_deserializing)]
     Search,
+    #[serde(skip_deserializing)]
+    Query,
+    #[serde(skip_deserializing)]
+    Form,
+    Changes,
+    #[serde(skip_deserializing)]
+    Export,
+    Schema,
 }
 
 pub struct App {
-    dao: BlockingDao,       // db handle
-    tables: DbTables,       // the list of tables
-    table: Option<DbTable>, // the selected table
-    focus: Focus,           // what ui element has focus
-    dims: Rect,             // how large the frame is
-    bindings: KeyBindSet,   // keybindings
-    search: Option<String>, // filter query
+    dao: BlockingDao,             // db handle
+    tables: DbTables,             // the list of tables
+    table: Option<DbTable>,       // the selected table
+    focus: Focus,                 // what ui element has focus
+    dims: Rect,                   // how large the frame is
+    bindings: KeyBindSet,         // keybindings
+    search: Option<String>,       // filter query
+    search_error: Option<String>, // why the filter query is invalid
+    query: Option<String>,        // ad-hoc sql
+    query_error: Option<String>,  // why the last query failed
+    form: Option<Form>,           // the row being inserted or edited
+    pending: Vec<Change>,         // staged changes to be committed
+    commit_error: Option<String>, // why the last commit failed
+    export_path: Option<String>,  // where to export the table to
+    status: Option<String>,       // the outcome of the last action
+    schema: Option<TableDetails>, // the definition of the selected table
+    schema_scroll: u16,           // how far the schema panel is scrolled
+    trail: Vec<Crumb>,            // the tables foreign keys were followed from
+    theme: Theme,                 // the colors to draw with
+    page_size: Option<usize>,     // how many rows to page by
+}
+
+/// A table that was left by following a foreign key, to return to later
+struct Crumb {
+    table: DbTable,
+    search: Option<String>,
+    selected: Option<usize>, // the position in the list of tables
 }
 
 struct KeyBindSet {
-    bindings: HashMap<Focus, HashMap<KeyEvent, Action>>,
+    // the keys of each action, in the order they are shown in the help bar
+    bindings: HashMap<Focus, Vec<(Action, Vec<KeyEvent>)>>,
 }
 
 impl KeyBindSet {
     fn matches(&self, focus: Focus, event: KeyEvent) -> Option<Action> {
+        self.actions(focus)
+            .iter()
+            .find(|(_, keys)| keys.contains(&event))
+            .map(|(action, _)| *action)
+    }
+
+    fn actions(&self, focus: Focus) -> &[(Action, Vec<KeyEvent>)] {
         self.bindings
             .get(&focus)
-            .map(|b| b.get(&event))
-            .flatten()
-            .cloned()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    // groups the keys of each action, keeping the order of the actions
+    fn group<const N: usize>(keys: [(KeyEvent, Action); N]) -> Vec<(Action, Vec<KeyEvent>)> {
+        let mut actions: Vec<(Action, Vec<KeyEvent>)> = vec![];
+        for (key, action) in keys {
+            match actions.iter_mut().find(|(a, _)| *a == action) {
+                Some((_, keys)) => keys.push(key),
+                None => actions.push((action, vec![key])),
+            }
+        }
+        actions
+    }
+
+    /// Replaces the keys of actions. A key is taken from any other action it
+    /// was bound to, and an empty list of keys unbinds the action.
+    fn rebind(&mut self, keys: &HashMap<Focus, HashMap<Action, Vec<Key>>>) {
+        for (focus, actions) in keys {
+            let bindings = self.bindings.entry(*focus).or_default();
+            for (action, keys) in actions {
+                let keys = keys.iter().map(|k| k.0).collect::<Vec<_>>();
+                for (_, bound) in bindings.iter_mut() {
+                    bound.retain(|k| !keys.contains(k));
+                }
+                match bindings.iter_mut().find(|(a, _)| a == action) {
+                    Some((_, bound)) => *bound = keys,
+                    None => bindings.push((*action, keys)),
+                }
+            }
+            bindings.retain(|(_, keys)| !keys.is_empty());
+        }
     }
 }
 
@@ -50,7 +119,7 @@ impl Default for KeyBindSet {
         let ctrl_key = |code: KeyCode| -> KeyEvent { kevent(code, KeyModifiers::CONTROL) };
         let mut bindings = HashMap::default();
         bindings.insert(Focus::Tables, {
-            HashMap::from([
+            Self::group([
                 // tablesnext
                 (key(KeyCode::Down), TablesNext),
                 (key(KeyCode::Char('J')), TablesNext),
@@ -64,13 +133,17 @@ impl Default for KeyBindSet {
                 (key(KeyCode::Char('l')), ChangeFocus(Focus::Table)),
                 (key(KeyCode::Char('o')), ChangeFocus(Focus::Table)),
                 (key(KeyCode::Enter), ChangeFocus(Focus::Table)),
+                // query
+                (key(KeyCode::Char(':')), ChangeFocus(Focus::Query)),
+                // schema
+                (key(KeyCode::Char('s')), ChangeFocus(Focus::Schema)),
                 // quit
                 (key(KeyCode::Char('q')), Quit),
                 (key(KeyCode::Esc), Quit),
             ])
         });
         bindings.insert(Focus::Table, {
-            HashMap::from([
+            Self::group([
                 // tablesnext
                 (key(KeyCode::Char('J')), TablesNext),
                 // tablesprev
@@ -81,11 +154,19 @@ impl Default for KeyBindSet {
                 // tableprev
                 (key(KeyCode::Up), TablePrev),
                 (key(KeyCode::Char('k')), TablePrev),
+                // columnnext
+                (key(KeyCode::Right), ColumnNext),
+                (key(KeyCode::Char('l')), ColumnNext),
+                // columnprev, which leaves the table from the first column
+                (key(KeyCode::Left), ColumnPrev),
+                (key(KeyCode::Char('h')), ColumnPrev),
                 // focustables
-                (key(KeyCode::Left), ChangeFocus(Focus::Tables)),
-                (key(KeyCode::Char('h')), ChangeFocus(Focus::Tables)),
                 (key(KeyCode::Char('q')), ChangeFocus(Focus::Tables)),
                 (key(KeyCode::Esc), ChangeFocus(Focus::Tables)),
+                // columns
+                (key(KeyCode::Char('o')), SortColumn),
+                (key(KeyCode::Char('-')), HideColumn),
+                (key(KeyCode::Char('+')), ShowColumns),
                 // pageup
                 (key(KeyCode::PageUp), PageUp),
                 (ctrl_key(KeyCode::Char('u')), PageUp),
@@ -96,14 +177,58 @@ impl Default for KeyBindSet {
                 (key(KeyCode::Char('/')), ChangeFocus(Focus::Search)),
                 (key(KeyCode::Char('?')), ChangeFocus(Focus::Search)),
                 (ctrl_key(KeyCode::Char('f')), ChangeFocus(Focus::Search)),
+                // query
+                (key(KeyCode::Char(':')), ChangeFocus(Focus::Query)),
+                // edit
+                (key(KeyCode::Char('e')), EditRow),
+                (key(KeyCode::Char('i')), InsertRow),
+                (key(KeyCode::Char('d')), DeleteRow),
+                (key(KeyCode::Char('c')), ChangeFocus(Focus::Changes)),
+                // follow
+                (key(KeyCode::Enter), FollowKey),
+                // back
+                (key(KeyCode::Backspace), Back),
+                // export
+                (key(KeyCode::Char('x')), ChangeFocus(Focus::Export)),
+                // schema
+                (key(KeyCode::Char('s')), ChangeFocus(Focus::Schema)),
+            ])
+        });
+        bindings.insert(Focus::Schema, {
+            Self::group([
+                // tablesnext
+                (key(KeyCode::Char('J')), TablesNext),
+                // tablesprev
+                (key(KeyCode::Char('K')), TablesPrev),
+                // scrolldown
+                (key(KeyCode::Down), SchemaDown),
+                (key(KeyCode::Char('j')), SchemaDown),
+                // scrollup
+                (key(KeyCode::Up), SchemaUp),
+                (key(KeyCode::Char('k')), SchemaUp),
+                // focustables
+                (key(KeyCode::Char('s')), ChangeFocus(Focus::Tables)),
+                (key(KeyCode::Char('q')), ChangeFocus(Focus::Tables)),
+                (key(KeyCode::Esc), ChangeFocus(Focus::Tables)),
+            ])
+        });
+        bindings.insert(Focus::Changes, {
+            Self::group([
+                // commit
+                (key(KeyCode::Char('c')), Commit),
+                // rollback
+                (key(KeyCode::Char('r')), Rollback),
+                // focustable
+                (key(KeyCode::Char('q')), ChangeFocus(Focus::Table)),
+                (key(KeyCode::Esc), ChangeFocus(Focus::Table)),
             ])
         });
         Self { bindings }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Action {
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub(crate) enum Action {
     TablesNext,
     TablesPrev,
     TableNext,
@@ -112,17 +237,112 @@ enum Action {
     PageDown,
     ChangeFocus(Focus),
     Search,
+    RunQuery,
+    EditRow,
+    InsertRow,
+    DeleteRow,
+    SubmitForm,
+    Commit,
+    Rollback,
+    Export,
+    SchemaDown,
+    SchemaUp,
+    FollowKey,
+    Back,
+    ColumnNext,
+    ColumnPrev,
+    SortColumn,
+    HideColumn,
+    ShowColumns,
     Quit,
 }
 
+impl Action {
+    // the names of actions that can be bound in the config
+    const NAMES: [(&'static str, Action); 28] = [
+        ("tables_next", Action::TablesNext),
+        ("tables_prev", Action::TablesPrev),
+        ("table_next", Action::TableNext),
+        ("table_prev", Action::TablePrev),
+        ("column_next", Action::ColumnNext),
+        ("column_prev", Action::ColumnPrev),
+        ("page_up", Action::PageUp),
+        ("page_down", Action::PageDown),
+        ("sort", Action::SortColumn),
+        ("hide_column", Action::HideColumn),
+        ("show_columns", Action::ShowColumns),
+        ("edit_row", Action::EditRow),
+        ("insert_row", Action::InsertRow),
+        ("delete_row", Action::DeleteRow),
+        ("follow_key", Action::FollowKey),
+        ("back", Action::Back),
+        ("commit", Action::Commit),
+        ("rollback", Action::Rollback),
+        ("schema_down", Action::SchemaDown),
+        ("schema_up", Action::SchemaUp),
+        ("focus_tables", Action::ChangeFocus(Focus::Tables)),
+        ("focus_table", Action::ChangeFocus(Focus::Table)),
+        ("focus_search", Action::ChangeFocus(Focus::Search)),
+        ("focus_query", Action::ChangeFocus(Focus::Query)),
+        ("focus_changes", Action::ChangeFocus(Focus::Changes)),
+        ("focus_export", Action::ChangeFocus(Focus::Export)),
+        ("focus_schema", Action::ChangeFocus(Focus::Schema)),
+        ("quit", Action::Quit),
+    ];
+
+    // how the action is described in the help bar. actions with the same
+    // label are shown together.
+    fn label(&self, focus: Focus) -> &'static str {
+        match self {
+            Action::TablesNext | Action::TablesPrev if focus == Focus::Tables => "navigate",
+            Action::TablesNext | Action::TablesPrev => "tables",
+            Action::TableNext | Action::TablePrev => "navigate",
+            Action::ColumnNext | Action::ColumnPrev => "navigate",
+            Action::PageUp | Action::PageDown => "page",
+            Action::SortColumn => "sort",
+            Action::HideColumn => "hide",
+            Action::ShowColumns => "show all",
+            Action::EditRow => "edit",
+            Action::InsertRow => "insert",
+            Action::DeleteRow => "delete",
+            Action::FollowKey => "follow key",
+            Action::Back => "back",
+            Action::Commit => "commit",
+            Action::Rollback => "rollback",
+            Action::SchemaDown | Action::SchemaUp => "scroll",
+            Action::ChangeFocus(Focus::Table) if focus == Focus::Tables => "open",
+            Action::ChangeFocus(Focus::Tables | Focus::Table) => "back",
+            Action::ChangeFocus(Focus::Search) => "search",
+            Action::ChangeFocus(Focus::Query) => "query",
+            Action::ChangeFocus(Focus::Changes) => "changes",
+            Action::ChangeFocus(Focus::Export) => "export",
+            Action::ChangeFocus(Focus::Schema) => "schema",
+            Action::Quit => "quit",
+            _ => "",
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Action {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(d)?;
+        Action::NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, action)| *action)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown action `{name}`")))
+    }
+}
+
 impl App {
-    pub fn new(db: DbType) -> Result<Self> {
-        let dao = BlockingDao::new(db)?;
+    pub fn new(db: DbType, tz: Tz, config: Config) -> Result<Self> {
+        let dao = BlockingDao::new(db, tz)?;
         let tables = DbTables::new(dao.tables()?);
         let mut table = None;
         let focus = Focus::default();
         let dims = Rect::default();
-        let bindings = KeyBindSet::default();
+        let mut bindings = KeyBindSet::default();
+        bindings.rebind(&config.keys);
         let mut app = Self {
             dao,
             tables,
@@ -131,43 +351,79 @@ impl App {
             dims,
             bindings,
             search: None,
+            search_error: None,
+            query: None,
+            query_error: None,
+            form: None,
+            pending: vec![],
+            commit_error: None,
+            export_path: None,
+            status: None,
+            schema: None,
+            schema_scroll: 0,
+            trail: vec![],
+            theme: config.theme,
+            page_size: config.page_size,
         };
         Ok(app)
     }
 
     pub fn draw(&mut self, term: &mut Term) -> Result<()> {
         self.dims = term.size()?;
-        if self.table.is_none() && self.tables.selected().is_some() {
+        let has_records = self.tables.selected_object().map(SchemaObject::has_records);
+        if self.table.is_none() && has_records == Some(true) {
             self.open_table()?;
         }
         let num_table_rows = self.num_table_rows();
 
-        let table_records = if let Some(table) = self.table.as_mut() {
-            table.set_viewport_rows(num_table_rows);
-            Some(table.records()?)
-        } else {
-            None
+        let table_records = match self.table.as_mut() {
+            Some(table) if table.is_query() => {
+                table.set_viewport_rows(num_table_rows);
+                match table.records() {
+                    Ok(records) => Some(records),
+                    Err(err) => {
+                        // a query can still fail while paging. show the error
+                        // instead of exiting.
+                        self.query_error = Some(format!("{err:#}"));
+                        self.focus = Focus::Query;
+                        self.table = None;
+                        None
+                    }
+                }
+            }
+            Some(table) => {
+                table.set_viewport_rows(num_table_rows);
+                Some(table.records()?)
+            }
+            None => None,
         };
+        let query_height = self.query_editor_height();
         term.draw(move |frame| {
             let mut chrome = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
                 .split(frame.size());
 
-            let mut tables_entry_highlight_style = Style::default().fg(Color::Cyan);
+            let theme = self.theme;
+            let mut tables_entry_highlight_style = Style::default().fg(theme.selection);
             let mut tables_title_style = Style::default();
-            let mut table_entry_highlight_style = Style::default().fg(Color::Cyan);
+            let mut table_entry_highlight_style = Style::default().fg(theme.selection);
             let mut table_title_style = Style::default();
             match self.focus {
                 Focus::Tables => {
-                    tables_entry_highlight_style = Style::default().fg(Color::LightGreen);
-                    tables_title_style = Style::default().fg(Color::LightGreen);
+                    tables_entry_highlight_style = Style::default().fg(theme.focus);
+                    tables_title_style = Style::default().fg(theme.focus);
                 }
                 Focus::Table => {
-                    table_entry_highlight_style = Style::default().fg(Color::LightGreen);
-                    table_title_style = Style::default().fg(Color::LightGreen);
+                    table_entry_highlight_style = Style::default().fg(theme.focus);
+                    table_title_style = Style::default().fg(theme.focus);
                 }
-                Focus::Search => (),
+                Focus::Search
+                | Focus::Query
+                | Focus::Form
+                | Focus::Changes
+                | Focus::Export
+                | Focus::Schema => (),
             }
 
             frame.render_widget(self.draw_help(), chrome[1]);
@@ -183,9 +439,9 @@ impl App {
                 .split(chrome[0]);
             let items: Vec<ListItem> = self
                 .tables
-                .names
+                .objects
                 .iter()
-                .map(|n| n.clone())
+                .map(|o| o.label())
                 .map(|n| ListItem::new(n).style(Style::default()))
                 .collect();
             let list = List::new(items)
@@ -198,124 +454,468 @@ impl App {
                 .highlight_style(tables_entry_highlight_style);
             let state = &mut self.tables.state;
             frame.render_stateful_widget(list, chunks[0], state);
+            let right = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(query_height), Constraint::Min(0)].as_ref())
+                .split(chunks[1]);
+            if query_height > 0 {
+                frame.render_widget(self.draw_query(), right[0]);
+            }
             let num_table_rows = self.num_table_rows();
-            if let Some(selected_table) = &mut self.table {
+            // triggers have no table, so their definition is always shown
+            if self.focus == Focus::Schema || self.table.is_none() {
+                if let Some(schema) = self.draw_schema() {
+                    frame.render_widget(schema, right[1]);
+                }
+            } else if let Some(selected_table) = &mut self.table {
                 let Some((records, mut state)) = table_records else {
                     warn!("no records");
                     return;
                 };
-                let header_names = selected_table
-                    .schema
-                    .cols
-                    .iter()
-                    .map(|col| col.name().to_string())
-                    .collect::<Vec<_>>();
-                let header_style = Style::default().fg(Color::LightBlue).bold();
-                let header_cells = header_names
-                    .iter()
-                    .map(|name| Cell::from(name.clone()).style(header_style));
+                // 2 border
+                let cols = selected_table.visible_columns(right[1].width.saturating_sub(2));
+                let focused = Some(selected_table.view.pos).filter(|_| self.focus == Focus::Table);
+                let header_style = Style::default().fg(theme.header).bold();
+                let header_cells = cols.iter().map(|idx| {
+                    let mut style = header_style;
+                    if focused == Some(*idx) {
+                        style = style.reversed();
+                    }
+                    Cell::from(selected_table.header(*idx)).style(style)
+                });
                 let header = Row::new(header_cells)
                     .style(Style::default())
                     .height(1)
                     .bottom_margin(0);
+                let selected_row = state.selected();
                 let rows = records.iter().enumerate().map(|(row_idx, record)| {
                     let mut row_style = Style::default();
                     if row_idx % 2 == 0 {
-                        row_style = row_style.bg(Color::Indexed(234));
+                        row_style = row_style.bg(theme.stripe);
                     }
-                    let cells = record
-                        .fields
-                        .iter()
-                        .map(|field| format!("{}", field.val))
-                        .map(|s| Cell::from(s).style(row_style));
+                    let cells = cols.iter().map(|idx| {
+                        let mut style = row_style;
+                        if selected_row == Some(row_idx) && focused == Some(*idx) {
+                            style = style.reversed();
+                        }
+                        let val = record.fields.get(*idx).map(|f| f.val.to_string());
+                        Cell::from(val.unwrap_or_default()).style(style)
+                    });
                     Row::new(cells).height(1)
                 });
-                let widths = selected_table
-                    .schema
-                    .cols
+                let widths = cols
                     .iter()
-                    .enumerate()
-                    .map(|(idx, col)| {
-                        let header_len = header_names[idx].len();
-                        let col_len = selected_table.max_len(col, 4);
-                        let len = std::cmp::max(col_len, header_len);
-                        Constraint::Max(len.try_into().unwrap())
-                    })
+                    .map(|idx| Constraint::Max(selected_table.col_width(*idx) as u16))
                     .collect::<Vec<_>>();
+                let mut title = if selected_table.is_query() {
+                    format!("[ Query ({} records) ]", selected_table.count)
+                } else {
+                    let crumbs = self
+                        .trail
+                        .iter()
+                        .map(|c| c.table.name())
+                        .chain([selected_table.name()])
+                        .collect::<Vec<_>>();
+                    format!(
+                        "[ Table: {} ({} records) ]",
+                        crumbs.join(" > "),
+                        selected_table.count
+                    )
+                };
+                if selected_table.num_hidden() > 0 {
+                    title.push_str(&format!("[ {} hidden ]", selected_table.num_hidden()));
+                }
+                if !self.pending.is_empty() {
+                    title.push_str(&format!("[ {} pending ]", self.pending.len()));
+                }
                 let table: Table = Table::new(rows)
                     .header(header)
                     .block(
                         Block::default()
-                            .title(format!(
-                                "[ Table: {} ({} records) ]",
-                                selected_table.name(),
-                                selected_table.count
-                            ))
+                            .title(title)
                             .title_style(table_title_style)
                             .borders(Borders::ALL),
                     )
-                    .highlight_style(Style::default().fg(Color::LightGreen))
+                    .highlight_style(Style::default().fg(theme.focus))
                     .highlight_symbol("")
                     .widths(&widths);
-                frame.render_stateful_widget(table, chunks[1], &mut state);
+                frame.render_stateful_widget(table, right[1], &mut state);
+            }
+            match self.focus {
+                Focus::Form => {
+                    if let Some((form, height)) = self.draw_form() {
+                        let area = Self::popup_area(right[1], height);
+                        frame.render_widget(Clear, area);
+                        frame.render_widget(form, area);
+                    }
+                }
+                Focus::Changes => {
+                    let (changes, height) = self.draw_changes();
+                    let area = Self::popup_area(right[1], height);
+                    frame.render_widget(Clear, area);
+                    frame.render_widget(changes, area);
+                }
+                _ => {}
             }
         })?;
         Ok(())
     }
 
+    fn draw_query(&self) -> Paragraph<'_> {
+        let mut lines = vec![text::Line::from(vec![
+            Span::raw(self.query.as_deref().unwrap_or_default()),
+            Span::styled("█", Style::default().fg(self.theme.focus)),
+        ])];
+        if let Some(err) = &self.query_error {
+            lines.push(text::Line::styled(
+                err.as_str(),
+                Style::default().fg(self.theme.error),
+            ));
+        }
+        Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+            Block::default()
+                .title("[ query ]")
+                .title_style(Style::default().fg(self.theme.focus))
+                .borders(Borders::ALL),
+        )
+    }
+
+    // the form and the number of rows it needs
+    fn draw_form(&self) -> Option<(Paragraph<'_>, u16)> {
+        let form = self.form.as_ref()?;
+        let name_len = form.fields.iter().map(|f| f.name.len()).max();
+        let lines = form
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| {
+                let name = format!("{:>width$}: ", field.name, width = name_len.unwrap_or(0));
+                let mut spans = vec![
+                    Span::styled(name, Style::default().fg(self.theme.header)),
+                    Span::raw(field.value.as_str()),
+                ];
+                if idx == form.pos {
+                    spans.push(Span::styled("█", Style::default().fg(self.theme.focus)));
+                }
+                text::Line::from(spans)
+            })
+            .collect::<Vec<_>>();
+        let height = lines.len() as u16 + 2;
+        let form = Paragraph::new(lines).block(
+            Block::default()
+                .title(form.title())
+                .title_style(Style::default().fg(self.theme.focus))
+                .borders(Borders::ALL),
+        );
+        Some((form, height))
+    }
+
+    // the pending changes as a diff and the number of rows it needs
+    fn draw_changes(&self) -> (Paragraph<'_>, u16) {
+        let mut lines = self
+            .pending
+            .iter()
+            .map(|change| {
+                let color = match change {
+                    Change::Insert { .. } => self.theme.focus,
+                    Change::Delete { .. } => self.theme.error,
+                    Change::Update { .. } => self.theme.status,
+                };
+                text::Line::styled(change.to_string(), Style::default().fg(color))
+            })
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            lines.push(text::Line::from("no pending changes"));
+        }
+        if let Some(err) = &self.commit_error {
+            lines.push(text::Line::styled(
+                err.as_str(),
+                Style::default().fg(self.theme.error),
+            ));
+        }
+        let height = lines.len() as u16 + 2;
+        let changes = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+            Block::default()
+                .title(format!("[ pending changes ({}) ]", self.pending.len()))
+                .title_style(Style::default().fg(self.theme.focus))
+                .borders(Borders::ALL),
+        );
+        (changes, height)
+    }
+
+    // the definition of the selected table, view, or trigger
+    fn draw_schema(&self) -> Option<Paragraph<'_>> {
+        let details = self.schema.as_ref()?;
+        let lines = Self::schema_lines(details, &self.theme);
+        let schema = Paragraph::new(lines).scroll((self.schema_scroll, 0)).block(
+            Block::default()
+                .title(format!("[ Schema: {} ]", details.object.label()))
+                .title_style(Style::default().fg(self.theme.focus))
+                .borders(Borders::ALL),
+        );
+        Some(schema)
+    }
+
+    fn schema_lines<'a>(details: &'a TableDetails, theme: &Theme) -> Vec<text::Line<'a>> {
+        let heading_style = Style::default().fg(theme.header).bold();
+        let type_style = Style::default().fg(theme.key);
+        let constraint_style = Style::default().fg(theme.status);
+        let mut lines = vec![];
+        if !details.cols.is_empty() {
+            lines.push(text::Line::styled("columns", heading_style));
+            let name_len = details.cols.iter().map(|c| c.name.len()).max();
+            let type_len = details.cols.iter().map(|c| c.typ.len()).max();
+            for col in &details.cols {
+                let mut constraints = vec![];
+                if col.pk {
+                    constraints.push("PRIMARY KEY".to_string());
+                }
+                if col.notnull {
+                    constraints.push("NOT NULL".to_string());
+                }
+                if let Some(default) = &col.dflt_value {
+                    constraints.push(format!("DEFAULT {default}"));
+                }
+                lines.push(text::Line::from(vec![
+                    Span::raw(format!(
+                        "  {:<width$}  ",
+                        col.name,
+                        width = name_len.unwrap_or(0)
+                    )),
+                    Span::styled(
+                        format!("{:<width$}  ", col.typ, width = type_len.unwrap_or(0)),
+                        type_style,
+                    ),
+                    Span::styled(constraints.join(" "), constraint_style),
+                ]));
+            }
+            lines.push(text::Line::from(""));
+        }
+        if !details.indexes.is_empty() {
+            lines.push(text::Line::styled("indexes", heading_style));
+            for index in &details.indexes {
+                let mut spans = vec![Span::raw(format!(
+                    "  {} ({})",
+                    index.name,
+                    index.cols.join(", ")
+                ))];
+                if index.unique {
+                    spans.push(Span::styled(" UNIQUE", constraint_style));
+                }
+                if index.partial {
+                    spans.push(Span::styled(" PARTIAL", constraint_style));
+                }
+                lines.push(text::Line::from(spans));
+            }
+            lines.push(text::Line::from(""));
+        }
+        if !details.foreign_keys.is_empty() {
+            lines.push(text::Line::styled("foreign keys", heading_style));
+            // a key over several columns has a row for each column
+            for key in details.foreign_keys.chunk_by(|a, b| a.id == b.id) {
+                let from = key.iter().map(|k| k.from.as_str()).collect::<Vec<_>>();
+                let to = key
+                    .iter()
+                    .map(|k| k.to.as_deref().unwrap_or("<primary key>"))
+                    .collect::<Vec<_>>();
+                let mut spans = vec![Span::raw(format!(
+                    "  ({}) -> {}({})",
+                    from.join(", "),
+                    key[0].table,
+                    to.join(", ")
+                ))];
+                for (action, value) in
+                    [("UPDATE", &key[0].on_update), ("DELETE", &key[0].on_delete)]
+                {
+                    if value != "NO ACTION" {
+                        spans.push(Span::styled(
+                            format!(" ON {action} {value}"),
+                            constraint_style,
+                        ));
+                    }
+                }
+                lines.push(text::Line::from(spans));
+            }
+            lines.push(text::Line::from(""));
+        }
+        if let Some(sql) = &details.sql {
+            lines.push(text::Line::styled("sql", heading_style));
+            lines.extend(sql.lines().map(text::Line::from));
+        }
+        lines
+    }
+
+    // a centered area over the table that is tall enough for the content
+    fn popup_area(area: Rect, height: u16) -> Rect {
+        let height = height.min(area.height);
+        let width = area.width.saturating_sub(4);
+        Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        }
+    }
+
     fn draw_help(&mut self) -> Paragraph {
         let no_style = Style::default();
-        let key_style = Style::default().fg(Color::LightCyan);
+        let key_style = Style::default().fg(self.theme.key);
         let search_style = if self.search.is_some() {
-            Style::default().fg(Color::Green)
+            Style::default().fg(self.theme.search)
         } else {
             no_style
         };
-        let help = match self.focus {
-            Focus::Tables => {
-                Self::intersperse_keys(["j", "k", "h", "l", "↓", "↑", "←", "→"], key_style)
-                    .chain([Span::raw(": navigate | ")])
-                    .chain(Self::intersperse_keys(["q", "esc"], key_style))
-                    .chain([Span::raw(": back/quit")])
-                    .collect()
-            }
-            Focus::Table => {
-                Self::intersperse_keys(["j", "k", "h", "l", "↓", "↑", "←", "→"], key_style)
-                    .chain([Span::raw(": navigate | ")])
-                    .chain(Self::intersperse_keys(["q", "esc"], key_style))
-                    .chain([Span::raw(": back/quit | ")])
-                    .chain(Self::intersperse_keys(["/", "C-f"], key_style))
-                    .chain([Span::raw(": "), Span::styled("search", search_style)])
-                    .collect()
+        let search_error = self
+            .search_error
+            .as_ref()
+            .map(|e| Span::styled(format!(" ({e})"), Style::default().fg(self.theme.error)));
+        let mut help: Vec<Span> = match self.focus {
+            Focus::Tables | Focus::Table | Focus::Changes | Focus::Schema => {
+                let mut labels: Vec<(&str, Vec<String>)> = vec![];
+                for (action, keys) in self.bindings.actions(self.focus) {
+                    let label = action.label(self.focus);
+                    let keys = keys.iter().map(|k| Key(*k).to_string());
+                    match labels.iter_mut().find(|(l, _)| *l == label) {
+                        Some((_, label_keys)) => label_keys.extend(keys),
+                        None => labels.push((label, keys.collect())),
+                    }
+                }
+                let mut help = vec![];
+                for (idx, (label, keys)) in labels.into_iter().enumerate() {
+                    if idx > 0 {
+                        help.push(Span::raw(" | "));
+                    }
+                    help.extend(Self::intersperse_keys(keys, key_style));
+                    help.push(Span::raw(": "));
+                    if label == "search" {
+                        help.push(Span::styled(label, search_style));
+                    } else {
+                        help.push(Span::raw(label));
+                    }
+                }
+                help
             }
-
             Focus::Search => Self::intersperse_keys(["Esc"], key_style)
                 .chain([Span::raw(": exit search | ")])
                 .chain(Self::intersperse_keys(["Enter"], key_style))
                 .chain([Span::raw(": navigate results || current query: ")])
                 .map(|f| Some(f))
-                .chain(Some(
-                    self.search
-                        .as_ref()
-                        .map(|s| Span::styled(s, Style::default().fg(Color::Green))),
-                ))
+                .chain(Some(self.search.as_ref().map(|s| {
+                    Span::styled(s, Style::default().fg(self.theme.search))
+                })))
+                .chain(Some(search_error))
                 .flatten()
                 .collect::<Vec<_>>(),
+            Focus::Query => Self::intersperse_keys(["Esc"], key_style)
+                .chain([Span::raw(": exit query | ")])
+                .chain(Self::intersperse_keys(["Enter"], key_style))
+                .chain([Span::raw(": run query")])
+                .collect(),
+            Focus::Form => Self::intersperse_keys(["Esc"], key_style)
+                .chain([Span::raw(": cancel | ")])
+                .chain(Self::intersperse_keys(["Tab", "↓", "↑"], key_style))
+                .chain([Span::raw(": navigate | ")])
+                .chain(Self::intersperse_keys(["Enter"], key_style))
+                .chain([Span::raw(": stage changes (NULL for null)")])
+                .collect(),
+            Focus::Export => Self::intersperse_keys(["Esc"], key_style)
+                .chain([Span::raw(": cancel | ")])
+                .chain(Self::intersperse_keys(["Enter"], key_style))
+                .chain([Span::raw(": export (.csv, .jsonl, .md) to: ")])
+                .chain([Span::styled(
+                    self.export_path.clone().unwrap_or_default(),
+                    Style::default().fg(self.theme.search),
+                )])
+                .collect(),
         };
+        if let Some(status) = &self.status {
+            help.push(Span::raw(" || "));
+            help.push(Span::styled(
+                status.clone(),
+                Style::default().fg(self.theme.status),
+            ));
+        }
         Paragraph::new(text::Line::from(help))
     }
 
     fn num_table_rows(&mut self) -> usize {
-        (self.dims.height - 4) as usize // 2 border, 1 header
+        // 2 border, 1 header, 1 help
+        (self.dims.height - 4).saturating_sub(self.query_editor_height()) as usize
+    }
+
+    fn query_editor_height(&self) -> u16 {
+        match (self.focus, &self.query_error) {
+            (Focus::Query, None) => 3,    // 2 border, 1 query
+            (Focus::Query, Some(_)) => 6, // 2 border, 1 query, 3 error
+            _ => 0,
+        }
     }
 
     fn open_table(&mut self) -> Result<()> {
-        if let Some(name) = self.tables.selected() {
-            let mut table = DbTable::new(self.dao.clone(), name, self.search.clone())?;
-            if self.focus == Focus::Table {
-                table.select_first();
+        let Some(object) = self.tables.selected_object().cloned() else {
+            return Ok(());
+        };
+        if self.focus == Focus::Schema || !object.has_records() {
+            self.open_schema()?;
+        }
+        if !object.has_records() {
+            self.table = None;
+            return Ok(());
+        }
+        // keep the columns and order when searching the same table again
+        let view = self
+            .table
+            .as_ref()
+            .filter(|t| !t.is_query() && t.name() == object.name)
+            .map(|t| t.view.clone());
+        let table = DbTable::new(self.dao.clone(), object.name, self.search.clone());
+        // an invalid search should not end the session, keep showing the
+        // last results until it is fixed.
+        let mut table = match table {
+            Err(err) if self.search.is_some() => {
+                self.search_error = Some(format!("{err:#}"));
+                return Ok(());
             }
-            self.table.replace(table);
+            table => table?,
+        };
+        self.search_error = None;
+        if let Some(view) = view {
+            table.set_view(view);
+        }
+        if self.focus == Focus::Table {
+            table.select_first();
+        }
+        self.table.replace(table);
+        Ok(())
+    }
+
+    // opens the referenced table, remembering the current one to go back to
+    fn follow(&mut self, reference: Reference) -> Result<()> {
+        let idx = self
+            .tables
+            .objects
+            .iter()
+            .position(|o| o.name.eq_ignore_ascii_case(&reference.table))
+            .context(format!("{} does not exist", reference.table))?;
+        let name = self.tables.objects[idx].name.clone();
+        let mut table = DbTable::new(self.dao.clone(), name, Some(reference.search.clone()))?;
+        table.select_first();
+        if let Some(prev) = self.table.replace(table) {
+            self.trail.push(Crumb {
+                table: prev,
+                search: self.search.replace(reference.search),
+                selected: self.tables.state.selected(),
+            });
+        }
+        self.search_error = None;
+        self.tables.state.select(Some(idx));
+        Ok(())
+    }
+
+    fn open_schema(&mut self) -> Result<()> {
+        if let Some(name) = self.tables.selected() {
+            self.schema.replace(self.dao.table_details(name)?);
+            self.schema_scroll = 0;
         }
         Ok(())
     }
@@ -328,9 +928,15 @@ impl App {
                 if Self::should_quit(key) {
                     return Ok(Tick::Quit);
                 }
+                self.status = None;
                 let action = match self.focus {
                     Focus::Search => self.search(key),
-                    Focus::Tables | Focus::Table => self.bindings.matches(self.focus, key),
+                    Focus::Query => self.edit_query(key),
+                    Focus::Form => self.edit_form(key),
+                    Focus::Export => self.edit_export(key),
+                    Focus::Tables | Focus::Table | Focus::Changes | Focus::Schema => {
+                        self.bindings.matches(self.focus, key)
+                    }
                 };
 
                 if let Some(a) = action {
@@ -344,11 +950,13 @@ impl App {
     fn process_action(&mut self, action: Action) -> Tick {
         match action {
             Action::TablesNext => {
+                self.trail.clear();
                 if self.tables.next() {
                     self.open_table();
                 }
             }
             Action::TablesPrev => {
+                self.trail.clear();
                 if self.tables.previous() {
                     self.open_table();
                 }
@@ -368,20 +976,160 @@ impl App {
                     Focus::Tables => {
                         self.table.iter_mut().for_each(DbTable::unselect);
                     }
+                    Focus::Table if self.table.is_none() => {
+                        // a trigger is selected, which only has a definition
+                        self.focus = Focus::Schema;
+                    }
                     Focus::Table => {
                         self.table.iter_mut().for_each(DbTable::select_first);
                     }
+                    Focus::Schema => {
+                        if let Err(err) = self.open_schema() {
+                            self.status = Some(format!("{err:#}"));
+                        }
+                    }
                     Focus::Search => {
                         self.search = None;
+                        self.search_error = None;
                     }
+                    Focus::Export => {
+                        let name = self.table.as_ref().map(|t| t.name().to_string());
+                        self.export_path = Some(format!("{}.csv", name.unwrap_or_default()));
+                    }
+                    Focus::Query | Focus::Form | Focus::Changes => {}
                 }
             }
-            Action::PageUp => {}
-            Action::PageDown => {}
+            Action::PageUp => {
+                let rows = self.page_size.unwrap_or_else(|| self.num_table_rows());
+                self.table.iter_mut().for_each(|t| t.page_up(rows));
+            }
+            Action::PageDown => {
+                let rows = self.page_size.unwrap_or_else(|| self.num_table_rows());
+                self.table.iter_mut().for_each(|t| t.page_down(rows));
+            }
             Action::Search => {
                 // User is searching live, return to search prompt
                 self.open_table();
             }
+            Action::RunQuery => self.run_query(),
+            Action::EditRow => {
+                let form = self.table.as_ref().filter(|t| !t.is_query()).and_then(|t| {
+                    let mut form = Form::edit(&t.schema, t.selected()?)?;
+                    // start on the focused column
+                    form.select_field(t.focused_column()?.name());
+                    Some(form)
+                });
+                if let Some(form) = form {
+                    self.form.replace(form);
+                    self.focus = Focus::Form;
+                }
+            }
+            Action::InsertRow => {
+                let table = self.table.as_ref().filter(|t| t.schema.has_rowid());
+                if let Some(table) = table {
+                    self.form.replace(Form::insert(&table.schema));
+                    self.focus = Focus::Form;
+                }
+            }
+            Action::DeleteRow => {
+                let table = self.table.as_ref().filter(|t| !t.is_query());
+                if let Some(table) = table {
+                    let record = table.selected();
+                    if let Some((record, rowid)) = record.and_then(|r| Some((r, r.rowid()?))) {
+                        // a second delete of the row would fail the whole commit
+                        let staged = self.pending.iter().any(|c| {
+                            matches!(c, Change::Delete { table: t, rowid: r, .. }
+                                if t == table.name() && *r == rowid)
+                        });
+                        if !staged {
+                            self.pending.push(Change::Delete {
+                                table: table.name().to_string(),
+                                rowid,
+                                record: record.clone(),
+                            });
+                        }
+                    }
+                }
+            }
+            Action::SubmitForm => {
+                if let Some(form) = self.form.take() {
+                    self.pending.extend(form.changes());
+                }
+                self.focus = Focus::Table;
+            }
+            Action::Commit => match self.dao.apply(&self.pending) {
+                Ok(()) => {
+                    self.pending.clear();
+                    self.commit_error = None;
+                    self.focus = Focus::Table;
+                    // pick up the new counts and values
+                    self.open_table();
+                }
+                Err(err) => {
+                    warn!("commit failed: {err:#}");
+                    self.commit_error = Some(format!("{err:#}"));
+                }
+            },
+            Action::Export => {
+                self.focus = Focus::Table;
+                if let Some(path) = self.export_path.take() {
+                    self.status = Some(match self.export(&path) {
+                        Ok(count) => format!("exported {count} records to {path}"),
+                        Err(err) => format!("export failed: {err:#}"),
+                    });
+                }
+            }
+            Action::FollowKey => {
+                let table = self.table.as_ref().filter(|t| !t.is_query());
+                let reference = table.map(DbTable::reference).transpose();
+                match reference.map(Option::flatten) {
+                    Ok(Some(reference)) => {
+                        if let Err(err) = self.follow(reference) {
+                            self.status = Some(format!("{err:#}"));
+                        }
+                    }
+                    Ok(None) => self.status = Some("no foreign key to follow".to_string()),
+                    Err(err) => self.status = Some(format!("{err:#}")),
+                }
+            }
+            Action::Back => {
+                if let Some(crumb) = self.trail.pop() {
+                    self.table.replace(crumb.table);
+                    self.search = crumb.search;
+                    self.search_error = None;
+                    self.tables.state.select(crumb.selected);
+                }
+            }
+            Action::ColumnNext => {
+                self.table.iter_mut().for_each(|t| {
+                    t.next_column();
+                });
+            }
+            Action::ColumnPrev => {
+                let moved = self.table.as_mut().is_some_and(DbTable::previous_column);
+                if !moved {
+                    return self.process_action(Action::ChangeFocus(Focus::Tables));
+                }
+            }
+            Action::SortColumn => self.table.iter_mut().for_each(DbTable::cycle_sort),
+            Action::HideColumn => self.table.iter_mut().for_each(DbTable::hide_column),
+            Action::ShowColumns => self.table.iter_mut().for_each(DbTable::show_columns),
+            Action::SchemaDown => {
+                let lines = self
+                    .schema
+                    .as_ref()
+                    .map(|s| Self::schema_lines(s, &self.theme));
+                let max = lines.map(|l| l.len()).unwrap_or(0).saturating_sub(1) as u16;
+                self.schema_scroll = (self.schema_scroll + 1).min(max);
+            }
+            Action::SchemaUp => {
+                self.schema_scroll = self.schema_scroll.saturating_sub(1);
+            }
+            Action::Rollback => {
+                self.pending.clear();
+                self.commit_error = None;
+                self.focus = Focus::Table;
+            }
             Action::Quit => return Tick::Quit,
         }
 
@@ -420,6 +1168,104 @@ impl App {
         }
     }
 
+    fn edit_query(&mut self, k: KeyEvent) -> Option<Action> {
+        match k.code {
+            KeyCode::Esc => {
+                self.query_error = None;
+                Some(Action::ChangeFocus(Focus::Table))
+            }
+            KeyCode::Enter => Some(Action::RunQuery),
+            KeyCode::Backspace => {
+                if let Some(q) = self.query.as_mut() {
+                    q.pop();
+                }
+                if self.query.as_ref().is_some_and(|q| q.is_empty()) {
+                    self.query = None;
+                }
+                None
+            }
+            KeyCode::Char(c) => {
+                self.query.get_or_insert_with(String::default).push(c);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn edit_form(&mut self, k: KeyEvent) -> Option<Action> {
+        let form = self.form.as_mut()?;
+        match k.code {
+            KeyCode::Esc => {
+                self.form = None;
+                Some(Action::ChangeFocus(Focus::Table))
+            }
+            KeyCode::Enter => Some(Action::SubmitForm),
+            KeyCode::Tab | KeyCode::Down => {
+                form.select_next();
+                None
+            }
+            KeyCode::BackTab | KeyCode::Up => {
+                form.select_previous();
+                None
+            }
+            KeyCode::Backspace => {
+                form.pop();
+                None
+            }
+            KeyCode::Char(c) => {
+                form.push(c);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn edit_export(&mut self, k: KeyEvent) -> Option<Action> {
+        match k.code {
+            KeyCode::Esc => {
+                self.export_path = None;
+                Some(Action::ChangeFocus(Focus::Table))
+            }
+            KeyCode::Enter => Some(Action::Export),
+            KeyCode::Backspace => {
+                self.export_path.get_or_insert_with(String::default).pop();
+                None
+            }
+            KeyCode::Char(c) => {
+                self.export_path.get_or_insert_with(String::default).push(c);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    // writes the current table, including its search, to a file
+    fn export(&self, path: &str) -> Result<usize> {
+        let table = self.table.as_ref().context("no table to export")?;
+        let format = Format::from_path(path).context("unknown export format")?;
+        let file = std::fs::File::create(path).context(format!("could not create {path}"))?;
+        Exporter::new(format).export(table, io::BufWriter::new(file))
+    }
+
+    fn run_query(&mut self) {
+        let Some(sql) = self.query.clone() else {
+            return;
+        };
+        match DbTable::query(self.dao.clone(), sql) {
+            Ok(mut table) => {
+                table.select_first();
+                self.table.replace(table);
+                self.trail.clear();
+                self.query_error = None;
+                self.focus = Focus::Table;
+            }
+            Err(err) => {
+                warn!("query failed: {err:#}");
+                self.query_error = Some(format!("{err:#}"));
+            }
+        }
+    }
+
     fn should_quit(key: KeyEvent) -> bool {
         match key.code {
             KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return true,
@@ -439,3 +1285,65 @@ impl App {
             .skip(1)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebind() -> Result<()> {
+        let config = Config::parse(
+            r#"
+            [keys.table]
+            sort = ["S", "o"]
+            focus_schema = ["ctrl-s"]
+            hide_column = []
+            "#,
+        )?;
+        let mut bindings = KeyBindSet::default();
+        bindings.rebind(&config.keys);
+        let key = |s: &str| -> Result<KeyEvent> { Ok(s.parse::<Key>()?.0) };
+
+        let matches =
+            |s: &str| -> Result<Option<Action>> { Ok(bindings.matches(Focus::Table, key(s)?)) };
+        assert_eq!(Some(Action::SortColumn), matches("S")?);
+        assert_eq!(Some(Action::SortColumn), matches("o")?);
+        assert_eq!(Some(Action::ChangeFocus(Focus::Schema)), matches("ctrl-s")?);
+        // replaced and unbound keys no longer do anything
+        assert_eq!(None, matches("s")?);
+        assert_eq!(None, matches("-")?);
+        assert!(!bindings
+            .actions(Focus::Table)
+            .iter()
+            .any(|(a, _)| *a == Action::HideColumn));
+        // other views keep their keys
+        assert_eq!(
+            Some(Action::ChangeFocus(Focus::Schema)),
+            bindings.matches(Focus::Tables, key("s")?)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_row_once() -> Result<()> {
+        let mut app = App::new(DbType::Memory, Tz::UTC, Config::default())?;
+        app.dao
+            .execute("create table foo (name TEXT, age INTEGER)")?;
+        app.dao
+            .execute("insert into foo (name, age) values ('collin', 46), ('ryan', 40)")?;
+        app.tables = DbTables::new(app.dao.tables()?);
+        app.focus = Focus::Table;
+        app.open_table()?;
+        let table = app.table.as_mut().unwrap();
+        table.set_viewport_rows(10);
+        table.records()?;
+
+        app.process_action(Action::DeleteRow);
+        app.process_action(Action::DeleteRow);
+        assert_eq!(1, app.pending.len());
+        app.process_action(Action::Commit);
+        assert!(app.commit_error.is_none());
+        assert_eq!(1, app.dao.count(Count::new("foo"))?);
+        Ok(())
+    }
+}
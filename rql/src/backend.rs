@@ -0,0 +1,217 @@
+use crate::{
+    dao::*,
+    filter::{Dialect, Filter, Where},
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono_tz::Tz;
+use mysql::MysqlBackend;
+use postgres::PostgresBackend;
+use sqlite::SqliteBackend;
+
+mod mysql;
+mod postgres;
+mod sqlite;
+
+/// The queries that differ between databases. Records are decoded into
+/// values in the timezone they are displayed in.
+#[async_trait]
+pub(crate) trait Backend: Send + Sync {
+    async fn tables(&self) -> Result<Vec<SchemaObject>>;
+    async fn table_details(&self, name: &str) -> Result<TableDetails>;
+    async fn foreign_keys(&self, name: &str) -> Result<Vec<ForeignKeySpec>>;
+    async fn table_schema(&self, name: &str) -> Result<TableSchema>;
+    async fn records(&self, schema: &TableSchema, req: GetRecords, tz: &Tz) -> Result<Vec<Record>>;
+    async fn count(&self, req: Count) -> Result<u64>;
+    async fn max_lens(&self, schema: &TableSchema, req: MaxLens) -> Result<Vec<usize>>;
+    async fn query_schema(&self, sql: &str) -> Result<TableSchema>;
+    async fn query_count(&self, sql: &str) -> Result<u64>;
+    async fn query(&self, schema: &TableSchema, req: Query, tz: &Tz) -> Result<Vec<Record>>;
+    async fn apply(&self, changes: &[Change]) -> Result<()>;
+    async fn execute(&self, sql: &str) -> Result<()>;
+}
+
+/// Connects to the backend picked by the scheme of the url. Anything that is
+/// not a postgres or mysql url is opened as sqlite.
+pub(crate) async fn connect(db: &DbType<'_>) -> Result<Box<dyn Backend>> {
+    let backend: Box<dyn Backend> = match db {
+        DbType::Path(url) => match url.split_once("://").map(|(scheme, _)| scheme) {
+            Some("postgres" | "postgresql") => Box::new(PostgresBackend::connect(url).await?),
+            Some("mysql" | "mariadb") => Box::new(MysqlBackend::connect(url).await?),
+            _ => Box::new(SqliteBackend::connect(url).await?),
+        },
+        DbType::Memory => Box::new(SqliteBackend::connect(":memory:").await?),
+    };
+    Ok(backend)
+}
+
+// prepares a user supplied statement to be used as a subquery. only a single
+// statement is accepted, otherwise one that closes the subquery could run
+// others after it.
+pub(crate) fn trim_query(sql: &str) -> Result<&str> {
+    let sql = sql.trim().trim_end_matches(';').trim_end();
+    if sql.is_empty() {
+        anyhow::bail!("query is empty");
+    }
+    if has_separator(sql) {
+        anyhow::bail!("only a single statement can be run");
+    }
+    Ok(sql)
+}
+
+// whether there is a ; that is not in a quote or a comment
+fn has_separator(sql: &str) -> bool {
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ';' => return true,
+            // a doubled quote is an escaped one, which is skipped as an empty
+            // quote followed by another
+            '\'' | '"' | '`' => while chars.next().is_some_and(|q| q != c) {},
+            '-' if chars.next_if_eq(&'-').is_some() => {
+                while chars.next().is_some_and(|c| c != '\n') {}
+            }
+            '/' if chars.next_if_eq(&'*').is_some() => {
+                while let Some(c) = chars.next() {
+                    if c == '*' && chars.next_if_eq(&'/').is_some() {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+// compiles the search query into a where clause for the table. None is
+// returned if there is no query or it does not constrain anything.
+pub(crate) fn build_where_clause(
+    schema: &TableSchema,
+    query: Option<&str>,
+    dialect: Dialect,
+) -> Result<Option<Where>> {
+    match query {
+        Some(query) => Filter::parse(query)?.to_sql(schema, dialect),
+        None => Ok(None),
+    }
+}
+
+// the select of a page of records for the servers that have no rowid. rows
+// are ordered by the primary key, or by every column of a table without one,
+// after the sort if there is one, so that pages do not overlap.
+fn records_query(
+    dialect: Dialect,
+    schema: &TableSchema,
+    cols: &str,
+    wher: &Option<Where>,
+    req: &GetRecords,
+) -> String {
+    let mut key = schema
+        .cols
+        .iter()
+        .filter_map(|c| match c {
+            TableColumn::Spec(spec) if spec.pk => Some(dialect.quote(&spec.name)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if key.is_empty() {
+        // names in the order by are the selected columns, which are cast to
+        // text when their type has no ordering
+        key = schema
+            .cols
+            .iter()
+            .map(|c| dialect.quote(c.name()))
+            .collect();
+    }
+    let mut order = req
+        .sort
+        .iter()
+        .map(|s| s.to_sql(dialect))
+        .chain(key)
+        .collect::<Vec<_>>()
+        .join(", ");
+    if !order.is_empty() {
+        order.insert_str(0, " order by ");
+    }
+    let wher = wher
+        .as_ref()
+        .map(|w| format!(" WHERE {}", w.sql))
+        .unwrap_or_default();
+    format!(
+        "select {cols} from {}{wher}{order}{}",
+        dialect.quote(&schema.name),
+        limit_offset(dialect, req.limit, req.offset)
+    )
+}
+
+// a page of the results of an ad-hoc query
+fn query_query(dialect: Dialect, req: &Query) -> Result<String> {
+    let order = req
+        .sort
+        .as_ref()
+        .map(|s| format!(" order by {}", s.to_sql(dialect)))
+        .unwrap_or_default();
+    Ok(format!(
+        "select * from ({}) as q{order}{}",
+        trim_query(&req.sql)?,
+        limit_offset(dialect, req.limit, req.offset)
+    ))
+}
+
+fn limit_offset(dialect: Dialect, limit: Option<usize>, offset: Option<usize>) -> String {
+    match (limit, offset) {
+        (Some(limit), Some(offset)) => format!(" limit {limit} offset {offset}"),
+        (Some(limit), None) => format!(" limit {limit}"),
+        // mysql requires a limit for an offset to be used
+        (None, Some(offset)) if dialect == Dialect::Mysql => {
+            format!(" limit {} offset {offset}", u64::MAX)
+        }
+        (None, Some(offset)) => format!(" offset {offset}"),
+        (None, None) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_records_query_order() -> Result<()> {
+        let backend = SqliteBackend::connect(":memory:").await?;
+        backend
+            .execute("create table pet (id INTEGER PRIMARY KEY, name TEXT)")
+            .await?;
+        backend
+            .execute("create view named as select id, name from pet")
+            .await?;
+
+        // pages are ordered by the key, or every column without one
+        let schema = backend.table_schema("pet").await?;
+        let query = records_query(Dialect::Mysql, &schema, "*", &None, &GetRecords::new("pet"));
+        assert_eq!("select * from `pet` order by `id`", query);
+        let schema = backend.table_schema("named").await?;
+        let query = records_query(
+            Dialect::Postgres,
+            &schema,
+            "*",
+            &None,
+            &GetRecords::new("named"),
+        );
+        assert_eq!(r#"select * from "named" order by "id", "name""#, query);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trim_query() -> Result<()> {
+        assert_eq!("select 1", trim_query(" select 1 ;; ")?);
+        assert_eq!(
+            "select ';', \"a;b\", 'it''s;' -- ;\n/* ; */ from foo",
+            trim_query("select ';', \"a;b\", 'it''s;' -- ;\n/* ; */ from foo;")?
+        );
+        assert!(trim_query(" ; ").is_err());
+        assert!(trim_query("select 1); drop table foo; select * from (select 1").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,611 @@
+use super::{build_where_clause, query_query, records_query, trim_query, Backend};
+use crate::{
+    dao::*,
+    filter::{Dialect, Where},
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use sqlx::{
+    mysql::{MySqlPool, MySqlRow},
+    types::Decimal,
+    Column, Executor, MySql, Row, Statement, TypeInfo, ValueRef,
+};
+use tracing::{debug, info};
+
+const DIALECT: Dialect = Dialect::Mysql;
+
+/// The tables and views of the database named in the url. There is no rowid,
+/// so rows cannot be edited.
+pub(crate) struct MysqlBackend {
+    pool: MySqlPool,
+}
+
+// the declared types that are decoded as they are sent. columns of other
+// types, like bits and geometries, are read as text.
+const DECODED: [&str; 24] = [
+    "tinyint",
+    "smallint",
+    "mediumint",
+    "int",
+    "bigint",
+    "float",
+    "double",
+    "decimal",
+    "char",
+    "varchar",
+    "tinytext",
+    "text",
+    "mediumtext",
+    "longtext",
+    "enum",
+    "binary",
+    "varbinary",
+    "tinyblob",
+    "blob",
+    "mediumblob",
+    "longblob",
+    "date",
+    "datetime",
+    "timestamp",
+];
+
+impl MysqlBackend {
+    pub(crate) async fn connect(url: &str) -> Result<Self> {
+        // sqlx only knows the mysql scheme
+        let url = match url.strip_prefix("mariadb://") {
+            Some(rest) => format!("mysql://{rest}"),
+            None => url.to_string(),
+        };
+        let pool = MySqlPool::connect(&url)
+            .await
+            .context("could not connect to mysql")?;
+        Ok(Self { pool })
+    }
+
+    async fn schema_object(&self, name: &str) -> Result<SchemaObject> {
+        let mut conn = self.pool.acquire().await?;
+        let typ = sqlx::query_scalar::<_, String>(
+            "select table_type from information_schema.tables
+            where table_schema = database() and table_name = ?",
+        )
+        .bind(name)
+        .fetch_optional(&mut *conn)
+        .await?
+        .context(format!("{name} does not exist"))?;
+        let kind = match typ.as_str() {
+            "VIEW" => ObjectKind::View,
+            _ => ObjectKind::Table,
+        };
+        let name = name.to_string();
+        Ok(SchemaObject { name, kind })
+    }
+
+    // the data type, like int, and the column type, like int(10) unsigned,
+    // of each column
+    async fn columns(&self, name: &str) -> Result<Vec<(String, TableColumnSpec)>> {
+        let mut conn = self.pool.acquire().await?;
+        let cols = sqlx::query_as::<_, (String, String, String, i64, i64, Option<String>, i64)>(
+            "select column_name, data_type, column_type,
+                cast(ordinal_position as signed), is_nullable = 'NO',
+                column_default, column_key = 'PRI'
+            from information_schema.columns
+            where table_schema = database() and table_name = ?
+            order by ordinal_position",
+        )
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|(name, data_type, typ, pos, notnull, dflt_value, pk)| {
+            let spec = TableColumnSpec {
+                name,
+                typ,
+                cid: (pos - 1) as u32,
+                notnull: notnull != 0,
+                dflt_value,
+                pk: pk != 0,
+            };
+            (data_type, spec)
+        })
+        .collect();
+        Ok(cols)
+    }
+
+    async fn indexes(&self, name: &str) -> Result<Vec<IndexSpec>> {
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query_as::<_, (String, i64, Option<String>)>(
+            "select index_name, cast(non_unique as signed), column_name
+            from information_schema.statistics
+            where table_schema = database() and table_name = ?
+            order by index_name, seq_in_index",
+        )
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?;
+        let mut indexes: Vec<IndexSpec> = vec![];
+        for (name, non_unique, col) in rows {
+            // functional indexes have no column name
+            let col = col.unwrap_or_else(|| "<expr>".to_string());
+            match indexes.last_mut() {
+                Some(index) if index.name == name => index.cols.push(col),
+                _ => indexes.push(IndexSpec {
+                    origin: match (name.as_str(), non_unique) {
+                        ("PRIMARY", _) => "pk",
+                        (_, 0) => "u",
+                        _ => "c",
+                    }
+                    .to_string(),
+                    name,
+                    unique: non_unique == 0,
+                    partial: false,
+                    cols: vec![col],
+                }),
+            }
+        }
+        Ok(indexes)
+    }
+}
+
+#[async_trait]
+impl Backend for MysqlBackend {
+    async fn tables(&self) -> Result<Vec<SchemaObject>> {
+        let mut conn = self.pool.acquire().await?;
+        let res = sqlx::query_as::<_, (String, String)>(
+            "select table_name, table_type from information_schema.tables
+            where table_schema = database()
+            order by case table_type when 'VIEW' then 1 else 0 end, table_name",
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|(name, typ)| SchemaObject {
+            name,
+            kind: match typ.as_str() {
+                "VIEW" => ObjectKind::View,
+                _ => ObjectKind::Table,
+            },
+        })
+        .collect();
+        Ok(res)
+    }
+
+    async fn table_details(&self, name: &str) -> Result<TableDetails> {
+        info!(name, "Getting table details");
+        let object = self.schema_object(name).await?;
+        let mut conn = self.pool.acquire().await?;
+        // the statement is the second column for tables and views alike
+        let query = format!("show create table {}", DIALECT.quote(name));
+        let row = sqlx::query(&query).fetch_one(&mut *conn).await?;
+        let sql = row.try_get::<Option<String>, _>(1)?;
+        let cols = self
+            .columns(name)
+            .await?
+            .into_iter()
+            .map(|(_, spec)| spec)
+            .collect();
+        Ok(TableDetails {
+            object,
+            cols,
+            indexes: self.indexes(name).await?,
+            foreign_keys: self.foreign_keys(name).await?,
+            sql,
+        })
+    }
+
+    async fn foreign_keys(&self, name: &str) -> Result<Vec<ForeignKeySpec>> {
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query_as::<_, (String, i64, String, String, String, String, String)>(
+            "select k.constraint_name, cast(k.ordinal_position as signed),
+                k.referenced_table_name, k.column_name, k.referenced_column_name,
+                r.update_rule, r.delete_rule
+            from information_schema.key_column_usage k
+            join information_schema.referential_constraints r
+                on r.constraint_schema = k.constraint_schema
+                and r.constraint_name = k.constraint_name
+            where k.table_schema = database() and k.table_name = ?
+                and k.referenced_table_name is not null
+            order by k.constraint_name, k.ordinal_position",
+        )
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?;
+        let mut keys: Vec<ForeignKeySpec> = vec![];
+        let mut last = None;
+        for (constraint, pos, table, from, to, on_update, on_delete) in rows {
+            // keys are numbered like sqlite does
+            let id = match keys.last() {
+                Some(key) if last.as_ref() == Some(&constraint) => key.id,
+                Some(key) => key.id + 1,
+                None => 0,
+            };
+            last = Some(constraint);
+            keys.push(ForeignKeySpec {
+                id,
+                seq: pos - 1,
+                table,
+                from,
+                to: Some(to),
+                on_update,
+                on_delete,
+            });
+        }
+        Ok(keys)
+    }
+
+    async fn table_schema(&self, name: &str) -> Result<TableSchema> {
+        info!(name, "Getting table schema");
+        // fails if the table does not exist
+        self.schema_object(name).await?;
+        let cols = self
+            .columns(name)
+            .await?
+            .into_iter()
+            .map(|(_, spec)| TableColumn::Spec(spec))
+            .collect();
+        let name = name.to_string();
+        Ok(TableSchema { name, cols })
+    }
+
+    async fn records(&self, schema: &TableSchema, req: GetRecords, tz: &Tz) -> Result<Vec<Record>> {
+        let schema = self.table_schema(&req.table_name).await?;
+        let data_types = self.columns(&req.table_name).await?;
+        let mut conn = self.pool.acquire().await?;
+        let cols = data_types
+            .iter()
+            .map(|(data_type, spec)| {
+                let ident = DIALECT.quote(&spec.name);
+                if DECODED.contains(&data_type.as_str()) {
+                    ident
+                } else {
+                    format!("CAST({ident} AS CHAR) AS {ident}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let where_clause = build_where_clause(&schema, req.query.as_deref(), DIALECT)?;
+        let query = records_query(DIALECT, &schema, &cols, &where_clause, &req);
+        debug!(query, "records query");
+        let rows = sqlx::query_with(&query, Where::arguments_of::<MySql>(&where_clause))
+            .fetch_all(&mut *conn)
+            .await?;
+        rows.iter().map(|row| decode_row(row, tz)).collect()
+    }
+
+    async fn count(&self, req: Count) -> Result<u64> {
+        let schema = self.table_schema(&req.table_name).await?;
+        let mut conn = self.pool.acquire().await?;
+        let mut query = format!("select count(*) from {}", DIALECT.quote(&schema.name));
+        let where_clause = build_where_clause(&schema, req.query.as_deref(), DIALECT)?;
+        if let Some(wher) = &where_clause {
+            query.push_str(&format!(" WHERE {}", wher.sql));
+        }
+        debug!(query, "count query");
+        let count = sqlx::query_scalar_with::<_, i64, _>(
+            &query,
+            Where::arguments_of::<MySql>(&where_clause),
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok(count as u64)
+    }
+
+    async fn max_lens(&self, schema: &TableSchema, req: MaxLens) -> Result<Vec<usize>> {
+        let mut conn = self.pool.acquire().await?;
+        let lens = schema
+            .cols
+            .iter()
+            .map(|c| {
+                let len = DIALECT.length(&DIALECT.quote(c.name()));
+                format!("cast(max({len}) as signed)")
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut query = format!("select {lens} from {}", DIALECT.quote(&schema.name));
+        let where_clause = build_where_clause(schema, req.query.as_deref(), DIALECT)?;
+        if let Some(wher) = &where_clause {
+            query.push_str(&format!(" WHERE {}", wher.sql));
+        }
+        debug!(query, "max lens query");
+        let row = sqlx::query_with(&query, Where::arguments_of::<MySql>(&where_clause))
+            .fetch_one(&mut *conn)
+            .await?;
+        let mut res = vec![];
+        for (idx, col) in schema.cols.iter().enumerate() {
+            // the length of a date as text does not match how it is displayed
+            if let Some(len) = col.field_type().display_len() {
+                res.push(len);
+                continue;
+            }
+            let len = row.try_get::<Option<i64>, _>(idx)?.unwrap_or_default();
+            res.push(len.try_into().unwrap_or_default());
+        }
+        Ok(res)
+    }
+
+    async fn query_schema(&self, sql: &str) -> Result<TableSchema> {
+        let query = format!("select * from ({}) as q", trim_query(sql)?);
+        info!(query, "Getting query schema");
+        let mut conn = self.pool.acquire().await?;
+        let stmt = (&mut *conn).prepare(&query).await?;
+        let cols = stmt
+            .columns()
+            .iter()
+            .map(|c| {
+                TableColumn::Result(ResultColumn {
+                    name: c.name().to_string(),
+                    ordinal: c.ordinal(),
+                })
+            })
+            .collect();
+        let name = "query".to_string();
+        Ok(TableSchema { name, cols })
+    }
+
+    async fn query_count(&self, sql: &str) -> Result<u64> {
+        let mut conn = self.pool.acquire().await?;
+        let query = format!("select count(*) from ({}) as q", trim_query(sql)?);
+        debug!(query, "query count query");
+        let count = sqlx::query_scalar::<_, i64>(&query)
+            .fetch_one(&mut *conn)
+            .await?;
+        Ok(count as u64)
+    }
+
+    async fn query(&self, schema: &TableSchema, req: Query, tz: &Tz) -> Result<Vec<Record>> {
+        let mut conn = self.pool.acquire().await?;
+        let query = query_query(DIALECT, &req)?;
+        debug!(query, "query records query");
+        let rows = sqlx::query(&query).fetch_all(&mut *conn).await?;
+        rows.iter().map(|row| decode_row(row, tz)).collect()
+    }
+
+    async fn apply(&self, changes: &[Change]) -> Result<()> {
+        anyhow::bail!("changes can only be written to sqlite databases")
+    }
+
+    async fn execute(&self, sql: &str) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        (&mut *conn).execute(sql).await?;
+        Ok(())
+    }
+}
+
+fn decode_row(row: &MySqlRow, tz: &Tz) -> Result<Record> {
+    let mut record = Record::default();
+    for column in row.columns() {
+        let name = column.name().to_string();
+        let (typ, val) = decode(row, column.ordinal(), tz)?;
+        record.fields.push(Field { name, typ, val });
+    }
+    Ok(record)
+}
+
+// values are decoded by the type mysql sends with them, which is known even
+// for nulls. the session time zone is UTC, so timestamps are read as UTC.
+fn decode(row: &MySqlRow, idx: usize, tz: &Tz) -> Result<(FieldType, FieldValue)> {
+    let raw = row.try_get_raw(idx)?;
+    let null = raw.is_null();
+    let name = raw.type_info().name().to_string();
+    let res = match name.as_str() {
+        "BOOLEAN" => (FieldType::Boolean, FieldValue::Boolean(row.try_get(idx)?)),
+        "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "BIGINT" => {
+            (FieldType::Integer, FieldValue::Integer(row.try_get(idx)?))
+        }
+        unsigned if unsigned.ends_with(" UNSIGNED") => {
+            let val = row.try_get_unchecked::<Option<u64>, _>(idx)?;
+            match val.map(i64::try_from).transpose() {
+                Ok(val) => (FieldType::Integer, FieldValue::Integer(val)),
+                // too large to be an integer
+                Err(_) => (
+                    FieldType::Text,
+                    FieldValue::Text(val.map(|v| v.to_string())),
+                ),
+            }
+        }
+        "FLOAT" => {
+            let val = row.try_get::<Option<f32>, _>(idx)?;
+            (FieldType::Real, FieldValue::Real(val.map(f64::from)))
+        }
+        "DOUBLE" => (FieldType::Real, FieldValue::Real(row.try_get(idx)?)),
+        "DECIMAL" => {
+            let val = row.try_get::<Option<Decimal>, _>(idx)?;
+            let val = val.and_then(|d| d.to_string().parse().ok());
+            (FieldType::Numeric, FieldValue::Numeric(val))
+        }
+        "CHAR" | "VARCHAR" | "TINYTEXT" | "TEXT" | "MEDIUMTEXT" | "LONGTEXT" | "ENUM" | "SET"
+        | "JSON" => {
+            let val = row.try_get_unchecked::<Option<String>, _>(idx)?;
+            (FieldType::Text, FieldValue::Text(val))
+        }
+        "BINARY" | "VARBINARY" | "TINYBLOB" | "BLOB" | "MEDIUMBLOB" | "LONGBLOB" => {
+            (FieldType::Blob, FieldValue::Blob(row.try_get(idx)?))
+        }
+        "DATE" => (FieldType::Date, FieldValue::Date(row.try_get(idx)?)),
+        "DATETIME" => {
+            let val = row.try_get::<Option<NaiveDateTime>, _>(idx)?;
+            let val = val.map(|dt| dt.and_utc().with_timezone(tz));
+            (FieldType::DateTime, FieldValue::DateTime(val))
+        }
+        "TIMESTAMP" => {
+            let val = row.try_get::<Option<DateTime<Utc>>, _>(idx)?;
+            let val = val.map(|dt| dt.with_timezone(tz));
+            (FieldType::DateTime, FieldValue::DateTime(val))
+        }
+        _ if null => (FieldType::Null, FieldValue::Null),
+        // the value can be cast to text in the query to see it
+        _ => {
+            debug!(typ = name, "could not decode value");
+            (FieldType::Text, FieldValue::Text(Some(format!("<{name}>"))))
+        }
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // runs against the database at $RQL_TEST_MYSQL_URL, see the README. the
+    // tables are dropped and created again on each run.
+    async fn connect() -> Result<MysqlBackend> {
+        let url = std::env::var("RQL_TEST_MYSQL_URL").context("RQL_TEST_MYSQL_URL is not set")?;
+        let db = MysqlBackend::connect(&url).await?;
+        db.execute("drop view if exists named; drop table if exists pet, owner")
+            .await?;
+        Ok(db)
+    }
+
+    #[tokio::test]
+    #[ignore = "needs RQL_TEST_MYSQL_URL"]
+    async fn test_mysql() -> Result<()> {
+        let db = connect().await?;
+        db.execute(
+            "create table owner (id int primary key auto_increment, name varchar(20) not null unique);
+            create table pet (
+                id bigint unsigned primary key auto_increment,
+                name varchar(20) default 'rex',
+                owner_id int,
+                born timestamp null,
+                weight decimal(5, 2),
+                good boolean,
+                mood enum('happy', 'sad'),
+                big bigint unsigned,
+                tags json,
+                foreign key (owner_id) references owner(id) on delete cascade
+            );
+            create index pet_owner on pet (owner_id, name);
+            create view named as select name, owner_id + 1 as next from pet;
+            insert into owner (name) values ('collin'), ('other');
+            insert into pet (name, owner_id, born, weight, good, mood, big, tags) values
+                ('rex', 1, '2023-09-14 16:30:00', 12.5, true, 'happy', 18446744073709551615, '[\"a\"]'),
+                ('o''brien', 2, null, null, false, null, 7, null),
+                ('100%', 1, null, 1, null, 'sad', null, null)",
+        )
+        .await?;
+
+        let objects = db.tables().await?;
+        assert_eq!(
+            vec![
+                ("owner", ObjectKind::Table),
+                ("pet", ObjectKind::Table),
+                ("named", ObjectKind::View),
+            ],
+            objects
+                .iter()
+                .map(|o| (o.name.as_str(), o.kind))
+                .collect::<Vec<_>>()
+        );
+
+        let details = db.table_details("pet").await?;
+        assert_eq!(9, details.cols.len());
+        assert!(details.cols[0].pk && details.cols[0].notnull);
+        assert_eq!("bigint unsigned", details.cols[0].typ);
+        assert_eq!("varchar(20)", details.cols[1].typ);
+        assert!(details.cols[1].dflt_value.is_some());
+        let index = details.indexes.iter().find(|i| i.name == "pet_owner");
+        assert_eq!(vec!["owner_id", "name"], index.unwrap().cols);
+        let pkey = details.indexes.iter().find(|i| i.origin == "pk").unwrap();
+        assert!(pkey.unique);
+        let key = &details.foreign_keys[0];
+        assert_eq!(
+            ("owner", "owner_id", Some("id"), "CASCADE"),
+            (
+                key.table.as_str(),
+                key.from.as_str(),
+                key.to.as_deref(),
+                key.on_delete.as_str()
+            )
+        );
+        let details = db.table_details("named").await?;
+        assert!(details.sql.unwrap().starts_with("CREATE"));
+        assert!(db.table_details("nope").await.is_err());
+
+        let tz: Tz = "America/New_York".parse().unwrap();
+        let schema = db.table_schema("pet").await?;
+        assert!(!schema.has_rowid());
+        let records = db
+            .records(&schema, GetRecords::new("pet").limit(2), &tz)
+            .await?;
+        assert_eq!(2, records.len());
+        // an unsigned value too large to be an integer is shown as text, and
+        // json, which is not decoded, is cast to text
+        assert_eq!(
+            vec![
+                "1",
+                "rex",
+                "1",
+                "2023-09-14T12:30:00-04:00",
+                "12.5",
+                "true",
+                "happy",
+                "18446744073709551615",
+                "[\"a\"]"
+            ],
+            records[0]
+                .fields
+                .iter()
+                .map(|f| f.val.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert!(records[1].fields[3].val.is_null());
+        assert_eq!("7", records[1].fields[7].val.to_string());
+
+        // searches are case insensitive, and bind booleans and numbers
+        let search = |query: &str| GetRecords::new("pet").search(query);
+        let names = |records: Vec<Record>| -> Vec<String> {
+            records
+                .iter()
+                .map(|r| r.fields[1].val.to_string())
+                .collect()
+        };
+        let matches = db.records(&schema, search("REX"), &tz).await?;
+        assert_eq!(vec!["rex"], names(matches));
+        let matches = db.records(&schema, search("name:%"), &tz).await?;
+        assert_eq!(vec!["100%"], names(matches));
+        let matches = db.records(&schema, search("good:false"), &tz).await?;
+        assert_eq!(vec!["o'brien"], names(matches));
+        let sorted = search("owner_id=1").maybe_sort(&Some(Sort::desc("name")));
+        assert_eq!(
+            vec!["rex", "100%"],
+            names(db.records(&schema, sorted, &tz).await?)
+        );
+        // an offset without a limit needs one that is large enough
+        let offset = GetRecords::new("pet").offset(1);
+        assert_eq!(
+            vec!["o'brien", "100%"],
+            names(db.records(&schema, offset, &tz).await?)
+        );
+        assert_eq!(2, db.count(Count::new("pet").search("owner_id=1")).await?);
+        assert_eq!(0, db.count(Count::new("pet").search("nomatch")).await?);
+
+        let lens = db.max_lens(&schema, MaxLens::new("pet")).await?;
+        assert_eq!(7, lens[1]);
+        assert_eq!(25, lens[3]);
+
+        let sql = "select p.name, o.name as owner from pet p join owner o on o.id = p.owner_id;";
+        let schema = db.query_schema(sql).await?;
+        assert_eq!(
+            vec!["name", "owner"],
+            schema.cols.iter().map(|c| c.name()).collect::<Vec<_>>()
+        );
+        assert_eq!(3, db.query_count(sql).await?);
+        let query = Query::new(sql)
+            .offset(1)
+            .maybe_sort(&Some(Sort::asc("name")));
+        let records = db.query(&schema, query, &tz).await?;
+        assert_eq!(2, records.len());
+        assert_eq!(
+            vec!["rex", "collin"],
+            records[1]
+                .fields
+                .iter()
+                .map(|f| f.val.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert!(db.query_schema("select nope from pet").await.is_err());
+        assert!(db.apply(&[]).await.is_err());
+        Ok(())
+    }
+}
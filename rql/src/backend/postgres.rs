@@ -0,0 +1,612 @@
+use super::{build_where_clause, query_query, records_query, trim_query, Backend};
+use crate::{
+    dao::*,
+    filter::{Dialect, Where},
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use sqlx::{
+    postgres::{PgPool, PgRow},
+    types::{Decimal, JsonValue, Uuid},
+    Column, Executor, Postgres, Row, Statement, TypeInfo, ValueRef,
+};
+use tracing::{debug, info};
+
+const DIALECT: Dialect = Dialect::Postgres;
+
+/// The tables and views of the current schema, usually `public`. There is no
+/// rowid, so rows cannot be edited.
+pub(crate) struct PostgresBackend {
+    pool: PgPool,
+}
+
+// the declared types that are decoded as they are sent. columns of other
+// types, like arrays and enums, are read as text.
+const DECODED: [&str; 19] = [
+    "boolean",
+    "smallint",
+    "integer",
+    "bigint",
+    "real",
+    "double precision",
+    "numeric",
+    "text",
+    "character varying",
+    "character",
+    "name",
+    "bytea",
+    "uuid",
+    "json",
+    "jsonb",
+    "date",
+    "time without time zone",
+    "timestamp without time zone",
+    "timestamp with time zone",
+];
+
+impl PostgresBackend {
+    pub(crate) async fn connect(url: &str) -> Result<Self> {
+        let pool = PgPool::connect(url)
+            .await
+            .context("could not connect to postgres")?;
+        Ok(Self { pool })
+    }
+
+    // the kind of the object and, for views, its definition
+    async fn schema_object(&self, name: &str) -> Result<(SchemaObject, Option<String>)> {
+        let mut conn = self.pool.acquire().await?;
+        let (typ, def) = sqlx::query_as::<_, (String, Option<String>)>(
+            "select table_type::text,
+                case table_type when 'VIEW' then pg_get_viewdef(to_regclass($2), true) end
+            from information_schema.tables
+            where table_schema = current_schema() and table_name = $1",
+        )
+        .bind(name)
+        .bind(DIALECT.quote(name))
+        .fetch_optional(&mut *conn)
+        .await?
+        .context(format!("{name} does not exist"))?;
+        let kind = match typ.as_str() {
+            "VIEW" => ObjectKind::View,
+            _ => ObjectKind::Table,
+        };
+        let sql = def.map(|def| format!("CREATE VIEW {} AS\n{def}", DIALECT.quote(name)));
+        let object = SchemaObject {
+            name: name.to_string(),
+            kind,
+        };
+        Ok((object, sql))
+    }
+
+    async fn columns(&self, name: &str) -> Result<Vec<TableColumnSpec>> {
+        let mut conn = self.pool.acquire().await?;
+        let cols = sqlx::query_as::<_, (String, String, i32, bool, Option<String>, bool)>(
+            "select c.column_name::text, c.data_type::text, c.ordinal_position::int4,
+                c.is_nullable = 'NO', c.column_default::text,
+                exists (
+                    select 1 from information_schema.table_constraints t
+                    join information_schema.key_column_usage k
+                        using (constraint_schema, constraint_name)
+                    where t.constraint_type = 'PRIMARY KEY'
+                        and t.table_schema = c.table_schema
+                        and t.table_name = c.table_name
+                        and k.column_name = c.column_name
+                )
+            from information_schema.columns c
+            where c.table_schema = current_schema() and c.table_name = $1
+            order by c.ordinal_position",
+        )
+        .bind(name)
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(
+            |(name, typ, pos, notnull, dflt_value, pk)| TableColumnSpec {
+                name,
+                typ,
+                cid: (pos - 1) as u32,
+                notnull,
+                dflt_value,
+                pk,
+            },
+        )
+        .collect();
+        Ok(cols)
+    }
+
+    async fn indexes(&self, name: &str) -> Result<Vec<IndexSpec>> {
+        let mut conn = self.pool.acquire().await?;
+        let indexes = sqlx::query_as::<_, (String, bool, String, bool, Vec<String>)>(
+            "select i.relname::text, x.indisunique,
+                case when x.indisprimary then 'pk' when c.oid is not null then 'u' else 'c' end,
+                x.indpred is not null,
+                array(
+                    select pg_get_indexdef(x.indexrelid, k + 1, true)
+                    from generate_subscripts(x.indkey, 1) k
+                    order by k
+                )
+            from pg_index x
+            join pg_class i on i.oid = x.indexrelid
+            left join pg_constraint c on c.conindid = x.indexrelid and c.contype = 'u'
+            where x.indrelid = to_regclass($1)
+            order by i.relname",
+        )
+        .bind(DIALECT.quote(name))
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|(name, unique, origin, partial, cols)| IndexSpec {
+            name,
+            unique,
+            origin,
+            partial,
+            cols,
+        })
+        .collect();
+        Ok(indexes)
+    }
+}
+
+// the referential action of a foreign key, as sqlite names it
+const ACTION: &str = "case {} when 'r' then 'RESTRICT' when 'c' then 'CASCADE'
+    when 'n' then 'SET NULL' when 'd' then 'SET DEFAULT' else 'NO ACTION' end";
+
+#[async_trait]
+impl Backend for PostgresBackend {
+    async fn tables(&self) -> Result<Vec<SchemaObject>> {
+        let mut conn = self.pool.acquire().await?;
+        let res = sqlx::query_as::<_, (String, String)>(
+            "select table_name::text, table_type::text from information_schema.tables
+            where table_schema = current_schema()
+            order by case table_type when 'VIEW' then 1 else 0 end, table_name",
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|(name, typ)| SchemaObject {
+            name,
+            kind: match typ.as_str() {
+                "VIEW" => ObjectKind::View,
+                _ => ObjectKind::Table,
+            },
+        })
+        .collect();
+        Ok(res)
+    }
+
+    async fn table_details(&self, name: &str) -> Result<TableDetails> {
+        info!(name, "Getting table details");
+        let (object, sql) = self.schema_object(name).await?;
+        Ok(TableDetails {
+            object,
+            cols: self.columns(name).await?,
+            indexes: self.indexes(name).await?,
+            foreign_keys: self.foreign_keys(name).await?,
+            sql,
+        })
+    }
+
+    async fn foreign_keys(&self, name: &str) -> Result<Vec<ForeignKeySpec>> {
+        let mut conn = self.pool.acquire().await?;
+        let query = format!(
+            "select c.oid::int8, k.seq - 1, r.relname::text, a.attname::text, f.attname::text,
+                {}, {}
+            from pg_constraint c
+            cross join unnest(c.conkey, c.confkey) with ordinality as k(col, fcol, seq)
+            join pg_class r on r.oid = c.confrelid
+            join pg_attribute a on a.attrelid = c.conrelid and a.attnum = k.col
+            join pg_attribute f on f.attrelid = c.confrelid and f.attnum = k.fcol
+            where c.contype = 'f' and c.conrelid = to_regclass($1)
+            order by c.conname, k.seq",
+            ACTION.replace("{}", "c.confupdtype"),
+            ACTION.replace("{}", "c.confdeltype"),
+        );
+        let keys = sqlx::query_as::<_, (i64, i64, String, String, String, String, String)>(&query)
+            .bind(DIALECT.quote(name))
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(
+                |(id, seq, table, from, to, on_update, on_delete)| ForeignKeySpec {
+                    id,
+                    seq,
+                    table,
+                    from,
+                    to: Some(to),
+                    on_update,
+                    on_delete,
+                },
+            )
+            .collect();
+        Ok(keys)
+    }
+
+    async fn table_schema(&self, name: &str) -> Result<TableSchema> {
+        info!(name, "Getting table schema");
+        // fails if the table does not exist
+        self.schema_object(name).await?;
+        let cols = self
+            .columns(name)
+            .await?
+            .into_iter()
+            .map(TableColumn::Spec)
+            .collect();
+        let name = name.to_string();
+        Ok(TableSchema { name, cols })
+    }
+
+    async fn records(&self, schema: &TableSchema, req: GetRecords, tz: &Tz) -> Result<Vec<Record>> {
+        let schema = self.table_schema(&req.table_name).await?;
+        let mut conn = self.pool.acquire().await?;
+        let cols = schema
+            .cols
+            .iter()
+            .map(|col| {
+                let ident = DIALECT.quote(col.name());
+                match col {
+                    TableColumn::Spec(spec) if !DECODED.contains(&spec.typ.as_str()) => {
+                        format!("CAST({ident} AS text) AS {ident}")
+                    }
+                    _ => ident,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let where_clause = build_where_clause(&schema, req.query.as_deref(), DIALECT)?;
+        let query = records_query(DIALECT, &schema, &cols, &where_clause, &req);
+        debug!(query, "records query");
+        let rows = sqlx::query_with(&query, Where::arguments_of::<Postgres>(&where_clause))
+            .fetch_all(&mut *conn)
+            .await?;
+        rows.iter().map(|row| decode_row(row, tz)).collect()
+    }
+
+    async fn count(&self, req: Count) -> Result<u64> {
+        let schema = self.table_schema(&req.table_name).await?;
+        let mut conn = self.pool.acquire().await?;
+        let mut query = format!("select count(*) from {}", DIALECT.quote(&schema.name));
+        let where_clause = build_where_clause(&schema, req.query.as_deref(), DIALECT)?;
+        if let Some(wher) = &where_clause {
+            query.push_str(&format!(" WHERE {}", wher.sql));
+        }
+        debug!(query, "count query");
+        let count = sqlx::query_scalar_with::<_, i64, _>(
+            &query,
+            Where::arguments_of::<Postgres>(&where_clause),
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok(count as u64)
+    }
+
+    async fn max_lens(&self, schema: &TableSchema, req: MaxLens) -> Result<Vec<usize>> {
+        let mut conn = self.pool.acquire().await?;
+        let lens = schema
+            .cols
+            .iter()
+            .map(|c| format!("max({})::int8", DIALECT.length(&DIALECT.quote(c.name()))))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut query = format!("select {lens} from {}", DIALECT.quote(&schema.name));
+        let where_clause = build_where_clause(schema, req.query.as_deref(), DIALECT)?;
+        if let Some(wher) = &where_clause {
+            query.push_str(&format!(" WHERE {}", wher.sql));
+        }
+        debug!(query, "max lens query");
+        let row = sqlx::query_with(&query, Where::arguments_of::<Postgres>(&where_clause))
+            .fetch_one(&mut *conn)
+            .await?;
+        let mut res = vec![];
+        for (idx, col) in schema.cols.iter().enumerate() {
+            // the length of a date as text does not match how it is displayed
+            if let Some(len) = col.field_type().display_len() {
+                res.push(len);
+                continue;
+            }
+            let len = row.try_get::<Option<i64>, _>(idx)?.unwrap_or_default();
+            res.push(len.try_into().unwrap_or_default());
+        }
+        Ok(res)
+    }
+
+    async fn query_schema(&self, sql: &str) -> Result<TableSchema> {
+        let query = format!("select * from ({}) as q", trim_query(sql)?);
+        info!(query, "Getting query schema");
+        let mut conn = self.pool.acquire().await?;
+        let stmt = (&mut *conn).prepare(&query).await?;
+        let cols = stmt
+            .columns()
+            .iter()
+            .map(|c| {
+                TableColumn::Result(ResultColumn {
+                    name: c.name().to_string(),
+                    ordinal: c.ordinal(),
+                })
+            })
+            .collect();
+        let name = "query".to_string();
+        Ok(TableSchema { name, cols })
+    }
+
+    async fn query_count(&self, sql: &str) -> Result<u64> {
+        let mut conn = self.pool.acquire().await?;
+        let query = format!("select count(*) from ({}) as q", trim_query(sql)?);
+        debug!(query, "query count query");
+        let count = sqlx::query_scalar::<_, i64>(&query)
+            .fetch_one(&mut *conn)
+            .await?;
+        Ok(count as u64)
+    }
+
+    async fn query(&self, schema: &TableSchema, req: Query, tz: &Tz) -> Result<Vec<Record>> {
+        let mut conn = self.pool.acquire().await?;
+        let query = query_query(DIALECT, &req)?;
+        debug!(query, "query records query");
+        let rows = sqlx::query(&query).fetch_all(&mut *conn).await?;
+        rows.iter().map(|row| decode_row(row, tz)).collect()
+    }
+
+    async fn apply(&self, changes: &[Change]) -> Result<()> {
+        anyhow::bail!("changes can only be written to sqlite databases")
+    }
+
+    async fn execute(&self, sql: &str) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        // several statements can be run at once without arguments
+        (&mut *conn).execute(sql).await?;
+        Ok(())
+    }
+}
+
+fn decode_row(row: &PgRow, tz: &Tz) -> Result<Record> {
+    let mut record = Record::default();
+    for column in row.columns() {
+        let name = column.name().to_string();
+        let (typ, val) = decode(row, column.ordinal(), tz)?;
+        record.fields.push(Field { name, typ, val });
+    }
+    Ok(record)
+}
+
+// values are decoded by the type postgres sends with them, which is known
+// even for nulls.
+fn decode(row: &PgRow, idx: usize, tz: &Tz) -> Result<(FieldType, FieldValue)> {
+    let raw = row.try_get_raw(idx)?;
+    let null = raw.is_null();
+    let name = raw.type_info().name().to_string();
+    let res = match name.as_str() {
+        "BOOL" => (FieldType::Boolean, FieldValue::Boolean(row.try_get(idx)?)),
+        "INT2" => {
+            let val = row.try_get::<Option<i16>, _>(idx)?;
+            (FieldType::Integer, FieldValue::Integer(val.map(i64::from)))
+        }
+        "INT4" => {
+            let val = row.try_get::<Option<i32>, _>(idx)?;
+            (FieldType::Integer, FieldValue::Integer(val.map(i64::from)))
+        }
+        "INT8" => (FieldType::Integer, FieldValue::Integer(row.try_get(idx)?)),
+        "FLOAT4" => {
+            let val = row.try_get::<Option<f32>, _>(idx)?;
+            (FieldType::Real, FieldValue::Real(val.map(f64::from)))
+        }
+        "FLOAT8" => (FieldType::Real, FieldValue::Real(row.try_get(idx)?)),
+        "NUMERIC" => {
+            let val = row.try_get::<Option<Decimal>, _>(idx)?;
+            let val = val.and_then(|d| d.to_string().parse().ok());
+            (FieldType::Numeric, FieldValue::Numeric(val))
+        }
+        "TEXT" | "VARCHAR" | "CHAR" | "NAME" => {
+            (FieldType::Text, FieldValue::Text(row.try_get(idx)?))
+        }
+        "BYTEA" => (FieldType::Blob, FieldValue::Blob(row.try_get(idx)?)),
+        "UUID" => {
+            let val = row.try_get::<Option<Uuid>, _>(idx)?;
+            (
+                FieldType::Text,
+                FieldValue::Text(val.map(|v| v.to_string())),
+            )
+        }
+        "JSON" | "JSONB" => {
+            let val = row.try_get::<Option<JsonValue>, _>(idx)?;
+            (
+                FieldType::Text,
+                FieldValue::Text(val.map(|v| v.to_string())),
+            )
+        }
+        "DATE" => (FieldType::Date, FieldValue::Date(row.try_get(idx)?)),
+        "TIME" => (FieldType::Time, FieldValue::Time(row.try_get(idx)?)),
+        // timestamps without a time zone are in UTC
+        "TIMESTAMP" => {
+            let val = row.try_get::<Option<NaiveDateTime>, _>(idx)?;
+            let val = val.map(|dt| dt.and_utc().with_timezone(tz));
+            (FieldType::DateTime, FieldValue::DateTime(val))
+        }
+        "TIMESTAMPTZ" => {
+            let val = row.try_get::<Option<DateTime<Utc>>, _>(idx)?;
+            let val = val.map(|dt| dt.with_timezone(tz));
+            (FieldType::DateTime, FieldValue::DateTime(val))
+        }
+        _ if null => (FieldType::Null, FieldValue::Null),
+        // the value can be cast to text in the query to see it
+        _ => {
+            debug!(typ = name, "could not decode value");
+            (FieldType::Text, FieldValue::Text(Some(format!("<{name}>"))))
+        }
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // runs against the database at $RQL_TEST_POSTGRES_URL, see the README.
+    // the tables are created in a schema of their own that is replaced on
+    // each run.
+    async fn connect() -> Result<PostgresBackend> {
+        let url = std::env::var("RQL_TEST_POSTGRES_URL")
+            .context("RQL_TEST_POSTGRES_URL is not set")?;
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(1)
+            .after_connect(|conn, _| {
+                Box::pin(async move {
+                    conn.execute("drop schema if exists rql_test cascade; create schema rql_test; set search_path to rql_test").await?;
+                    Ok(())
+                })
+            })
+            .connect(&url)
+            .await?;
+        Ok(PostgresBackend { pool })
+    }
+
+    #[tokio::test]
+    #[ignore = "needs RQL_TEST_POSTGRES_URL"]
+    async fn test_postgres() -> Result<()> {
+        let db = connect().await?;
+        db.execute(
+            "create table owner (id serial primary key, name text not null unique);
+            create type mood as enum ('happy', 'sad');
+            create table pet (
+                id bigserial primary key,
+                name varchar(20) default 'rex',
+                owner_id int references owner(id) on delete cascade,
+                born timestamptz,
+                weight numeric(5, 2),
+                good boolean,
+                mood mood,
+                tags text[]
+            );
+            create index pet_owner on pet (owner_id, lower(name));
+            create view named as select name, owner_id + 1 as next from pet;
+            insert into owner (name) values ('collin'), ('other');
+            insert into pet (name, owner_id, born, weight, good, mood, tags) values
+                ('rex', 1, '2023-09-14 16:30:00+00', 12.5, true, 'happy', '{a,b}'),
+                ('o''brien', 2, null, null, false, null, null),
+                ('100%', 1, null, 1, null, 'sad', '{}')",
+        )
+        .await?;
+
+        let objects = db.tables().await?;
+        assert_eq!(
+            vec![
+                ("owner", ObjectKind::Table),
+                ("pet", ObjectKind::Table),
+                ("named", ObjectKind::View),
+            ],
+            objects
+                .iter()
+                .map(|o| (o.name.as_str(), o.kind))
+                .collect::<Vec<_>>()
+        );
+
+        let details = db.table_details("pet").await?;
+        assert_eq!(8, details.cols.len());
+        assert!(details.cols[0].pk && details.cols[0].notnull);
+        assert_eq!("character varying", details.cols[1].typ);
+        assert_eq!(
+            Some("'rex'::character varying".to_string()),
+            details.cols[1].dflt_value
+        );
+        let index = details.indexes.iter().find(|i| i.name == "pet_owner");
+        assert_eq!(vec!["owner_id", "lower(name::text)"], index.unwrap().cols);
+        let pkey = details.indexes.iter().find(|i| i.origin == "pk").unwrap();
+        assert!(pkey.unique);
+        let key = &details.foreign_keys[0];
+        assert_eq!(
+            ("owner", "owner_id", Some("id"), "CASCADE"),
+            (
+                key.table.as_str(),
+                key.from.as_str(),
+                key.to.as_deref(),
+                key.on_delete.as_str()
+            )
+        );
+        let details = db.table_details("named").await?;
+        assert!(details.sql.unwrap().starts_with("CREATE VIEW"));
+        assert!(db.table_details("nope").await.is_err());
+
+        let tz: Tz = "America/New_York".parse().unwrap();
+        let schema = db.table_schema("pet").await?;
+        assert!(!schema.has_rowid());
+        let records = db
+            .records(&schema, GetRecords::new("pet").limit(2), &tz)
+            .await?;
+        assert_eq!(
+            vec![
+                "1",
+                "rex",
+                "1",
+                "2023-09-14T12:30:00-04:00",
+                "12.5",
+                "true",
+                "happy",
+                "{a,b}"
+            ],
+            records[0]
+                .fields
+                .iter()
+                .map(|f| f.val.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert!(records[1].fields[3].val.is_null());
+
+        // searches are case insensitive, and bind booleans and numbers
+        let search = |query: &str| GetRecords::new("pet").search(query);
+        let names = |records: Vec<Record>| -> Vec<String> {
+            records
+                .iter()
+                .map(|r| r.fields[1].val.to_string())
+                .collect()
+        };
+        let matches = db.records(&schema, search("REX"), &tz).await?;
+        assert_eq!(vec!["rex"], names(matches));
+        let matches = db.records(&schema, search("name:%"), &tz).await?;
+        assert_eq!(vec!["100%"], names(matches));
+        let matches = db.records(&schema, search("good:false"), &tz).await?;
+        assert_eq!(vec!["o'brien"], names(matches));
+        let matches = db
+            .records(&schema, search("-mood:happy weight<5"), &tz)
+            .await?;
+        assert_eq!(vec!["100%"], names(matches));
+        let matches = db.records(&schema, search("-good:true"), &tz).await?;
+        assert_eq!(vec!["o'brien", "100%"], names(matches));
+        let sorted = search("owner_id=1").maybe_sort(&Some(Sort::desc("name")));
+        assert_eq!(
+            vec!["rex", "100%"],
+            names(db.records(&schema, sorted, &tz).await?)
+        );
+        assert_eq!(2, db.count(Count::new("pet").search("owner_id=1")).await?);
+        assert_eq!(0, db.count(Count::new("pet").search("nomatch")).await?);
+
+        let lens = db.max_lens(&schema, MaxLens::new("pet")).await?;
+        assert_eq!(7, lens[1]);
+        assert_eq!(25, lens[3]);
+
+        let sql =
+            "select p.name, o.name as owner, p.tags from pet p join owner o on o.id = p.owner_id;";
+        let schema = db.query_schema(sql).await?;
+        assert_eq!(
+            vec!["name", "owner", "tags"],
+            schema.cols.iter().map(|c| c.name()).collect::<Vec<_>>()
+        );
+        assert_eq!(3, db.query_count(sql).await?);
+        let query = Query::new(sql)
+            .offset(1)
+            .maybe_sort(&Some(Sort::asc("name")));
+        let records = db.query(&schema, query, &tz).await?;
+        assert_eq!(2, records.len());
+        assert_eq!(
+            vec!["rex", "collin", "<TEXT[]>"],
+            records[1]
+                .fields
+                .iter()
+                .map(|f| f.val.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert!(db.query_schema("select nope from pet").await.is_err());
+        assert!(db.apply(&[]).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,548 @@
+use super::{build_where_clause, trim_query, Backend};
+use crate::{
+    dao::*,
+    filter::{quote_ident, Dialect, Where},
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use sqlx::{
+    sqlite::{SqliteConnection, SqliteRow},
+    Column, Executor, Pool, Row, Sqlite, SqlitePool, Statement, TypeInfo, ValueRef,
+};
+use tracing::{debug, info};
+
+pub(crate) struct SqliteBackend {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteBackend {
+    pub(crate) async fn connect(path: &str) -> Result<Self> {
+        let pool = if path == ":memory:" {
+            SqlitePool::connect(path)
+                .await
+                .context("could not connect to memory db")?
+        } else {
+            SqlitePool::connect(path)
+                .await
+                .context(format!(r#"could not open "{path}""#))?
+        };
+        Ok(Self { pool })
+    }
+
+    // the kind of the object and the statement that created it
+    async fn schema_object(&self, name: &str) -> Result<(SchemaObject, Option<String>)> {
+        let mut conn = self.pool.acquire().await?;
+        let (typ, sql) = sqlx::query_as::<_, (String, Option<String>)>(
+            "select type, sql from sqlite_schema where name = ?",
+        )
+        .bind(name)
+        .fetch_optional(&mut *conn)
+        .await?
+        .context(format!("{name} does not exist"))?;
+        let object = SchemaObject {
+            name: name.to_string(),
+            kind: ObjectKind::from(typ.as_str()),
+        };
+        Ok((object, sql))
+    }
+}
+
+#[async_trait]
+impl Backend for SqliteBackend {
+    async fn tables(&self) -> Result<Vec<SchemaObject>> {
+        #[derive(sqlx::FromRow)]
+        struct Record {
+            name: String,
+            #[sqlx(rename = "type")]
+            typ: String,
+        }
+        let mut conn = self.pool.acquire().await?;
+        let res = sqlx::query_as::<_, Record>(
+            "select name, type from sqlite_schema
+            where type in ('table', 'view', 'trigger')
+            order by case type when 'table' then 0 when 'view' then 1 else 2 end, name",
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|s| SchemaObject {
+            name: s.name,
+            kind: ObjectKind::from(s.typ.as_str()),
+        })
+        .collect();
+        Ok(res)
+    }
+
+    async fn table_schema(&self, name: &str) -> Result<TableSchema> {
+        let name = name.to_string();
+        info!(name, "Getting table schema");
+        let (object, _) = self.schema_object(&name).await?;
+        let mut conn = self.pool.acquire().await?;
+        let query = format!("pragma table_info({})", quote_ident(&name));
+        let mut cols = sqlx::query_as::<_, TableColumnSpec>(&query)
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|c| TableColumn::Spec(c))
+            .collect::<Vec<_>>();
+        // views do not have a rowid
+        if object.kind == ObjectKind::Table {
+            cols.insert(0, TableColumn::RowId);
+        }
+        let schema = TableSchema { name, cols };
+        Ok(schema)
+    }
+
+    async fn foreign_keys(&self, name: &str) -> Result<Vec<ForeignKeySpec>> {
+        let mut conn = self.pool.acquire().await?;
+        let query = format!("pragma foreign_key_list({})", quote_ident(name));
+        let keys = sqlx::query_as::<_, ForeignKeySpec>(&query)
+            .fetch_all(&mut *conn)
+            .await?;
+        Ok(keys)
+    }
+
+    async fn table_details(&self, name: &str) -> Result<TableDetails> {
+        info!(name, "Getting table details");
+        let (object, sql) = self.schema_object(name).await?;
+        let mut conn = self.pool.acquire().await?;
+        let ident = quote_ident(name);
+        let cols = sqlx::query_as::<_, TableColumnSpec>(&format!("pragma table_info({ident})"))
+            .fetch_all(&mut *conn)
+            .await?;
+        let mut indexes = sqlx::query_as::<_, IndexSpec>(&format!("pragma index_list({ident})"))
+            .fetch_all(&mut *conn)
+            .await?;
+        for index in &mut indexes {
+            let query = format!("pragma index_info({})", quote_ident(&index.name));
+            index.cols = sqlx::query_as::<_, (i64, i64, Option<String>)>(&query)
+                .fetch_all(&mut *conn)
+                .await?
+                .into_iter()
+                .map(|(_, _, name)| name.unwrap_or_else(|| "<expr>".to_string()))
+                .collect();
+        }
+        let foreign_keys = self.foreign_keys(name).await?;
+        Ok(TableDetails {
+            object,
+            cols,
+            indexes,
+            foreign_keys,
+            sql,
+        })
+    }
+
+    async fn max_lens(&self, schema: &TableSchema, req: MaxLens) -> Result<Vec<usize>> {
+        let mut conn = self.pool.acquire().await?;
+        let query_parts = &schema
+            .cols
+            .iter()
+            .map(|c| format!("max(length({}))", quote_ident(c.name())))
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut query = format!("select {} from {}", query_parts, quote_ident(&schema.name));
+        let where_clause = build_where_clause(schema, req.query.as_deref(), Dialect::Sqlite)?;
+        if let Some(wher) = &where_clause {
+            query.push_str(&format!(" WHERE {}", wher.sql));
+        }
+        debug!("max lens query: {}", query);
+        let row = sqlx::query_with(&query, Where::arguments_of::<Sqlite>(&where_clause))
+            .fetch_one(&mut *conn)
+            .await?;
+        let mut res = vec![];
+        for (idx, col) in schema.cols.iter().enumerate() {
+            // the stored length of a date does not match how it is displayed
+            if let Some(len) = col.field_type().display_len() {
+                res.push(len);
+                continue;
+            }
+            let len = row.get::<i64, _>(idx);
+            res.push(len.try_into().unwrap_or_default());
+        }
+        Ok(res)
+    }
+
+    async fn count(&self, req: Count) -> Result<u64> {
+        #[derive(sqlx::FromRow)]
+        struct Record {
+            count: i64,
+        }
+        let schema = self.table_schema(&req.table_name).await?;
+        let mut conn = self.pool.acquire().await?;
+        let mut query = format!(
+            "select count(*) as count from {}",
+            quote_ident(&req.table_name)
+        );
+        let where_clause = build_where_clause(&schema, req.query.as_deref(), Dialect::Sqlite)?;
+        if let Some(wher) = &where_clause {
+            query.push_str(&format!(" WHERE {}", wher.sql));
+        }
+
+        debug!("count query: {}", query);
+        let record = sqlx::query_as_with::<_, Record, _>(
+            &query,
+            Where::arguments_of::<Sqlite>(&where_clause),
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok(record.count as u64)
+    }
+
+    async fn records(&self, schema: &TableSchema, req: GetRecords, tz: &Tz) -> Result<Vec<Record>> {
+        let schema = self.table_schema(&req.table_name).await?;
+        let table_name = quote_ident(&req.table_name);
+        let mut conn = self.pool.acquire().await?;
+        let limit = req.limit.map(|v| format!("limit {v}")).unwrap_or_default();
+        let offset = req
+            .offset
+            .map(|v| format!("offset {v}"))
+            .unwrap_or_default();
+        let where_clause = build_where_clause(&schema, req.query.as_deref(), Dialect::Sqlite)?;
+        let order = match &req.sort {
+            // break ties so that pages do not overlap
+            Some(sort) if schema.has_rowid() => {
+                format!("order by {}, rowid", sort.to_sql(Dialect::Sqlite))
+            }
+            Some(sort) => format!("order by {}", sort.to_sql(Dialect::Sqlite)),
+            None => String::new(),
+        };
+
+        let cols = if schema.has_rowid() { "rowid, *" } else { "*" };
+        let query = if let Some(wher) = &where_clause {
+            format!(
+                "select {} from {} WHERE {} {} {} {}",
+                cols, table_name, wher.sql, order, limit, offset
+            )
+        } else {
+            format!(
+                "select {} from {} {} {} {}",
+                cols, table_name, order, limit, offset
+            )
+        };
+        debug!(query, "records query: {}", query);
+
+        let rows = sqlx::query_with(&query, Where::arguments_of::<Sqlite>(&where_clause))
+            .fetch_all(&mut *conn)
+            .await?;
+        let mut records = vec![];
+        for row in rows {
+            let mut record = Record::default();
+            for column in row.columns() {
+                let name = column.name().to_string();
+                let ord = column.ordinal();
+                let col = &schema.cols[ord];
+                let typ = match col.field_type() {
+                    FieldType::Null => FieldType::of_value(&row, ord)?,
+                    typ => typ,
+                };
+                let val = typ.decode(&row, ord, tz)?;
+                let field = Field { name, typ, val };
+                record.fields.push(field);
+            }
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    async fn query_schema(&self, sql: &str) -> Result<TableSchema> {
+        let query = format!("select * from ({})", trim_query(sql)?);
+        info!(query, "Getting query schema");
+        let mut conn = self.pool.acquire().await?;
+        let stmt = (&mut *conn).prepare(&query).await?;
+        let cols = stmt
+            .columns()
+            .iter()
+            .map(|c| {
+                TableColumn::Result(ResultColumn {
+                    name: c.name().to_string(),
+                    ordinal: c.ordinal(),
+                })
+            })
+            .collect();
+        let name = "query".to_string();
+        Ok(TableSchema { name, cols })
+    }
+
+    async fn query_count(&self, sql: &str) -> Result<u64> {
+        let mut conn = self.pool.acquire().await?;
+        let query = format!("select count(*) from ({})", trim_query(sql)?);
+        debug!("query count query: {}", query);
+        let count = sqlx::query_scalar::<_, i64>(&query)
+            .fetch_one(&mut *conn)
+            .await?;
+        Ok(count as u64)
+    }
+
+    async fn query(&self, schema: &TableSchema, req: Query, tz: &Tz) -> Result<Vec<Record>> {
+        let mut conn = self.pool.acquire().await?;
+        // sqlite requires a limit for an offset to be used
+        let limit = req.limit.map(|v| v as i64).unwrap_or(-1);
+        let offset = req.offset.unwrap_or_default();
+        let order = req
+            .sort
+            .as_ref()
+            .map(|s| format!("order by {}", s.to_sql(Dialect::Sqlite)))
+            .unwrap_or_default();
+        let query = format!(
+            "select * from ({}) {} limit {} offset {}",
+            trim_query(&req.sql)?,
+            order,
+            limit,
+            offset
+        );
+        debug!("query records query: {}", query);
+        let rows = sqlx::query(&query).fetch_all(&mut *conn).await?;
+        let mut records = vec![];
+        for row in rows {
+            let mut record = Record::default();
+            for (ord, col) in schema.cols.iter().enumerate() {
+                let name = col.name().to_string();
+                let typ = FieldType::of_value(&row, ord)?;
+                let val = typ.decode(&row, ord, tz)?;
+                let field = Field { name, typ, val };
+                record.fields.push(field);
+            }
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    // applies all of the changes in a single transaction
+    async fn apply(&self, changes: &[Change]) -> Result<()> {
+        info!(changes = changes.len(), "Applying changes");
+        let mut tx = self.pool.begin().await?;
+        for change in changes {
+            change.execute(&mut tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn execute(&self, sql: &str) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query(sql).execute(&mut *conn).await?;
+        Ok(())
+    }
+}
+
+impl FieldType {
+    fn decode(&self, row: &SqliteRow, idx: usize, tz: &Tz) -> Result<FieldValue> {
+        let val = match self {
+            FieldType::Null => FieldValue::Null,
+            FieldType::RowId => FieldValue::RowID(self.decode_i64(row, idx)?.unwrap()),
+            FieldType::Text => FieldValue::Text(self.decode_string(row, idx)?),
+            FieldType::Real => FieldValue::Real(self.decode_f64(row, idx)?),
+            FieldType::Blob => FieldValue::Blob(self.decode_bytes(row, idx)?),
+            FieldType::Integer => FieldValue::Integer(self.decode_i64(row, idx)?),
+            FieldType::Numeric => FieldValue::Numeric(self.decode_f64(row, idx)?),
+            FieldType::Boolean => FieldValue::Boolean(self.decode_bool(row, idx)?),
+            FieldType::Date | FieldType::Time | FieldType::DateTime => {
+                self.decode_temporal(row, idx, tz)?
+            }
+        };
+        Ok(val)
+    }
+
+    // the type of the value as it is stored, regardless of the declared type
+    // of its column.
+    fn of_value(row: &SqliteRow, idx: usize) -> Result<FieldType> {
+        let raw = row.try_get_raw(idx)?;
+        if raw.is_null() {
+            return Ok(FieldType::Null);
+        }
+        let typ = match raw.type_info().name() {
+            "INTEGER" => FieldType::Integer,
+            "REAL" => FieldType::Real,
+            "BLOB" => FieldType::Blob,
+            _ => FieldType::Text,
+        };
+        Ok(typ)
+    }
+
+    // sqlite stores dates and times as iso-8601 text, unix epoch integers, or
+    // julian day reals. values that cannot be read as the column's type are
+    // shown as they are stored.
+    fn decode_temporal(&self, row: &SqliteRow, idx: usize, tz: &Tz) -> Result<FieldValue> {
+        let raw = row.try_get_raw(idx)?;
+        if raw.is_null() {
+            let val = match self {
+                FieldType::Date => FieldValue::Date(None),
+                FieldType::Time => FieldValue::Time(None),
+                _ => FieldValue::DateTime(None),
+            };
+            return Ok(val);
+        }
+        let storage = raw.type_info().name().to_string();
+        let temporal = match storage.as_str() {
+            "INTEGER" => {
+                let secs = row.try_get::<i64, _>(idx)?;
+                DateTime::from_timestamp(secs, 0).map(Temporal::Instant)
+            }
+            "REAL" => {
+                let days = row.try_get::<f64, _>(idx)?;
+                let millis = ((days - UNIX_EPOCH_JULIAN_DAY) * 86_400_000.0).round();
+                DateTime::from_timestamp_millis(millis as i64).map(Temporal::Instant)
+            }
+            _ => Temporal::parse(&row.try_get::<String, _>(idx)?),
+        };
+        let val = match (self, temporal) {
+            (FieldType::DateTime, Some(Temporal::Instant(dt))) => {
+                FieldValue::DateTime(Some(dt.with_timezone(tz)))
+            }
+            (FieldType::DateTime, Some(Temporal::Date(d))) => {
+                let dt = d.and_time(NaiveTime::MIN).and_utc();
+                FieldValue::DateTime(Some(dt.with_timezone(tz)))
+            }
+            (FieldType::Date, Some(Temporal::Instant(dt))) => {
+                FieldValue::Date(Some(dt.with_timezone(tz).date_naive()))
+            }
+            (FieldType::Date, Some(Temporal::Date(d))) => FieldValue::Date(Some(d)),
+            (FieldType::Time, Some(Temporal::Instant(dt))) => {
+                FieldValue::Time(Some(dt.with_timezone(tz).time()))
+            }
+            (FieldType::Time, Some(Temporal::Time(t))) => FieldValue::Time(Some(t)),
+            _ => {
+                debug!(storage, typ = ?self, "could not decode temporal value");
+                FieldValue::Text(row.try_get_unchecked::<Option<String>, _>(idx)?)
+            }
+        };
+        Ok(val)
+    }
+
+    fn decode_bool(&self, row: &SqliteRow, idx: usize) -> Result<Option<bool>> {
+        Ok(row.try_get::<Option<bool>, _>(idx)?)
+    }
+
+    fn decode_i64(&self, row: &SqliteRow, idx: usize) -> Result<Option<i64>> {
+        Ok(row.try_get::<Option<i64>, _>(idx)?)
+    }
+
+    fn decode_bytes(&self, row: &SqliteRow, idx: usize) -> Result<Option<Vec<u8>>> {
+        Ok(row.try_get::<Option<Vec<u8>>, _>(idx)?)
+    }
+
+    fn decode_f64(&self, row: &SqliteRow, idx: usize) -> Result<Option<f64>> {
+        Ok(row.try_get::<Option<f64>, _>(idx)?)
+    }
+
+    fn decode_string(&self, row: &SqliteRow, idx: usize) -> Result<Option<String>> {
+        Ok(row.try_get::<Option<String>, _>(idx)?)
+    }
+}
+
+// the julian day of 1970-01-01T00:00:00Z
+const UNIX_EPOCH_JULIAN_DAY: f64 = 2_440_587.5;
+
+// a date and/or time read from a stored value
+#[derive(Debug, PartialEq)]
+enum Temporal {
+    Instant(DateTime<Utc>),
+    Date(NaiveDate),
+    Time(NaiveTime),
+}
+
+impl Temporal {
+    // parses the text formats understood by sqlite's date and time
+    // functions. times without an offset are in UTC.
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let with_offset = match text.strip_suffix(['Z', 'z']) {
+            Some(rest) => format!("{rest}+00:00"),
+            None => text.to_string(),
+        };
+        for fmt in [
+            "%Y-%m-%d %H:%M:%S%.f%:z",
+            "%Y-%m-%dT%H:%M:%S%.f%:z",
+            "%Y-%m-%d %H:%M%:z",
+            "%Y-%m-%dT%H:%M%:z",
+        ] {
+            if let Ok(dt) = DateTime::parse_from_str(&with_offset, fmt) {
+                return Some(Temporal::Instant(dt.with_timezone(&Utc)));
+            }
+        }
+        for fmt in [
+            "%Y-%m-%d %H:%M:%S%.f",
+            "%Y-%m-%dT%H:%M:%S%.f",
+            "%Y-%m-%d %H:%M",
+            "%Y-%m-%dT%H:%M",
+        ] {
+            if let Ok(dt) = NaiveDateTime::parse_from_str(text, fmt) {
+                return Some(Temporal::Instant(dt.and_utc()));
+            }
+        }
+        if let Ok(d) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+            return Some(Temporal::Date(d));
+        }
+        for fmt in ["%H:%M:%S%.f", "%H:%M"] {
+            if let Ok(t) = NaiveTime::parse_from_str(text, fmt) {
+                return Some(Temporal::Time(t));
+            }
+        }
+        None
+    }
+}
+
+impl Change {
+    async fn execute(&self, conn: &mut SqliteConnection) -> Result<()> {
+        let res = match self {
+            Change::Update {
+                table,
+                rowid,
+                column,
+                new,
+                ..
+            } => {
+                let query = format!(
+                    "update {} set {} = ? where rowid = ?",
+                    quote_ident(table),
+                    quote_ident(column)
+                );
+                debug!("update query: {}", query);
+                sqlx::query(&query)
+                    .bind(new)
+                    .bind(rowid)
+                    .execute(&mut *conn)
+                    .await?
+            }
+            Change::Insert { table, values } if values.is_empty() => {
+                let query = format!("insert into {} default values", quote_ident(table));
+                debug!("insert query: {}", query);
+                sqlx::query(&query).execute(&mut *conn).await?
+            }
+            Change::Insert { table, values } => {
+                let cols = values
+                    .iter()
+                    .map(|(name, _)| quote_ident(name))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let params = vec!["?"; values.len()].join(", ");
+                let query = format!(
+                    "insert into {} ({}) values ({})",
+                    quote_ident(table),
+                    cols,
+                    params
+                );
+                debug!("insert query: {}", query);
+                values
+                    .iter()
+                    .fold(sqlx::query(&query), |q, (_, val)| q.bind(val))
+                    .execute(&mut *conn)
+                    .await?
+            }
+            Change::Delete { table, rowid, .. } => {
+                let query = format!("delete from {} where rowid = ?", quote_ident(table));
+                debug!("delete query: {}", query);
+                sqlx::query(&query).bind(rowid).execute(&mut *conn).await?
+            }
+        };
+        if res.rows_affected() != 1 {
+            anyhow::bail!(
+                "{self}: expected 1 row to change, got {}",
+                res.rows_affected()
+            );
+        }
+        Ok(())
+    }
+}
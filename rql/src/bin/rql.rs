@@ -1,36 +1,78 @@
 use rql::prelude::*;
-use std::{fmt::Debug, path::Path};
+use std::{
+    fmt::Debug,
+    path::{Path, PathBuf},
+};
 use tracing_subscriber::{filter::LevelFilter, prelude::*};
 
 #[derive(clap::Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    #[arg(long, env)]
+    #[arg(long, env, global = true)]
     log: Option<String>,
 
-    #[arg(env)]
-    db_path: String,
+    /// A sqlite file, or a postgres:// or mysql:// url
+    #[arg(env, required = true)]
+    db_path: Option<String>,
+
+    /// The timezone dates and times are displayed in, e.g. America/New_York
+    #[arg(long, env = "RQL_TZ", default_value = "UTC", global = true)]
+    tz: Tz,
+
+    /// Read settings from this file instead of $XDG_CONFIG_HOME/rql/config.toml
+    #[arg(long, env = "RQL_CONFIG")]
+    config: Option<PathBuf>,
 
     #[arg(long, default_value_t = false)]
     quit: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Writes the records of a table to stdout
+    Export(ExportArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct ExportArgs {
+    db_path: String,
+
+    table: String,
+
+    #[arg(long, value_enum)]
+    format: Format,
+
+    /// Only export records matching the search, as typed in the table view
+    #[arg(long)]
+    search: Option<String>,
 }
 
 impl Debug for Args {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let db_path = Path::new(&self.db_path);
+        let db_path = self.db_path.as_deref().map(Path::new);
         let db_name = db_path
-            .file_name()
+            .and_then(|p| p.file_name())
             .map(|s| s.to_os_string())
             .unwrap_or_default();
         f.debug_struct("args")
             .field("db_name", &db_name)
             .field("log", &self.log)
+            .field("tz", &self.tz)
+            .field("config", &self.config)
             .finish()
     }
 }
 
 fn main() {
     let args = Args::parse();
-    if let Err(err) = setup_and_run(&args) {
+    let res = match &args.command {
+        Some(Command::Export(export_args)) => export(&args, export_args),
+        None => setup_and_run(&args),
+    };
+    if let Err(err) = res {
         error!("{err:?}");
         process::exit(1);
     }
@@ -51,7 +93,11 @@ fn init_tracing(args: &Args) -> Result<()> {
     } else {
         // provide users something when a fatal error occurs
         tracing_subscriber::registry()
-            .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::ERROR))
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .with_writer(io::stderr)
+                    .with_filter(LevelFilter::ERROR),
+            )
             .init();
     }
 
@@ -60,19 +106,33 @@ fn init_tracing(args: &Args) -> Result<()> {
 
 fn setup_and_run(args: &Args) -> Result<()> {
     init_tracing(args)?;
+    // read before the terminal is taken over so errors are printed plainly
+    let config = Config::load(args.config.as_deref())?;
     let mut term = setup_terminal().context("term setup failed")?;
-    let res = run(&args, &mut term);
+    let res = run(&args, config, &mut term);
     restore_terminal(&mut term).context("term restore failed")?;
     res
 }
 
-fn run(args: &Args, term: &mut Term) -> Result<()> {
+fn export(args: &Args, export_args: &ExportArgs) -> Result<()> {
+    init_tracing(args)?;
+    info!(?export_args, "Exporting");
+    let dao = BlockingDao::new(DbType::Path(&export_args.db_path), args.tz)?;
+    let table = DbTable::new(dao, export_args.table.clone(), export_args.search.clone())?;
+    let out = io::BufWriter::new(io::stdout().lock());
+    let count = Exporter::new(export_args.format).export(&table, out)?;
+    info!(count, "Exported records");
+    Ok(())
+}
+
+fn run(args: &Args, config: Config, term: &mut Term) -> Result<()> {
     if args.quit {
         return Ok(());
     }
     info!(?args, "Running");
-    let db: DbType = DbType::Path(args.db_path.as_str());
-    let mut app = App::new(db)?;
+    let db_path = args.db_path.as_deref().context("no db path")?;
+    let db: DbType = DbType::Path(db_path);
+    let mut app = App::new(db, args.tz, config)?;
     loop {
         app.draw(term)?;
         match app.tick()? {
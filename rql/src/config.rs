@@ -0,0 +1,283 @@
+use crate::prelude::*;
+use serde::{de, Deserialize, Deserializer};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Settings read from `$XDG_CONFIG_HOME/rql/config.toml`. Everything is
+/// optional and anything left out keeps its default:
+///
+///   page_size = 20
+///
+///   [keys.table]
+///   sort = ["o", "ctrl-o"]
+///   focus_search = ["/"]
+///
+///   [theme]
+///   focus = "lightgreen"
+///   stripe = "234"
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// How many rows page up and page down move, the table's height if unset
+    pub page_size: Option<usize>,
+    /// The keys of actions by where they apply. They replace the default keys.
+    pub(crate) keys: HashMap<Focus, HashMap<Action, Vec<Key>>>,
+    pub theme: Theme,
+}
+
+impl Config {
+    /// Where the config is read from if no path is given
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".config")))?;
+        Some(dir.join("rql").join("config.toml"))
+    }
+
+    /// Reads the config at the path, or at the default path. A missing default
+    /// config is not an error.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Self::default());
+            }
+            res => res.context(format!("could not read {}", path.display()))?,
+        };
+        info!(path = %path.display(), "Loading config");
+        Self::parse(&text).context(format!("invalid config {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+}
+
+/// The colors used to draw the ui
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    #[serde(deserialize_with = "color")]
+    pub focus: Color, // what has focus and inserts
+    #[serde(deserialize_with = "color")]
+    pub selection: Color, // selections without focus
+    #[serde(deserialize_with = "color")]
+    pub header: Color, // column headers and labels
+    #[serde(deserialize_with = "color")]
+    pub stripe: Color, // the background of every other row
+    #[serde(deserialize_with = "color")]
+    pub key: Color, // keys in the help bar
+    #[serde(deserialize_with = "color")]
+    pub search: Color, // an active search
+    #[serde(deserialize_with = "color")]
+    pub error: Color, // errors and deletes
+    #[serde(deserialize_with = "color")]
+    pub status: Color, // the outcome of actions and updates
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            focus: Color::LightGreen,
+            selection: Color::Cyan,
+            header: Color::LightBlue,
+            stripe: Color::Indexed(234),
+            key: Color::LightCyan,
+            search: Color::Green,
+            error: Color::LightRed,
+            status: Color::LightYellow,
+        }
+    }
+}
+
+// colors are named, indexed like "234", or rgb like "#ff0000"
+fn color<'de, D: Deserializer<'de>>(d: D) -> Result<Color, D::Error> {
+    let s = String::deserialize(d)?;
+    Color::from_str(&s).map_err(|_| de::Error::custom(format!("unknown color `{s}`")))
+}
+
+/// A key as it is written in the config, like `j`, `ctrl-f` or `pagedown`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Key(pub KeyEvent);
+
+impl Key {
+    const NAMES: [(&'static str, KeyCode); 14] = [
+        ("enter", KeyCode::Enter),
+        ("esc", KeyCode::Esc),
+        ("tab", KeyCode::Tab),
+        ("backtab", KeyCode::BackTab),
+        ("backspace", KeyCode::Backspace),
+        ("delete", KeyCode::Delete),
+        ("space", KeyCode::Char(' ')),
+        ("up", KeyCode::Up),
+        ("down", KeyCode::Down),
+        ("left", KeyCode::Left),
+        ("right", KeyCode::Right),
+        ("pageup", KeyCode::PageUp),
+        ("pagedown", KeyCode::PageDown),
+        ("home", KeyCode::Home),
+    ];
+}
+
+impl FromStr for Key {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        // a lone - is a key rather than a separator
+        while let Some((modifier, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "c" => KeyModifiers::CONTROL,
+                "alt" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => anyhow::bail!("unknown modifier `{modifier}` in key `{s}`"),
+            };
+            rest = key;
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                let name = rest.to_lowercase();
+                let named = Self::NAMES.iter().find(|(n, _)| *n == name);
+                let function = name.strip_prefix('f').and_then(|n| n.parse().ok());
+                match (named, function) {
+                    (Some((_, code)), _) => *code,
+                    (None, Some(n)) if (1..=12).contains(&n) => KeyCode::F(n),
+                    _ => anyhow::bail!("unknown key `{s}`"),
+                }
+            }
+        };
+        Ok(Self(KeyEvent::new(code, modifiers)))
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let KeyEvent {
+            code, modifiers, ..
+        } = self.0;
+        if modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "C-")?;
+        }
+        if modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "M-")?;
+        }
+        match code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::Backspace => write!(f, "Bksp"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(d)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let config = Config::parse(
+            r#"
+            page_size = 5
+
+            [keys.table]
+            sort = ["S", "ctrl-o"]
+            quit = []
+
+            [theme]
+            focus = "magenta"
+            stripe = "236"
+            "#,
+        )?;
+        assert_eq!(Some(5), config.page_size);
+        assert_eq!(Color::Magenta, config.theme.focus);
+        assert_eq!(Color::Indexed(236), config.theme.stripe);
+        assert_eq!(Color::LightBlue, config.theme.header);
+        assert_eq!(1, config.keys.len());
+
+        let empty = Config::parse("")?;
+        assert_eq!(None, empty.page_size);
+        assert!(empty.keys.is_empty());
+
+        // errors point at the offending key
+        let err = |text: &str| format!("{:#}", Config::parse(text).unwrap_err());
+        let msg = err("[theme]\nfocus = \"grean\"");
+        assert!(
+            msg.contains("line 2") && msg.contains("unknown color `grean`"),
+            "{msg}"
+        );
+        let msg = err("[keys.table]\nsort = [\"ctl-o\"]");
+        assert!(
+            msg.contains("line 2") && msg.contains("unknown modifier `ctl`"),
+            "{msg}"
+        );
+        let msg = err("[keys.table]\nsrot = [\"o\"]");
+        assert!(
+            msg.contains("line 2") && msg.contains("unknown action `srot`"),
+            "{msg}"
+        );
+        let msg = err("[keys.search]\nquit = [\"q\"]");
+        assert!(msg.contains("line 1") && msg.contains("search"), "{msg}");
+        let msg = err("pagesize = 5");
+        assert!(msg.contains("unknown field `pagesize`"), "{msg}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_key() -> Result<()> {
+        let key = |code, modifiers| Key(KeyEvent::new(code, modifiers));
+        for (s, expected, display) in [
+            ("j", key(KeyCode::Char('j'), KeyModifiers::NONE), "j"),
+            ("J", key(KeyCode::Char('J'), KeyModifiers::NONE), "J"),
+            ("-", key(KeyCode::Char('-'), KeyModifiers::NONE), "-"),
+            (
+                "ctrl-f",
+                key(KeyCode::Char('f'), KeyModifiers::CONTROL),
+                "C-f",
+            ),
+            ("C--", key(KeyCode::Char('-'), KeyModifiers::CONTROL), "C--"),
+            (
+                "PageDown",
+                key(KeyCode::PageDown, KeyModifiers::NONE),
+                "PgDn",
+            ),
+            ("alt-f5", key(KeyCode::F(5), KeyModifiers::ALT), "M-F5"),
+        ] {
+            let parsed: Key = s.parse()?;
+            assert_eq!(expected, parsed, "{s}");
+            assert_eq!(display, parsed.to_string());
+        }
+        assert!("f13".parse::<Key>().is_err());
+        assert!("nope".parse::<Key>().is_err());
+        Ok(())
+    }
+}
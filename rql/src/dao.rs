@@ -1,8 +1,13 @@
-use anyhow::{Context, Result};
-use sqlx::{sqlite::SqliteRow, Column, Pool, Row, Sqlite, SqlitePool};
-use std::{fmt::Display, ops::Deref, sync::Arc, time::Instant};
+use crate::{
+    backend::{self, Backend},
+    filter::Dialect,
+};
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveTime, SecondsFormat};
+use chrono_tz::Tz;
+use std::{fmt::Display, sync::Arc};
 use tokio::runtime::Runtime;
-use tracing::{debug, info, warn};
+use tracing::debug;
 
 #[derive(Clone)]
 pub struct BlockingDao {
@@ -15,20 +20,29 @@ struct BlockingInner {
 }
 
 impl BlockingDao {
-    pub fn new(db: DbType) -> Result<Self> {
+    /// Connects to the db. Timestamps are displayed in the provided timezone.
+    pub fn new(db: DbType, tz: Tz) -> Result<Self> {
         let rt = tokio::runtime::Builder::new_multi_thread()
             .enable_all()
             .build()?;
-        let dao = rt.block_on(Dao::new(db))?;
+        let dao = rt.block_on(Dao::new(db))?.timezone(tz);
         let inner = BlockingInner { dao, rt };
         let inner = inner.into();
         Ok(Self { inner })
     }
 
-    pub fn tables(&self) -> Result<Vec<String>> {
+    pub fn tables(&self) -> Result<Vec<SchemaObject>> {
         self.inner.rt.block_on(self.inner.dao.tables())
     }
 
+    pub fn table_details<P: AsRef<str>>(&self, name: P) -> Result<TableDetails> {
+        self.inner.rt.block_on(self.inner.dao.table_details(name))
+    }
+
+    pub fn foreign_keys<P: AsRef<str>>(&self, name: P) -> Result<Vec<ForeignKeySpec>> {
+        self.inner.rt.block_on(self.inner.dao.foreign_keys(name))
+    }
+
     pub fn table_schema<P: AsRef<str>>(&self, table_name: P) -> Result<TableSchema> {
         self.inner
             .rt
@@ -46,11 +60,32 @@ impl BlockingDao {
     pub fn max_lens(&self, schema: &TableSchema, req: MaxLens) -> Result<Vec<usize>> {
         self.inner.rt.block_on(self.inner.dao.max_lens(schema, req))
     }
+
+    pub fn query_schema(&self, sql: &str) -> Result<TableSchema> {
+        self.inner.rt.block_on(self.inner.dao.query_schema(sql))
+    }
+
+    pub fn query_count(&self, sql: &str) -> Result<u64> {
+        self.inner.rt.block_on(self.inner.dao.query_count(sql))
+    }
+
+    pub fn query(&self, schema: &TableSchema, req: Query) -> Result<Vec<Record>> {
+        self.inner.rt.block_on(self.inner.dao.query(schema, req))
+    }
+
+    pub fn apply(&self, changes: &[Change]) -> Result<()> {
+        self.inner.rt.block_on(self.inner.dao.apply(changes))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn execute(&self, sql: &'static str) -> Result<()> {
+        self.inner.rt.block_on(self.inner.dao.execute(sql))
+    }
 }
 
-#[derive(Clone)]
 struct Dao {
-    pool: Pool<Sqlite>,
+    backend: Box<dyn Backend>,
+    tz: Tz,
 }
 
 #[derive(Debug, Clone)]
@@ -59,10 +94,19 @@ pub struct TableSchema {
     pub cols: Vec<TableColumn>,
 }
 
+impl TableSchema {
+    /// Whether rows can be addressed by their rowid. Views and query results
+    /// do not have one.
+    pub fn has_rowid(&self) -> bool {
+        self.cols.first() == Some(&TableColumn::RowId)
+    }
+}
+
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub enum TableColumn {
     RowId,
     Spec(TableColumnSpec),
+    Result(ResultColumn),
 }
 
 impl TableColumn {
@@ -70,26 +114,109 @@ impl TableColumn {
         match self {
             TableColumn::RowId => "rowid",
             TableColumn::Spec(spec) => &spec.name,
+            TableColumn::Result(col) => &col.name,
         }
     }
     pub fn field_type(&self) -> FieldType {
         match self {
             TableColumn::RowId => FieldType::RowId,
             TableColumn::Spec(spec) => FieldType::from(spec.typ.as_ref()),
+            // decoded by the type of the value
+            TableColumn::Result(_) => FieldType::Null,
         }
     }
 }
 
+/// A column in the result set of an ad-hoc query. Query results are not
+/// tied to a table so the values are decoded by their storage class.
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
+pub struct ResultColumn {
+    pub name: String,
+    pub ordinal: usize,
+}
+
 #[derive(sqlx::FromRow, Hash, PartialEq, Eq, Clone, Debug)]
-#[allow(dead_code)]
 pub struct TableColumnSpec {
     pub name: String,
     #[sqlx(rename = "type")]
-    typ: String,
-    cid: u32,
-    notnull: bool,
-    dflt_value: String,
-    pk: bool,
+    pub typ: String,
+    pub cid: u32,
+    pub notnull: bool,
+    pub dflt_value: Option<String>,
+    pub pk: bool,
+}
+
+/// A table, view, or trigger in the db
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SchemaObject {
+    pub name: String,
+    pub kind: ObjectKind,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ObjectKind {
+    Table,
+    View,
+    Trigger,
+}
+
+impl SchemaObject {
+    /// Whether the object has records that can be browsed
+    pub fn has_records(&self) -> bool {
+        self.kind != ObjectKind::Trigger
+    }
+
+    pub fn label(&self) -> String {
+        match self.kind {
+            ObjectKind::Table => self.name.clone(),
+            ObjectKind::View => format!("{} (view)", self.name),
+            ObjectKind::Trigger => format!("{} (trigger)", self.name),
+        }
+    }
+}
+
+impl From<&str> for ObjectKind {
+    fn from(value: &str) -> Self {
+        match value {
+            "view" => ObjectKind::View,
+            "trigger" => ObjectKind::Trigger,
+            _ => ObjectKind::Table,
+        }
+    }
+}
+
+/// Everything known about a table's definition
+#[derive(Debug, Clone)]
+pub struct TableDetails {
+    pub object: SchemaObject,
+    pub cols: Vec<TableColumnSpec>,
+    pub indexes: Vec<IndexSpec>,
+    pub foreign_keys: Vec<ForeignKeySpec>,
+    pub sql: Option<String>,
+}
+
+#[derive(sqlx::FromRow, PartialEq, Eq, Clone, Debug)]
+pub struct IndexSpec {
+    pub name: String,
+    pub unique: bool,
+    /// c for create index, u for unique constraints, pk for primary keys
+    pub origin: String,
+    pub partial: bool,
+    #[sqlx(skip)]
+    pub cols: Vec<String>,
+}
+
+#[derive(sqlx::FromRow, PartialEq, Eq, Clone, Debug)]
+pub struct ForeignKeySpec {
+    pub id: i64,
+    pub seq: i64,
+    /// the referenced table
+    pub table: String,
+    pub from: String,
+    /// None if the referenced table's primary key is used
+    pub to: Option<String>,
+    pub on_update: String,
+    pub on_delete: String,
 }
 
 /// A row in the table
@@ -98,6 +225,15 @@ pub struct Record {
     pub fields: Vec<Field>,
 }
 
+impl Record {
+    pub fn rowid(&self) -> Option<i64> {
+        self.fields.iter().find_map(|f| match f.val {
+            FieldValue::RowID(id) => Some(id),
+            _ => None,
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Field {
     pub name: String,
@@ -115,17 +251,34 @@ pub enum FieldValue {
     Integer(Option<i64>),
     Numeric(Option<f64>),
     Boolean(Option<bool>),
-    Date(Option<Instant>),
-    Time(Option<Instant>),
-    DateTime(Option<Instant>),
+    Date(Option<NaiveDate>),
+    Time(Option<NaiveTime>),
+    DateTime(Option<DateTime<Tz>>),
 }
 
 impl FieldValue {
+    pub fn is_null(&self) -> bool {
+        use FieldValue::*;
+        matches!(
+            self,
+            Null | Text(None)
+                | Real(None)
+                | Blob(None)
+                | Integer(None)
+                | Numeric(None)
+                | Boolean(None)
+                | Date(None)
+                | Time(None)
+                | DateTime(None)
+        )
+    }
+
     pub fn len(&self) -> usize {
         use FieldValue::*;
         match self {
             RowID(val) => count_digits(*val),
             Text(Some(s)) => s.len(),
+            Date(Some(_)) | Time(Some(_)) | DateTime(Some(_)) => self.to_string().len(),
             Null => 4,
             _ => 10,
         }
@@ -153,9 +306,9 @@ impl Display for FieldValue {
             Integer(Some(val)) => write!(f, "{val}"),
             Numeric(Some(val)) => write!(f, "{val}"),
             Boolean(Some(val)) => write!(f, "{val}"),
-            Date(Some(val)) => write!(f, "{val:?}"),
-            Time(Some(val)) => write!(f, "{val:?}"),
-            DateTime(Some(val)) => write!(f, "{val:?}"),
+            Date(Some(val)) => write!(f, "{}", val.format("%Y-%m-%d")),
+            Time(Some(val)) => write!(f, "{}", val.format("%H:%M:%S")),
+            DateTime(Some(val)) => write!(f, "{}", val.to_rfc3339_opts(SecondsFormat::Secs, false)),
             _ => Ok(()),
         }
     }
@@ -177,46 +330,15 @@ pub enum FieldType {
 }
 
 impl FieldType {
-    fn decode(&self, row: &SqliteRow, idx: usize) -> Result<FieldValue> {
-        let val = match self {
-            FieldType::Null => FieldValue::Null,
-            FieldType::RowId => FieldValue::RowID(self.decode_i64(row, idx)?.unwrap()),
-            FieldType::Text => FieldValue::Text(self.decode_string(row, idx)?),
-            FieldType::Real => FieldValue::Real(self.decode_f64(row, idx)?),
-            FieldType::Blob => FieldValue::Blob(self.decode_bytes(row, idx)?),
-            FieldType::Integer => FieldValue::Integer(self.decode_i64(row, idx)?),
-            FieldType::Numeric => FieldValue::Numeric(self.decode_f64(row, idx)?),
-            FieldType::Boolean => FieldValue::Boolean(self.decode_bool(row, idx)?),
-            FieldType::Date => FieldValue::Date(self.decode_instant(row, idx)?),
-            FieldType::Time => FieldValue::Time(self.decode_instant(row, idx)?),
-            FieldType::DateTime => FieldValue::DateTime(self.decode_instant(row, idx)?),
-        };
-        Ok(val)
-    }
-
-    fn decode_instant(&self, row: &SqliteRow, idx: usize) -> Result<Option<Instant>> {
-        warn!("decode instant not implemented yet");
-        Ok(None)
-    }
-
-    fn decode_bool(&self, row: &SqliteRow, idx: usize) -> Result<Option<bool>> {
-        Ok(row.try_get::<Option<bool>, _>(idx)?)
-    }
-
-    fn decode_i64(&self, row: &SqliteRow, idx: usize) -> Result<Option<i64>> {
-        Ok(row.try_get::<Option<i64>, _>(idx)?)
-    }
-
-    fn decode_bytes(&self, row: &SqliteRow, idx: usize) -> Result<Option<Vec<u8>>> {
-        Ok(row.try_get::<Option<Vec<u8>>, _>(idx)?)
-    }
-
-    fn decode_f64(&self, row: &SqliteRow, idx: usize) -> Result<Option<f64>> {
-        Ok(row.try_get::<Option<f64>, _>(idx)?)
-    }
-
-    fn decode_string(&self, row: &SqliteRow, idx: usize) -> Result<Option<String>> {
-        Ok(row.try_get::<Option<String>, _>(idx)?)
+    /// The width of the type's values when displayed, if it does not depend
+    /// on the value.
+    pub fn display_len(&self) -> Option<usize> {
+        match self {
+            FieldType::Date => Some("2006-01-02".len()),
+            FieldType::Time => Some("15:04:05".len()),
+            FieldType::DateTime => Some("2006-01-02T15:04:05+07:00".len()),
+            _ => None,
+        }
     }
 }
 
@@ -228,20 +350,36 @@ impl From<&str> for FieldType {
             return FieldType::Text;
         }
         match value {
-            "string" | "text" | "timestamp" => FieldType::Text,
+            // columns of views are often declared without a type
+            "" => FieldType::Null,
+            "string" | "text" => FieldType::Text,
             "int" | "integer" | "bigint" | "uint64" | "numeric" => FieldType::Integer,
             "float" => FieldType::Real,
             "blob" => FieldType::Blob,
             "boolean" | "bool" => FieldType::Boolean,
-            "datetime" => FieldType::DateTime,
+            "datetime" | "timestamp" => FieldType::DateTime,
             "date" => FieldType::Date,
             "time" => FieldType::Time,
-            _ => panic!("unknown type: {}", value),
+            // the names postgres and mysql use
+            "smallint" | "tinyint" | "mediumint" => FieldType::Integer,
+            "character varying" | "character" | "varchar" | "char" => FieldType::Text,
+            "real" | "double precision" | "double" => FieldType::Real,
+            "bytea" => FieldType::Blob,
+            "timestamp with time zone" | "timestamp without time zone" => FieldType::DateTime,
+            "time without time zone" => FieldType::Time,
+            _ => {
+                debug!(
+                    typ = value,
+                    "decoding values of unknown type by their storage class"
+                );
+                FieldType::Null
+            }
         }
     }
 }
 
 pub enum DbType<'a> {
+    /// A sqlite file, or a postgres:// or mysql:// url
     Path(&'a str),
     Memory,
 }
@@ -252,6 +390,7 @@ pub struct GetRecords {
     pub limit: Option<usize>,
     pub offset: Option<usize>,
     pub query: Option<String>,
+    pub sort: Option<Sort>,
 }
 
 impl GetRecords {
@@ -277,6 +416,38 @@ impl GetRecords {
         self.query = query.to_owned();
         self
     }
+    pub fn maybe_sort(mut self, sort: &Option<Sort>) -> Self {
+        self.sort = sort.to_owned();
+        self
+    }
+}
+
+/// Orders records by a single column
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Sort {
+    pub column: String,
+    pub desc: bool,
+}
+
+impl Sort {
+    pub fn asc<S: Into<String>>(column: S) -> Self {
+        Self {
+            column: column.into(),
+            desc: false,
+        }
+    }
+
+    pub fn desc<S: Into<String>>(column: S) -> Self {
+        Self {
+            column: column.into(),
+            desc: true,
+        }
+    }
+
+    pub(crate) fn to_sql(&self, dialect: Dialect) -> String {
+        let dir = if self.desc { "desc" } else { "asc" };
+        format!("{} {}", dialect.quote(&self.column), dir)
+    }
 }
 
 #[derive(Default, Debug)]
@@ -325,201 +496,177 @@ impl MaxLens {
     }
 }
 
-impl<'a> DbType<'a> {
-    async fn connect(&'a self) -> Result<Pool<Sqlite>> {
+#[derive(Default, Debug)]
+pub struct Query {
+    pub sql: String,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    pub sort: Option<Sort>,
+}
+
+impl Query {
+    pub fn new<S: Into<String>>(sql: S) -> Self {
+        Self {
+            sql: sql.into(),
+            ..Default::default()
+        }
+    }
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+    pub fn maybe_sort(mut self, sort: &Option<Sort>) -> Self {
+        self.sort = sort.to_owned();
+        self
+    }
+}
+
+/// A pending write to a table. Values are bound as text and converted by the
+/// column's affinity, None is written as NULL.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Change {
+    Update {
+        table: String,
+        rowid: i64,
+        column: String,
+        old: FieldValue,
+        new: Option<String>,
+    },
+    Insert {
+        table: String,
+        values: Vec<(String, Option<String>)>,
+    },
+    Delete {
+        table: String,
+        rowid: i64,
+        record: Record,
+    },
+}
+
+// renders the change as a line in a diff
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let val = |v: &Option<String>| match v {
+            Some(v) => format!("'{v}'"),
+            None => "NULL".to_string(),
+        };
+        let field = |v: &FieldValue| {
+            if v.is_null() {
+                "NULL".to_string()
+            } else {
+                format!("'{v}'")
+            }
+        };
         match self {
-            Self::Path(path) => {
-                let path = path.as_ref();
-                SqlitePool::connect(path)
-                    .await
-                    .context(format!(r#"could not open "{path}""#))
+            Change::Update {
+                table,
+                rowid,
+                column,
+                old,
+                new,
+            } => write!(
+                f,
+                "~ {table} rowid={rowid} {column}: {} -> {}",
+                field(old),
+                val(new)
+            ),
+            Change::Insert { table, values } => {
+                let values = values
+                    .iter()
+                    .map(|(name, v)| format!("{name}={}", val(v)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "+ {table} ({values})")
+            }
+            Change::Delete {
+                table,
+                rowid,
+                record,
+            } => {
+                let values = record
+                    .fields
+                    .iter()
+                    .filter(|f| f.typ != FieldType::RowId)
+                    .map(|f| format!("{}={}", f.name, field(&f.val)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "- {table} rowid={rowid} ({values})")
             }
-            Self::Memory => SqlitePool::connect(":memory:")
-                .await
-                .context("could not connect to memory db"),
         }
     }
 }
 
 impl Dao {
     pub async fn new(db: DbType<'_>) -> Result<Self> {
-        let pool = db.connect().await?;
-        Ok(Self { pool })
+        let backend = backend::connect(&db).await?;
+        let tz = Tz::UTC;
+        Ok(Self { backend, tz })
     }
 
-    async fn tables(&self) -> Result<Vec<String>> {
-        #[derive(sqlx::FromRow)]
-        struct Record {
-            name: String,
-        }
-        let mut conn = self.pool.acquire().await?;
-        let res = sqlx::query_as::<_, Record>(
-            "select name from sqlite_schema where type='table' order by name",
-        )
-        .fetch_all(&mut *conn)
-        .await?
-        .into_iter()
-        .map(|s| s.name)
-        .collect();
-        Ok(res)
+    fn timezone(mut self, tz: Tz) -> Self {
+        self.tz = tz;
+        self
+    }
+
+    async fn tables(&self) -> Result<Vec<SchemaObject>> {
+        self.backend.tables().await
     }
 
     async fn table_schema<P: AsRef<str>>(&self, name: P) -> Result<TableSchema> {
-        let name = name.as_ref().to_string();
-        info!(name, "Getting table schema");
-        let mut conn = self.pool.acquire().await?;
-        let query = format!("pragma table_info({})", &name);
-        let mut cols = sqlx::query_as::<_, TableColumnSpec>(&query)
-            .fetch_all(&mut *conn)
-            .await?
-            .into_iter()
-            .map(|c| TableColumn::Spec(c))
-            .collect::<Vec<_>>();
-        cols.insert(0, TableColumn::RowId);
-        let schema = TableSchema { name, cols };
-        Ok(schema)
+        self.backend.table_schema(name.as_ref()).await
+    }
+
+    async fn foreign_keys<P: AsRef<str>>(&self, name: P) -> Result<Vec<ForeignKeySpec>> {
+        self.backend.foreign_keys(name.as_ref()).await
+    }
+
+    async fn table_details<P: AsRef<str>>(&self, name: P) -> Result<TableDetails> {
+        self.backend.table_details(name.as_ref()).await
     }
 
     async fn max_lens(&self, schema: &TableSchema, req: MaxLens) -> Result<Vec<usize>> {
-        let mut conn = self.pool.acquire().await?;
-        let query_parts = &schema
-            .cols
-            .iter()
-            .map(|c| format!("max(length({}))", c.name()))
-            .collect::<Vec<_>>()
-            .join(",");
-        let mut query = format!("select {} from {}", query_parts, schema.name);
-        let where_clause = req
-            .query
-            .as_ref()
-            .and_then(|q| build_where_clause(&schema, q));
-        if let Some(wher) = where_clause {
-            query.push_str(&format!(" WHERE {}", wher));
-        }
-        debug!("max lens query: {}", query);
-        let row = sqlx::query(&query).fetch_one(&mut *conn).await?;
-        let mut res = vec![];
-        for (idx, col) in schema.cols.iter().enumerate() {
-            let len = row.get::<i64, _>(idx);
-            res.push(len.try_into().unwrap_or_default());
-        }
-        Ok(res)
+        self.backend.max_lens(schema, req).await
     }
 
     async fn count(&self, req: Count) -> Result<u64> {
-        #[derive(sqlx::FromRow)]
-        struct Record {
-            count: i64,
-        }
-        let schema = self.table_schema(&req.table_name).await?;
-        let mut conn = self.pool.acquire().await?;
-        let mut query = format!("select count(*) as count from {}", &req.table_name);
-        let where_clause = req
-            .query
-            .as_ref()
-            .and_then(|q| build_where_clause(&schema, q));
-        if let Some(wher) = where_clause {
-            query.push_str(&format!(" WHERE {}", wher));
-        }
-
-        debug!("count query: {}", query);
-        let record = sqlx::query_as::<_, Record>(&query)
-            .fetch_one(&mut *conn)
-            .await?;
-        Ok(record.count as u64)
+        self.backend.count(req).await
     }
 
     async fn records(&self, schema: &TableSchema, req: GetRecords) -> Result<Vec<Record>> {
-        let table_name = req.table_name.as_str();
-        let schema = self.table_schema(table_name).await?;
-        let mut conn = self.pool.acquire().await?;
-        let limit = req.limit.map(|v| format!("limit {v}")).unwrap_or_default();
-        let offset = req
-            .offset
-            .map(|v| format!("offset {v}"))
-            .unwrap_or_default();
-        let where_clause = req
-            .query
-            .as_ref()
-            .and_then(|q| build_where_clause(&schema, q));
-
-        let query = if let Some(wher) = where_clause {
-            format!(
-                "select rowid, * from {} WHERE {} {} {}",
-                table_name, wher, limit, offset
-            )
-        } else {
-            format!("select rowid, * from {} {} {}", table_name, limit, offset)
-        };
-        debug!(query, "records query: {}", query);
-
-        let rows = sqlx::query(&query).fetch_all(&mut *conn).await?;
-        let mut records = vec![];
-        for row in rows {
-            let mut record = Record::default();
-            for column in row.columns() {
-                let name = column.name().to_string();
-                let ord = column.ordinal();
-                let col = &schema.cols[ord];
-                let typ = col.field_type();
-                let val = typ.decode(&row, ord)?;
-                let field = Field { name, typ, val };
-                record.fields.push(field);
-            }
-            records.push(record);
-        }
-        Ok(records)
+        self.backend.records(schema, req, &self.tz).await
     }
 
-    #[cfg(test)]
-    async fn execute(&self, sql: &'static str) -> Result<()> {
-        let mut conn = self.pool.acquire().await?;
-        sqlx::query(sql).execute(&mut *conn).await?;
-        Ok(())
+    async fn query_schema(&self, sql: &str) -> Result<TableSchema> {
+        self.backend.query_schema(sql).await
     }
-}
 
-// returns a where clause against all string columns, OR'd,
-// with the provided query. None is returned if no columns
-// can be queried against.
-//
-// A better query would allow per-column constraints and be
-// SQL type aware.
-fn build_where_clause(schema: &TableSchema, query: &str) -> Option<String> {
-    let numeric = query.parse::<f64>().is_ok();
-    let constraints: Vec<String> = schema
-        .cols
-        .iter()
-        .filter_map(|c| match c {
-            TableColumn::RowId if numeric => Some(format!("rowid = {}", query)),
-            TableColumn::Spec(spec)
-                if spec.typ.to_lowercase() == "text" || spec.typ.to_lowercase() == "string" =>
-            {
-                Some(format!("{} LIKE '%{}%'", spec.name, query))
-            }
-            TableColumn::Spec(spec)
-                if numeric
-                    && (spec.typ.to_lowercase() == "numeric"
-                        || spec.typ.to_lowercase() == "float"
-                        || spec.typ.to_lowercase() == "double"
-                        || spec.typ.to_lowercase() == "integer") =>
-            {
-                Some(format!("{} = {}", spec.name, query))
-            }
-            _ => return None,
-        })
-        .collect();
+    async fn query_count(&self, sql: &str) -> Result<u64> {
+        self.backend.query_count(sql).await
+    }
+
+    async fn query(&self, schema: &TableSchema, req: Query) -> Result<Vec<Record>> {
+        self.backend.query(schema, req, &self.tz).await
+    }
 
-    if constraints.len() > 0 {
-        Some(constraints.join(" OR "))
-    } else {
-        None
+    // applies all of the changes in a single transaction
+    async fn apply(&self, changes: &[Change]) -> Result<()> {
+        self.backend.apply(changes).await
+    }
+
+    #[cfg(test)]
+    async fn execute(&self, sql: &'static str) -> Result<()> {
+        self.backend.execute(sql).await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{backend::build_where_clause, filter::Arg};
     use tracing_test::traced_test;
 
     #[tokio::test]
@@ -562,25 +709,233 @@ mod tests {
             .await?;
         let schema = dao.table_schema("foo").await?;
 
-        let clause = build_where_clause(&schema, "collin");
-        assert_eq!(Some("name LIKE '%collin%'"), clause.as_deref());
+        let clause = build_where_clause(&schema, Some("collin"), Dialect::Sqlite)?.unwrap();
+        assert_eq!(r#"("name" LIKE ? ESCAPE '\')"#, clause.sql);
+        assert_eq!(vec![Arg::Text("%collin%".to_string())], clause.args);
 
-        let clause = build_where_clause(&schema, "1").unwrap();
-        assert!(clause.contains("name LIKE '%1%'"));
-        assert!(clause.contains("rowid = 1"));
-        assert!(clause.contains("age = 1"));
-        assert_eq!(3, clause.split(" OR ").collect::<Vec<_>>().len());
+        let clause = build_where_clause(&schema, Some("1"), Dialect::Sqlite)?.unwrap();
+        assert!(clause.sql.contains(r#""name" LIKE ?"#));
+        assert!(clause.sql.contains(r#""rowid" = ?"#));
+        assert!(clause.sql.contains(r#""age" = ?"#));
+        assert_eq!(3, clause.sql.split(" OR ").collect::<Vec<_>>().len());
+        assert_eq!(3, clause.args.len());
+
+        // terms are AND'd and bound by column type
+        let clause =
+            build_where_clause(&schema, Some("name:o'brien -age>=30"), Dialect::Sqlite)?.unwrap();
+        assert_eq!(
+            r#""name" LIKE ? ESCAPE '\' AND NOT coalesce("age" >= ?, 0)"#,
+            clause.sql
+        );
+        assert_eq!(
+            vec![Arg::Text("%o'brien%".to_string()), Arg::Integer(30)],
+            clause.args
+        );
 
-        let clause = build_where_clause(&schema, "1").unwrap();
+        assert!(build_where_clause(&schema, None, Dialect::Sqlite)?.is_none());
+        assert!(build_where_clause(&schema, Some("  "), Dialect::Sqlite)?.is_none());
+        assert!(build_where_clause(&schema, Some("nope:1"), Dialect::Sqlite).is_err());
+        assert!(build_where_clause(&schema, Some("age:old"), Dialect::Sqlite).is_err());
 
-        // no where clause
+        // nothing can match text in a table without text columns
         let dao = Dao::new(DbType::Memory).await?;
         dao.execute("create table foo (buzz INTEGER)").await?;
         let schema = dao.table_schema("foo").await?;
 
-        let clause = build_where_clause(&schema, "text search");
-        assert!(clause.is_none());
+        let clause = build_where_clause(&schema, Some("text search"), Dialect::Sqlite)?.unwrap();
+        assert_eq!("0 AND 0", clause.sql);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_query() -> Result<()> {
+        let dao = Dao::new(DbType::Memory).await?;
+        dao.execute("create table foo (name TEXT, age INTEGER)")
+            .await?;
+        dao.execute("create table bar (foo_id INTEGER, note TEXT)")
+            .await?;
+
+        // columns come from the statement even without any rows
+        let sql =
+            "select foo.name, bar.note, 1.5 as n from foo join bar on bar.foo_id = foo.rowid;";
+        let schema = dao.query_schema(sql).await?;
+        assert_eq!(
+            schema.cols.iter().map(|c| c.name()).collect::<Vec<_>>(),
+            vec!["name", "note", "n"]
+        );
+        assert_eq!(0, dao.query_count(sql).await?);
+
+        dao.execute("insert into foo (name, age) values ('collin', 46), ('other', 12)")
+            .await?;
+        dao.execute("insert into bar (foo_id, note) values (1, 'a'), (1, null), (2, 'c')")
+            .await?;
+        assert_eq!(3, dao.query_count(sql).await?);
+        let records = dao
+            .query(&schema, Query::new(sql).limit(1).offset(1))
+            .await?;
+        assert_eq!(1, records.len());
+        assert_eq!(
+            records[0]
+                .fields
+                .iter()
+                .map(|f| f.val.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                FieldValue::Text(Some("collin".to_string())),
+                FieldValue::Null,
+                FieldValue::Real(Some(1.5)),
+            ]
+        );
+
+        let sort = Some(Sort::desc("note"));
+        let records = dao
+            .query(&schema, Query::new(sql).limit(1).maybe_sort(&sort))
+            .await?;
+        assert_eq!(
+            FieldValue::Text(Some("c".to_string())),
+            records[0].fields[1].val
+        );
+
+        // bad statements are errors rather than panics
+        assert!(dao.query_schema("select nope from foo").await.is_err());
+        assert!(dao.query_schema("delete from foo").await.is_err());
+        assert!(dao.query_schema("  ; ").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_apply() -> Result<()> {
+        let dao = Dao::new(DbType::Memory).await?;
+        dao.execute("create table foo (name TEXT, age INTEGER default 1)")
+            .await?;
+        dao.execute("insert into foo (name, age) values ('collin', 46), ('other', 12)")
+            .await?;
+        let schema = dao.table_schema("foo").await?;
+        let records = dao.records(&schema, GetRecords::new("foo")).await?;
+        let values = |records: Vec<Record>| -> Vec<Vec<FieldValue>> {
+            records
+                .into_iter()
+                .map(|r| r.fields.into_iter().map(|f| f.val).collect())
+                .collect()
+        };
+
+        let changes = vec![
+            Change::Update {
+                table: "foo".to_string(),
+                rowid: 1,
+                column: "age".to_string(),
+                old: FieldValue::Integer(Some(46)),
+                new: Some("47".to_string()),
+            },
+            Change::Delete {
+                table: "foo".to_string(),
+                rowid: 2,
+                record: records[1].clone(),
+            },
+            Change::Insert {
+                table: "foo".to_string(),
+                values: vec![("name".to_string(), Some("o'brien".to_string()))],
+            },
+            Change::Insert {
+                table: "foo".to_string(),
+                values: vec![
+                    ("name".to_string(), None),
+                    ("age".to_string(), Some("3".to_string())),
+                ],
+            },
+        ];
+        assert_eq!(
+            changes.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+            vec![
+                "~ foo rowid=1 age: '46' -> '47'",
+                "- foo rowid=2 (name='other', age='12')",
+                "+ foo (name='o'brien')",
+                "+ foo (name=NULL, age='3')",
+            ]
+        );
+        dao.apply(&changes).await?;
+        let records = dao.records(&schema, GetRecords::new("foo")).await?;
+        assert_eq!(
+            values(records),
+            vec![
+                vec![
+                    FieldValue::RowID(1),
+                    FieldValue::Text(Some("collin".to_string())),
+                    FieldValue::Integer(Some(47)),
+                ],
+                vec![
+                    FieldValue::RowID(2),
+                    FieldValue::Text(Some("o'brien".to_string())),
+                    FieldValue::Integer(Some(1)),
+                ],
+                vec![
+                    FieldValue::RowID(3),
+                    FieldValue::Text(None),
+                    FieldValue::Integer(Some(3)),
+                ],
+            ]
+        );
+
+        // a failed change rolls back the ones before it
+        let changes = vec![
+            Change::Insert {
+                table: "foo".to_string(),
+                values: vec![],
+            },
+            Change::Delete {
+                table: "foo".to_string(),
+                rowid: 99,
+                record: Record::default(),
+            },
+        ];
+        assert!(dao.apply(&changes).await.is_err());
+        assert_eq!(3, dao.count(Count::new("foo")).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_decode_temporal() -> Result<()> {
+        let tz: Tz = "America/New_York".parse().unwrap();
+        let dao = Dao::new(DbType::Memory).await?.timezone(tz);
+        dao.execute("create table foo (at DATETIME, day DATE, time TIME)")
+            .await?;
+        dao.execute(
+            "insert into foo (at, day, time) values
+                ('2023-09-14 16:30:00', '2023-09-14', '16:30:00'),
+                (1694709000, 1694709000, 1694709000),
+                (2460202.1875, 2460202.1875, 2460202.1875),
+                ('2023-09-14T18:30:00.250+02:00', '2023-09-14T02:00Z', '16:30'),
+                (null, 'not a date', '2023-09-14')",
+        )
+        .await?;
+        let schema = dao.table_schema("foo").await?;
+        let records = dao.records(&schema, GetRecords::new("foo")).await?;
+        let values = records
+            .iter()
+            .map(|r| r.fields.iter().skip(1).map(|f| f.val.to_string()).collect())
+            .collect::<Vec<Vec<_>>>();
+        let same = vec!["2023-09-14T12:30:00-04:00", "2023-09-14", "12:30:00"];
+        assert_eq!(
+            values[0],
+            vec!["2023-09-14T12:30:00-04:00", "2023-09-14", "16:30:00"]
+        );
+        assert_eq!(values[1], same);
+        assert_eq!(values[2], same);
+        assert_eq!(
+            values[3],
+            vec!["2023-09-14T12:30:00-04:00", "2023-09-13", "16:30:00"]
+        );
+        assert_eq!(values[4], vec!["", "not a date", "2023-09-14"]);
 
+        // widths match what is displayed
+        let lens = dao.max_lens(&schema, MaxLens::new("foo")).await?;
+        assert_eq!(&lens[1..], &[25, 10, 8]);
+        assert_eq!(25, records[0].fields[1].val.len());
+        assert!(records[4].fields[1].val.is_null());
         Ok(())
     }
 
@@ -620,4 +975,163 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_quoted_names() -> Result<()> {
+        let dao = Dao::new(DbType::Memory).await?;
+        dao.execute(r#"create table "my table" ("order" TEXT, "group" INTEGER)"#)
+            .await?;
+        dao.execute(r#"insert into "my table" values ('first', 1), ('second', 22)"#)
+            .await?;
+        let schema = dao.table_schema("my table").await?;
+
+        let records = dao
+            .records(&schema, GetRecords::new("my table").search("second"))
+            .await?;
+        assert_eq!(1, records.len());
+        assert_eq!(2, dao.count(Count::new("my table")).await?);
+        let lens = dao.max_lens(&schema, MaxLens::new("my table")).await?;
+        assert_eq!(vec![1, 6, 2], lens);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_filter_search() -> Result<()> {
+        let dao = Dao::new(DbType::Memory).await?;
+        dao.execute("create table foo (name TEXT, age INTEGER, status TEXT)")
+            .await?;
+        dao.execute(
+            "insert into foo (name, age, status) values
+                ('collin', 46, 'active'),
+                ('o''brien', 31, 'deleted'),
+                ('100%', 12, null)",
+        )
+        .await?;
+        let schema = dao.table_schema("foo").await?;
+        let names = |records: Vec<Record>| -> Vec<FieldValue> {
+            records
+                .into_iter()
+                .map(|r| r.fields[1].val.clone())
+                .collect()
+        };
+        let text = |s: &str| FieldValue::Text(Some(s.to_string()));
+
+        // quotes and like wildcards are searched for literally
+        let matches = dao
+            .records(&schema, GetRecords::new("foo").search("o'brien"))
+            .await?;
+        assert_eq!(vec![text("o'brien")], names(matches));
+        let matches = dao
+            .records(&schema, GetRecords::new("foo").search("name:%"))
+            .await?;
+        assert_eq!(vec![text("100%")], names(matches));
+
+        // negated terms include nulls
+        let query = "age>20 -status:deleted";
+        let matches = dao
+            .records(&schema, GetRecords::new("foo").search(query))
+            .await?;
+        assert_eq!(vec![text("collin")], names(matches));
+        let matches = dao
+            .records(&schema, GetRecords::new("foo").search("-status:deleted"))
+            .await?;
+        assert_eq!(vec![text("collin"), text("100%")], names(matches));
+
+        // count and max lens agree with the records
+        assert_eq!(1, dao.count(Count::new("foo").search(query)).await?);
+        let lens = dao
+            .max_lens(&schema, MaxLens::new("foo").search("name:o"))
+            .await?;
+        assert_eq!(7, lens[1]);
+        Ok(())
+    }
+    #[tokio::test]
+    async fn test_table_details() -> Result<()> {
+        let dao = Dao::new(DbType::Memory).await?;
+        dao.execute("create table owner (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE)")
+            .await?;
+        dao.execute(
+            "create table pet (
+                name TEXT DEFAULT 'rex',
+                owner_id INTEGER REFERENCES owner(id) ON DELETE CASCADE
+            )",
+        )
+        .await?;
+        dao.execute("create index pet_owner on pet (owner_id, name)")
+            .await?;
+        dao.execute("create view named as select name, owner_id + 1 as next from pet")
+            .await?;
+        dao.execute("create trigger pet_added after insert on pet begin select 1; end")
+            .await?;
+        dao.execute("insert into owner (id, name) values (1, 'collin')")
+            .await?;
+        dao.execute("insert into pet (owner_id) values (1)").await?;
+
+        let objects = dao.tables().await?;
+        assert_eq!(
+            vec![
+                ("owner", ObjectKind::Table),
+                ("pet", ObjectKind::Table),
+                ("named", ObjectKind::View),
+                ("pet_added", ObjectKind::Trigger),
+            ],
+            objects
+                .iter()
+                .map(|o| (o.name.as_str(), o.kind))
+                .collect::<Vec<_>>()
+        );
+
+        let details = dao.table_details("pet").await?;
+        assert_eq!(Some("'rex'".to_string()), details.cols[0].dflt_value);
+        assert_eq!(None, details.cols[1].dflt_value);
+        assert_eq!(1, details.indexes.len());
+        assert_eq!("pet_owner", details.indexes[0].name);
+        assert_eq!(vec!["owner_id", "name"], details.indexes[0].cols);
+        assert!(!details.indexes[0].unique);
+        assert_eq!(1, details.foreign_keys.len());
+        let key = &details.foreign_keys[0];
+        assert_eq!(
+            ("owner", "owner_id", Some("id"), "CASCADE"),
+            (
+                key.table.as_str(),
+                key.from.as_str(),
+                key.to.as_deref(),
+                key.on_delete.as_str()
+            )
+        );
+        assert!(details.sql.unwrap().starts_with("CREATE TABLE pet"));
+
+        let details = dao.table_details("owner").await?;
+        assert!(details.cols[0].pk && !details.cols[0].notnull);
+        assert!(details.cols[1].notnull);
+        assert!(details.indexes[0].unique);
+        assert_eq!("u", details.indexes[0].origin);
+
+        let details = dao.table_details("pet_added").await?;
+        assert!(details.cols.is_empty());
+        assert!(details.sql.unwrap().starts_with("CREATE TRIGGER"));
+        assert!(dao.table_details("nope").await.is_err());
+
+        // views have no rowid and their columns may not be typed
+        let schema = dao.table_schema("named").await?;
+        assert!(!schema.has_rowid());
+        let records = dao
+            .records(&schema, GetRecords::new("named").limit(10))
+            .await?;
+        assert_eq!(
+            vec![
+                FieldValue::Text(Some("rex".to_string())),
+                FieldValue::Integer(Some(2))
+            ],
+            records[0]
+                .fields
+                .iter()
+                .map(|f| f.val.clone())
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,220 @@
+use crate::prelude::*;
+use std::{io::Write, path::Path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Csv,
+    Jsonl,
+    #[value(alias = "md")]
+    Markdown,
+}
+
+impl Format {
+    /// Picks the format from a file extension
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "csv" => Some(Format::Csv),
+            "jsonl" | "ndjson" => Some(Format::Jsonl),
+            "md" | "markdown" => Some(Format::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the records of a table, or the current view of it, in batches so
+/// that the whole table is never held in memory.
+pub struct Exporter {
+    format: Format,
+    batch_size: usize,
+}
+
+impl Exporter {
+    pub fn new(format: Format) -> Self {
+        Self {
+            format,
+            batch_size: 1000,
+        }
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Returns the number of records written
+    pub fn export<W: Write>(&self, table: &DbTable, mut w: W) -> Result<usize> {
+        let names = table
+            .schema
+            .cols
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect::<Vec<_>>();
+        self.write_header(&names, &mut w)?;
+        let mut count = 0;
+        loop {
+            let records = table.fetch(count, self.batch_size)?;
+            for record in &records {
+                self.write_record(&names, record, &mut w)?;
+            }
+            count += records.len();
+            debug!(count, "Exported batch of {} records", records.len());
+            if records.len() < self.batch_size {
+                break;
+            }
+        }
+        w.flush()?;
+        Ok(count)
+    }
+
+    fn write_header<W: Write>(&self, names: &[String], w: &mut W) -> Result<()> {
+        match self.format {
+            Format::Csv => {
+                let names = names.iter().map(|n| csv_escape(n)).collect::<Vec<_>>();
+                writeln!(w, "{}", names.join(","))?;
+            }
+            Format::Jsonl => {}
+            Format::Markdown => {
+                let names = names.iter().map(|n| md_escape(n)).collect::<Vec<_>>();
+                writeln!(w, "| {} |", names.join(" | "))?;
+                writeln!(w, "|{}", " --- |".repeat(names.len()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_record<W: Write>(&self, names: &[String], record: &Record, w: &mut W) -> Result<()> {
+        match self.format {
+            Format::Csv => {
+                let vals = record
+                    .fields
+                    .iter()
+                    .map(|f| text(&f.val).map(|v| csv_escape(&v)).unwrap_or_default())
+                    .collect::<Vec<_>>();
+                writeln!(w, "{}", vals.join(","))?;
+            }
+            Format::Jsonl => {
+                let vals = names
+                    .iter()
+                    .zip(&record.fields)
+                    .map(|(name, f)| {
+                        Ok(format!(
+                            "{}:{}",
+                            serde_json::to_string(name)?,
+                            json(&f.val)?
+                        ))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                writeln!(w, "{{{}}}", vals.join(","))?;
+            }
+            Format::Markdown => {
+                let vals = record
+                    .fields
+                    .iter()
+                    .map(|f| text(&f.val).map(|v| md_escape(&v)).unwrap_or_default())
+                    .collect::<Vec<_>>();
+                writeln!(w, "| {} |", vals.join(" | "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// the value as it is displayed, None for nulls. blobs are written as hex.
+fn text(val: &FieldValue) -> Option<String> {
+    match val {
+        _ if val.is_null() => None,
+        FieldValue::Blob(Some(bytes)) => Some(bytes.iter().map(|b| format!("{b:02x}")).collect()),
+        val => Some(val.to_string()),
+    }
+}
+
+fn json(val: &FieldValue) -> Result<String> {
+    use FieldValue::*;
+    let res = match val {
+        RowID(v) | Integer(Some(v)) => serde_json::to_string(v)?,
+        Real(Some(v)) | Numeric(Some(v)) => serde_json::to_string(v)?,
+        Boolean(Some(v)) => serde_json::to_string(v)?,
+        val => serde_json::to_string(&text(val))?,
+    };
+    Ok(res)
+}
+
+fn csv_escape(val: &str) -> String {
+    if val.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", val.replace('"', "\"\""))
+    } else {
+        val.to_string()
+    }
+}
+
+fn md_escape(val: &str) -> String {
+    val.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export() -> Result<()> {
+        let dao = BlockingDao::new(DbType::Memory, Tz::UTC)?;
+        dao.execute("create table foo (name TEXT, age INTEGER, score FLOAT, data BLOB)")?;
+        dao.execute(
+            "insert into foo (name, age, score, data) values
+                ('collin', 46, 1.5, x'0aff'),
+                ('a, \"b\"|c', null, null, null),
+                ('line
+break', 3, 0, null)",
+        )?;
+        let export = |format: Format, search: Option<&str>| -> Result<String> {
+            let table = DbTable::new(dao.clone(), "foo".to_string(), search.map(String::from))?;
+            let mut buf = vec![];
+            // a small batch size spans several fetches
+            let count = Exporter::new(format)
+                .batch_size(2)
+                .export(&table, &mut buf)?;
+            assert_eq!(count as u64, table.count);
+            Ok(String::from_utf8(buf)?)
+        };
+
+        assert_eq!(
+            export(Format::Csv, None)?,
+            "rowid,name,age,score,data
+1,collin,46,1.5,0aff
+2,\"a, \"\"b\"\"|c\",,,
+3,\"line
+break\",3,0,
+"
+        );
+        assert_eq!(
+            export(Format::Jsonl, Some("age>10"))?,
+            r#"{"rowid":1,"name":"collin","age":46,"score":1.5,"data":"0aff"}
+"#
+        );
+        assert_eq!(
+            export(Format::Markdown, Some("-name:collin"))?,
+            r#"| rowid | name | age | score | data |
+| --- | --- | --- | --- | --- |
+| 2 | a, "b"\|c |  |  |  |
+| 3 | line<br>break | 3 | 0 |  |
+"#
+        );
+        assert_eq!(
+            export(Format::Csv, Some("nomatch"))?,
+            "rowid,name,age,score,data\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Some(Format::Csv), Format::from_path("foo.CSV"));
+        assert_eq!(Some(Format::Jsonl), Format::from_path("/tmp/foo.jsonl"));
+        assert_eq!(Some(Format::Markdown), Format::from_path("foo.md"));
+        assert_eq!(None, Format::from_path("foo"));
+    }
+}
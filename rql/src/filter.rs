@@ -0,0 +1,516 @@
+use crate::dao::{TableColumn, TableSchema};
+use anyhow::{bail, Result};
+use sqlx::{database::HasArguments, Arguments, Database, Encode, Type};
+
+/// A parsed search query.
+///
+/// Terms are separated by whitespace and must all match:
+///
+///   foo            any text column contains foo (or a number column equals it)
+///   name:foo       the name column contains foo (equals it for number columns)
+///   age>30         comparisons: = != > >= < <=
+///   -status:gone   negates a term
+///   name:"a b"     values may be double quoted to include spaces
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Filter {
+    terms: Vec<Term>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Term {
+    negate: bool,
+    kind: TermKind,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum TermKind {
+    Any(String),
+    Column { name: String, op: Op, value: String },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Op {
+    Contains,
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl Op {
+    // longer operators first so that >= is not read as >
+    const ALL: [(&'static str, Op); 7] = [
+        (">=", Op::Ge),
+        ("<=", Op::Le),
+        ("!=", Op::Ne),
+        (":", Op::Contains),
+        ("=", Op::Eq),
+        (">", Op::Gt),
+        ("<", Op::Lt),
+    ];
+
+    fn sql(&self) -> &'static str {
+        match self {
+            Op::Contains | Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+        }
+    }
+}
+
+/// A compiled where clause and the values bound to its placeholders
+#[derive(Debug, PartialEq, Clone)]
+pub struct Where {
+    pub sql: String,
+    pub args: Vec<Arg>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Arg {
+    Text(String),
+    Integer(i64),
+    Real(f64),
+    Bool(bool),
+}
+
+impl Where {
+    /// The arguments for an optional clause, empty if there is none
+    pub fn arguments_of<'q, DB>(wher: &Option<Where>) -> <DB as HasArguments<'q>>::Arguments
+    where
+        DB: Database,
+        String: Encode<'q, DB> + Type<DB>,
+        i64: Encode<'q, DB> + Type<DB>,
+        f64: Encode<'q, DB> + Type<DB>,
+        bool: Encode<'q, DB> + Type<DB>,
+    {
+        wher.as_ref()
+            .map(|w| w.arguments::<DB>())
+            .unwrap_or_default()
+    }
+
+    pub fn arguments<'q, DB>(&self) -> <DB as HasArguments<'q>>::Arguments
+    where
+        DB: Database,
+        String: Encode<'q, DB> + Type<DB>,
+        i64: Encode<'q, DB> + Type<DB>,
+        f64: Encode<'q, DB> + Type<DB>,
+        bool: Encode<'q, DB> + Type<DB>,
+    {
+        let mut args = <DB as HasArguments<'q>>::Arguments::default();
+        for arg in &self.args {
+            match arg {
+                Arg::Text(v) => args.add(v.clone()),
+                Arg::Integer(v) => args.add(*v),
+                Arg::Real(v) => args.add(*v),
+                Arg::Bool(v) => args.add(*v),
+            }
+        }
+        args
+    }
+}
+
+/// The flavor of sql a backend speaks
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Dialect {
+    Sqlite,
+    Postgres,
+    Mysql,
+}
+
+impl Dialect {
+    pub fn quote(&self, name: &str) -> String {
+        match self {
+            Dialect::Mysql => format!("`{}`", name.replace('`', "``")),
+            Dialect::Sqlite | Dialect::Postgres => quote_ident(name),
+        }
+    }
+
+    // the placeholder of the nth (from 1) bound argument
+    fn placeholder(&self, n: usize) -> String {
+        match self {
+            Dialect::Postgres => format!("${n}"),
+            Dialect::Sqlite | Dialect::Mysql => "?".to_string(),
+        }
+    }
+
+    // a case insensitive match against an escaped like pattern
+    fn like(&self, ident: &str, placeholder: &str) -> String {
+        match self {
+            Dialect::Sqlite => format!("{ident} LIKE {placeholder} ESCAPE '\\'"),
+            Dialect::Postgres => format!("{} ILIKE {placeholder} ESCAPE '\\'", self.text(ident)),
+            // backslashes are escapes in mysql string literals
+            Dialect::Mysql => format!("{ident} LIKE {placeholder} ESCAPE '\\\\'"),
+        }
+    }
+
+    // the column as text, for comparing columns of types that text values
+    // cannot be compared with directly
+    fn text(&self, ident: &str) -> String {
+        match self {
+            Dialect::Postgres => format!("CAST({ident} AS text)"),
+            Dialect::Sqlite | Dialect::Mysql => ident.to_string(),
+        }
+    }
+
+    fn false_literal(&self) -> &'static str {
+        match self {
+            Dialect::Postgres => "FALSE",
+            Dialect::Sqlite | Dialect::Mysql => "0",
+        }
+    }
+
+    /// The number of characters in the column's values as text
+    pub fn length(&self, ident: &str) -> String {
+        match self {
+            Dialect::Sqlite => format!("length({ident})"),
+            Dialect::Postgres => format!("length(CAST({ident} AS text))"),
+            Dialect::Mysql => format!("char_length({ident})"),
+        }
+    }
+}
+
+// how a column's values should be compared
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Affinity {
+    Text,
+    Integer,
+    Boolean,
+    Real,
+    Other,
+}
+
+impl Affinity {
+    // loosely follows sqlite's rules for determining column affinity
+    fn of(col: &TableColumn) -> Self {
+        let typ = match col {
+            TableColumn::RowId => return Affinity::Integer,
+            TableColumn::Spec(spec) => spec.typ.to_lowercase(),
+            TableColumn::Result(_) => return Affinity::Other,
+        };
+        if typ.starts_with("bool") {
+            Affinity::Boolean
+        } else if typ.contains("int") {
+            Affinity::Integer
+        } else if ["char", "clob", "text", "string"]
+            .iter()
+            .any(|t| typ.contains(t))
+        {
+            Affinity::Text
+        } else if ["real", "floa", "doub", "numeric", "decimal"]
+            .iter()
+            .any(|t| typ.contains(t))
+        {
+            Affinity::Real
+        } else {
+            Affinity::Other
+        }
+    }
+
+    fn is_number(&self) -> bool {
+        matches!(self, Affinity::Integer | Affinity::Boolean | Affinity::Real)
+    }
+
+    fn arg(&self, value: &str) -> Option<Arg> {
+        match self {
+            Affinity::Integer => value.parse().ok().map(Arg::Integer),
+            Affinity::Boolean => match value.to_lowercase().as_str() {
+                "true" | "t" | "1" => Some(Arg::Bool(true)),
+                "false" | "f" | "0" => Some(Arg::Bool(false)),
+                _ => None,
+            },
+            Affinity::Real => value.parse().ok().map(Arg::Real),
+            Affinity::Text | Affinity::Other => Some(Arg::Text(value.to_string())),
+        }
+    }
+}
+
+impl Filter {
+    pub fn parse(query: &str) -> Result<Self> {
+        let terms = tokenize(query)?
+            .into_iter()
+            .filter_map(|token| Term::parse(&token))
+            .collect();
+        Ok(Self { terms })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Compiles the filter against the columns of a table. None is returned
+    /// if the filter has no terms.
+    pub fn to_sql(&self, schema: &TableSchema, dialect: Dialect) -> Result<Option<Where>> {
+        let mut clauses = vec![];
+        let mut args = vec![];
+        for term in &self.terms {
+            let clause = match &term.kind {
+                TermKind::Any(value) => any_clause(schema, dialect, value, &mut args),
+                TermKind::Column { name, op, value } => {
+                    column_clause(schema, dialect, name, *op, value, &mut args)?
+                }
+            };
+            if term.negate {
+                // a null never matches, so a negated term should include it
+                let fals = dialect.false_literal();
+                clauses.push(format!("NOT coalesce({clause}, {fals})"));
+            } else {
+                clauses.push(clause);
+            }
+        }
+        if clauses.is_empty() {
+            return Ok(None);
+        }
+        let sql = clauses.join(" AND ");
+        Ok(Some(Where { sql, args }))
+    }
+}
+
+impl Term {
+    fn parse(token: &str) -> Option<Self> {
+        let (negate, token) = match token.strip_prefix('-') {
+            Some(rest) if !rest.is_empty() => (true, rest),
+            _ => (false, token),
+        };
+        let kind = match column_op(token) {
+            // still being typed, so it does not constrain anything yet
+            Some((_, _, "")) => return None,
+            Some((name, op, value)) => TermKind::Column {
+                name: name.to_string(),
+                op,
+                value: unquote(value),
+            },
+            None => TermKind::Any(unquote(token)),
+        };
+        Some(Self { negate, kind })
+    }
+}
+
+// splits a term like `age>=30` into its column, operator, and value.
+fn column_op(token: &str) -> Option<(&str, Op, &str)> {
+    let end = token
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|idx| *idx > 0)?;
+    let (name, rest) = token.split_at(end);
+    Op::ALL
+        .iter()
+        .find_map(|(s, op)| rest.strip_prefix(s).map(|value| (name, *op, value)))
+}
+
+fn unquote(value: &str) -> String {
+    value.replace('"', "")
+}
+
+// splits the query on whitespace outside of double quotes
+fn tokenize(query: &str) -> Result<Vec<String>> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in query.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                token.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+    if quoted {
+        bail!("unterminated quote in search");
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// A term that matches rows where the column equals the value exactly
+pub fn eq_term(column: &str, value: &str) -> Result<String> {
+    if column.is_empty() || !column.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        bail!("column {column} cannot be searched");
+    }
+    if value.contains('"') {
+        bail!("value {value} cannot be searched");
+    }
+    if value.is_empty() || value.contains(char::is_whitespace) {
+        return Ok(format!("{column}=\"{value}\""));
+    }
+    Ok(format!("{column}={value}"))
+}
+
+pub(crate) fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn like_arg(value: &str) -> Arg {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    Arg::Text(format!("%{escaped}%"))
+}
+
+// matches the value against every text column, and against every number
+// column if the value is a number. the constraints are OR'd together.
+fn any_clause(schema: &TableSchema, dialect: Dialect, value: &str, args: &mut Vec<Arg>) -> String {
+    let constraints = schema
+        .cols
+        .iter()
+        .filter_map(|col| {
+            let affinity = Affinity::of(col);
+            let ident = dialect.quote(col.name());
+            match affinity {
+                Affinity::Text => {
+                    args.push(like_arg(value));
+                    Some(dialect.like(&ident, &dialect.placeholder(args.len())))
+                }
+                Affinity::Integer | Affinity::Real => {
+                    let arg = affinity.arg(value)?;
+                    args.push(arg);
+                    Some(format!("{ident} = {}", dialect.placeholder(args.len())))
+                }
+                Affinity::Boolean | Affinity::Other => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    if constraints.is_empty() {
+        // nothing can match the value
+        return dialect.false_literal().to_string();
+    }
+    format!("({})", constraints.join(" OR "))
+}
+
+fn column_clause(
+    schema: &TableSchema,
+    dialect: Dialect,
+    name: &str,
+    op: Op,
+    value: &str,
+    args: &mut Vec<Arg>,
+) -> Result<String> {
+    let Some(col) = schema
+        .cols
+        .iter()
+        .find(|c| c.name().eq_ignore_ascii_case(name))
+    else {
+        bail!("unknown column: {name}");
+    };
+    let affinity = Affinity::of(col);
+    let ident = dialect.quote(col.name());
+    if op == Op::Contains && !affinity.is_number() {
+        args.push(like_arg(value));
+        return Ok(dialect.like(&ident, &dialect.placeholder(args.len())));
+    }
+    let Some(arg) = affinity.arg(value) else {
+        let expected = if affinity == Affinity::Boolean {
+            "boolean"
+        } else {
+            "numeric"
+        };
+        bail!("column {} expects a {expected} value", col.name());
+    };
+    args.push(arg);
+    let ident = match affinity {
+        Affinity::Other => dialect.text(&ident),
+        _ => ident,
+    };
+    Ok(format!(
+        "{ident} {} {}",
+        op.sql(),
+        dialect.placeholder(args.len())
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let filter = Filter::parse(r#" foo  name:"a b" -status:deleted age>=30 "#)?;
+        assert_eq!(
+            filter.terms,
+            vec![
+                Term {
+                    negate: false,
+                    kind: TermKind::Any("foo".to_string()),
+                },
+                Term {
+                    negate: false,
+                    kind: TermKind::Column {
+                        name: "name".to_string(),
+                        op: Op::Contains,
+                        value: "a b".to_string(),
+                    },
+                },
+                Term {
+                    negate: true,
+                    kind: TermKind::Column {
+                        name: "status".to_string(),
+                        op: Op::Contains,
+                        value: "deleted".to_string(),
+                    },
+                },
+                Term {
+                    negate: false,
+                    kind: TermKind::Column {
+                        name: "age".to_string(),
+                        op: Op::Ge,
+                        value: "30".to_string(),
+                    },
+                },
+            ]
+        );
+
+        // operators must follow a column name
+        let filter = Filter::parse(">30 -")?;
+        assert_eq!(
+            filter.terms,
+            vec![
+                Term {
+                    negate: false,
+                    kind: TermKind::Any(">30".to_string()),
+                },
+                Term {
+                    negate: false,
+                    kind: TermKind::Any("-".to_string()),
+                },
+            ]
+        );
+
+        assert!(Filter::parse("").unwrap().is_empty());
+        assert!(Filter::parse("name: age>").unwrap().is_empty());
+        assert!(Filter::parse(r#"name:"foo"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_eq_term() -> Result<()> {
+        for value in ["collin", "a b", "", "-1", "x:y"] {
+            let filter = Filter::parse(&eq_term("name", value)?)?;
+            assert_eq!(
+                filter.terms,
+                vec![Term {
+                    negate: false,
+                    kind: TermKind::Column {
+                        name: "name".to_string(),
+                        op: Op::Eq,
+                        value: value.to_string(),
+                    },
+                }]
+            );
+        }
+        assert!(eq_term("first name", "x").is_err());
+        assert!(eq_term("name", "a\"b").is_err());
+        Ok(())
+    }
+}
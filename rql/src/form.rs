@@ -0,0 +1,149 @@
+use crate::prelude::*;
+
+/// The values of a single row being inserted or edited. Submitting the form
+/// produces changes that are staged until they are committed.
+pub struct Form {
+    table: String,
+    rowid: Option<i64>, // set when editing an existing row
+    pub fields: Vec<FormField>,
+    pub pos: usize,
+}
+
+pub struct FormField {
+    pub name: String,
+    pub value: String,
+    original: Option<FieldValue>,
+}
+
+impl Form {
+    /// An empty form for each of the table's columns
+    pub fn insert(schema: &TableSchema) -> Self {
+        let fields = schema
+            .cols
+            .iter()
+            .filter(|col| Self::editable(&col.field_type()))
+            .map(|col| FormField {
+                name: col.name().to_string(),
+                value: String::new(),
+                original: None,
+            })
+            .collect();
+        Self {
+            table: schema.name.clone(),
+            rowid: None,
+            fields,
+            pos: 0,
+        }
+    }
+
+    /// A form filled with the values of an existing row. None is returned if
+    /// the row cannot be addressed by its rowid.
+    pub fn edit(schema: &TableSchema, record: &Record) -> Option<Self> {
+        let rowid = record.rowid()?;
+        let fields = record
+            .fields
+            .iter()
+            .filter(|field| Self::editable(&field.typ))
+            .map(|field| FormField {
+                name: field.name.clone(),
+                value: Self::text(&field.val),
+                original: Some(field.val.clone()),
+            })
+            .collect();
+        Some(Self {
+            table: schema.name.clone(),
+            rowid: Some(rowid),
+            fields,
+            pos: 0,
+        })
+    }
+
+    // blobs cannot be entered as text and the rowid is managed by sqlite
+    fn editable(typ: &FieldType) -> bool {
+        !matches!(typ, FieldType::RowId | FieldType::Blob)
+    }
+
+    // how a value is shown in the form. NULL is used to enter a null value.
+    fn text(val: &FieldValue) -> String {
+        if val.is_null() {
+            "NULL".to_string()
+        } else {
+            val.to_string()
+        }
+    }
+
+    fn value(text: &str) -> Option<String> {
+        (text != "NULL").then(|| text.to_string())
+    }
+
+    pub fn title(&self) -> String {
+        match self.rowid {
+            Some(rowid) => format!("[ edit {} rowid={} ]", self.table, rowid),
+            None => format!("[ insert into {} ]", self.table),
+        }
+    }
+
+    /// Moves to the field of the column, if it can be edited
+    pub fn select_field(&mut self, name: &str) {
+        if let Some(pos) = self.fields.iter().position(|f| f.name == name) {
+            self.pos = pos;
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.fields.is_empty() {
+            self.pos = (self.pos + 1) % self.fields.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.fields.is_empty() {
+            self.pos = (self.pos + self.fields.len() - 1) % self.fields.len();
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        if let Some(field) = self.fields.get_mut(self.pos) {
+            field.value.push(c);
+        }
+    }
+
+    pub fn pop(&mut self) {
+        if let Some(field) = self.fields.get_mut(self.pos) {
+            field.value.pop();
+        }
+    }
+
+    /// The changes made by the form. Edits produce an update for each field
+    /// that changed, inserts leave empty fields to their column defaults.
+    pub fn changes(&self) -> Vec<Change> {
+        let Some(rowid) = self.rowid else {
+            let values = self
+                .fields
+                .iter()
+                .filter(|f| !f.value.is_empty())
+                .map(|f| (f.name.clone(), Self::value(&f.value)))
+                .collect();
+            return vec![Change::Insert {
+                table: self.table.clone(),
+                values,
+            }];
+        };
+        self.fields
+            .iter()
+            .filter_map(|f| {
+                let old = f.original.clone()?;
+                if Self::text(&old) == f.value {
+                    return None;
+                }
+                Some(Change::Update {
+                    table: self.table.clone(),
+                    rowid,
+                    column: f.name.clone(),
+                    old,
+                    new: Self::value(&f.value),
+                })
+            })
+            .collect()
+    }
+}
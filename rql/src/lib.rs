@@ -1,12 +1,20 @@
 #![allow(dead_code, unused)]
 pub mod app;
+mod backend;
+pub mod config;
 pub mod dao;
+pub mod export;
+pub mod filter;
+pub mod form;
 mod pager;
 pub mod table;
 pub mod tables;
 pub mod prelude {
-    pub use crate::{app::*, dao::*, pager::*, table::*, tables::*};
+    pub use crate::{
+        app::*, config::*, dao::*, export::*, filter::*, form::*, pager::*, table::*, tables::*,
+    };
     pub use anyhow::{Context, Error, Result};
+    pub use chrono_tz::Tz;
     pub use clap::Parser;
     pub use crossterm::{
         event,
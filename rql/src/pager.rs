@@ -86,6 +86,27 @@ impl Pager {
         }
     }
 
+    /// Moves forward by a number of rows, stopping at the last one
+    pub fn page_down(&mut self, rows: usize) {
+        if self.is_empty() {
+            return;
+        }
+        let Some(pos) = self.pos.as_mut() else { return };
+        *pos = (*pos + rows).min(self.count - 1);
+        if *pos >= self.viewport_rows + self.top {
+            self.top = (*pos + 1).saturating_sub(self.viewport_rows);
+        }
+    }
+
+    /// Moves backward by a number of rows, stopping at the first one
+    pub fn page_up(&mut self, rows: usize) {
+        let Some(pos) = self.pos.as_mut() else { return };
+        *pos = pos.saturating_sub(rows);
+        if *pos < self.top {
+            self.top = *pos;
+        }
+    }
+
     fn relative_pos(&self) -> Option<usize> {
         match self.pos {
             Some(pos) if pos >= self.top => Some(pos - self.top),
@@ -138,4 +159,23 @@ mod tests {
         p.prev();
         assert_eq!(p.top_pos_rel(), (2, Some(4), Some(2)));
     }
+
+    #[test]
+    fn test_page() {
+        let mut p = Pager::default().count(10).viewport_rows(3);
+        p.select(0);
+        p.page_down(4);
+        assert_eq!(p.top_pos_rel(), (2, Some(4), Some(2)));
+        p.page_down(4);
+        assert_eq!(p.top_pos_rel(), (6, Some(8), Some(2)));
+        // pages stop at the ends rather than wrapping
+        p.page_down(4);
+        assert_eq!(p.top_pos_rel(), (7, Some(9), Some(2)));
+        p.page_up(1);
+        assert_eq!(p.top_pos_rel(), (7, Some(8), Some(1)));
+        p.page_up(4);
+        assert_eq!(p.top_pos_rel(), (4, Some(4), Some(0)));
+        p.page_up(10);
+        assert_eq!(p.top_pos_rel(), (0, Some(0), Some(0)));
+    }
 }
@@ -1,5 +1,8 @@
 use crate::prelude::*;
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+};
 
 /// Enables the display of a table's contents
 pub struct DbTable {
@@ -9,7 +12,31 @@ pub struct DbTable {
     pub pager: Pager,
     pub count: u64,
     pub indexed: IndexedRecords,
-    search: Option<String>,
+    foreign_keys: Vec<ForeignKeySpec>,
+    pub view: ColumnView,
+    source: Source,
+}
+
+/// Which columns are shown and how the records are ordered
+#[derive(Debug, Default, Clone)]
+pub struct ColumnView {
+    pub pos: usize,          // the focused column
+    left: usize,             // the first scrolled column that is shown
+    hidden: HashSet<String>, // the names of hidden columns
+    pub sort: Option<Sort>,  // the order of the records
+}
+
+/// A search for the row that a foreign key refers to
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Reference {
+    pub table: String,
+    pub search: String,
+}
+
+/// Where the records of a DbTable come from
+enum Source {
+    Table { search: Option<String> },
+    Query { sql: String },
 }
 
 #[derive(Default)]
@@ -75,6 +102,7 @@ impl DbTable {
         let count = dao.count(Count::new(&name).maybe_search(&search))?;
         let schema = dao.table_schema(&name)?;
         let max_lens = dao.max_lens(&schema, MaxLens::new(&name).maybe_search(&search))?;
+        let foreign_keys = dao.foreign_keys(&name)?;
         let max_lens: HashMap<TableColumn, usize> = schema
             .cols
             .iter()
@@ -93,11 +121,37 @@ impl DbTable {
             pager,
             count,
             indexed,
-            search,
+            foreign_keys,
+            view: ColumnView::default(),
+            source: Source::Table { search },
+        };
+        Ok(table)
+    }
+
+    /// Builds a table from the results of an ad-hoc select statement
+    pub fn query(dao: BlockingDao, sql: String) -> Result<Self> {
+        info!(sql, "Building query table");
+        let schema = dao.query_schema(&sql)?;
+        let count = dao.query_count(&sql)?;
+        let pager = Pager::default().count(count);
+        let table = Self {
+            dao,
+            schema,
+            max_lens: HashMap::default(),
+            pager,
+            count,
+            indexed: IndexedRecords::default(),
+            foreign_keys: vec![],
+            view: ColumnView::default(),
+            source: Source::Query { sql },
         };
         Ok(table)
     }
 
+    pub fn is_query(&self) -> bool {
+        matches!(self.source, Source::Query { .. })
+    }
+
     pub fn set_viewport_rows(&mut self, rows: usize) {
         self.pager.set_viewport_rows(rows);
     }
@@ -122,13 +176,17 @@ impl DbTable {
                 0
             };
             let limit = view_rows * 3;
-            let mut spec = GetRecords::new(&self.schema.name)
-                .offset(offset)
-                .limit(limit);
-            if let Some(q) = self.search.as_ref() {
-                spec = spec.search(q);
+            let records = self.fetch(offset, limit)?;
+            if self.is_query() {
+                // there is no cheap way to measure an arbitrary query, so
+                // widen the columns as records are fetched.
+                for record in &records {
+                    for (col, field) in self.schema.cols.iter().zip(&record.fields) {
+                        let len = self.max_lens.entry(col.clone()).or_default();
+                        *len = (*len).max(field.val.len());
+                    }
+                }
             }
-            let records = self.dao.records(&self.schema, spec)?;
             let irs = (offset..offset + limit)
                 .zip(records.into_iter())
                 .map(|(idx, record)| IndexedRecord(idx, record))
@@ -155,6 +213,245 @@ impl DbTable {
         Ok((records, state))
     }
 
+    /// Fetches a window of records from the table or query, honoring the
+    /// current search.
+    pub fn fetch(&self, offset: usize, limit: usize) -> Result<Vec<Record>> {
+        match &self.source {
+            Source::Table { search } => {
+                let spec = GetRecords::new(&self.schema.name)
+                    .offset(offset)
+                    .limit(limit)
+                    .maybe_search(search)
+                    .maybe_sort(&self.view.sort);
+                self.dao.records(&self.schema, spec)
+            }
+            Source::Query { sql } => {
+                let spec = Query::new(sql.as_str())
+                    .offset(offset)
+                    .limit(limit)
+                    .maybe_sort(&self.view.sort);
+                self.dao.query(&self.schema, spec)
+            }
+        }
+    }
+
+    /// The record under the cursor, if it has been fetched
+    pub fn selected(&self) -> Option<&Record> {
+        let pos = self.pager.pos?;
+        self.indexed
+            .0
+            .iter()
+            .find(|r| r.index() == pos)
+            .map(|r| &r.1)
+    }
+
+    /// Where the foreign key of the focused column leads for the selected
+    /// record. None is returned if the column is not part of a foreign key or
+    /// the key is null.
+    pub fn reference(&self) -> Result<Option<Reference>> {
+        let (Some(record), Some(col)) = (self.selected(), self.focused_column()) else {
+            return Ok(None);
+        };
+        let value = |name: &str| {
+            record
+                .fields
+                .iter()
+                .find(|f| f.name == name)
+                .filter(|f| !f.val.is_null())
+                .map(|f| f.val.to_string())
+        };
+        // a key over several columns has a row for each column
+        for key in self.foreign_keys.chunk_by(|a, b| a.id == b.id) {
+            if !key.iter().any(|k| k.from == col.name()) {
+                continue;
+            }
+            let Some(values) = key
+                .iter()
+                .map(|k| value(&k.from))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            let to = if key.iter().all(|k| k.to.is_some()) {
+                key.iter().filter_map(|k| k.to.clone()).collect()
+            } else {
+                // the key refers to the primary key of the other table
+                self.dao
+                    .table_details(&key[0].table)?
+                    .cols
+                    .into_iter()
+                    .filter(|c| c.pk)
+                    .map(|c| c.name)
+                    .collect::<Vec<_>>()
+            };
+            if to.len() != values.len() {
+                anyhow::bail!(
+                    "{} has no key for {} to refer to",
+                    key[0].table,
+                    self.name()
+                );
+            }
+            let search = to
+                .iter()
+                .zip(&values)
+                .map(|(col, val)| eq_term(col, val))
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(Some(Reference {
+                table: key[0].table.clone(),
+                search: search.join(" "),
+            }));
+        }
+        Ok(None)
+    }
+
+    pub fn focused_column(&self) -> Option<&TableColumn> {
+        self.schema.cols.get(self.view.pos)
+    }
+
+    /// Focuses the column with the name, returning whether it was found
+    pub fn select_column(&mut self, name: &str) -> bool {
+        let pos = self.schema.cols.iter().position(|c| c.name() == name);
+        if let Some(pos) = pos {
+            self.view.hidden.remove(name);
+            self.view.pos = pos;
+        }
+        pos.is_some()
+    }
+
+    fn is_hidden(&self, idx: usize) -> bool {
+        self.view.hidden.contains(self.schema.cols[idx].name())
+    }
+
+    // the rowid stays in view while scrolling through the other columns
+    fn is_pinned(&self, idx: usize) -> bool {
+        self.schema.cols[idx] == TableColumn::RowId
+    }
+
+    /// Focuses the next shown column, returning whether it moved
+    pub fn next_column(&mut self) -> bool {
+        let next = (self.view.pos + 1..self.schema.cols.len()).find(|idx| !self.is_hidden(*idx));
+        next.map(|idx| self.view.pos = idx).is_some()
+    }
+
+    /// Focuses the previous shown column, returning whether it moved
+    pub fn previous_column(&mut self) -> bool {
+        let prev = (0..self.view.pos).rev().find(|idx| !self.is_hidden(*idx));
+        prev.map(|idx| self.view.pos = idx).is_some()
+    }
+
+    /// Hides the focused column. The last shown column cannot be hidden.
+    pub fn hide_column(&mut self) {
+        let Some(name) = self.focused_column().map(|c| c.name().to_string()) else {
+            return;
+        };
+        if self.view.hidden.len() + 1 >= self.schema.cols.len() {
+            return;
+        }
+        self.view.hidden.insert(name);
+        if !self.next_column() {
+            self.previous_column();
+        }
+    }
+
+    pub fn show_columns(&mut self) {
+        self.view.hidden.clear();
+    }
+
+    pub fn num_hidden(&self) -> usize {
+        self.view.hidden.len()
+    }
+
+    /// Sorts by the focused column ascending, then descending, then not at
+    /// all. The records are fetched again from the first one.
+    pub fn cycle_sort(&mut self) {
+        let Some(name) = self.focused_column().map(|c| c.name().to_string()) else {
+            return;
+        };
+        self.view.sort = match self.view.sort.take() {
+            Some(sort) if sort.column == name && sort.desc => None,
+            Some(sort) if sort.column == name => Some(Sort::desc(name)),
+            _ => Some(Sort::asc(name)),
+        };
+        self.indexed = IndexedRecords::default();
+        self.pager.unselect();
+        self.select_first();
+    }
+
+    /// Keeps the columns and order of another view of the same table
+    pub fn set_view(&mut self, view: ColumnView) {
+        if view.pos < self.schema.cols.len() {
+            self.view = view;
+        }
+    }
+
+    /// The header of a column, marked if the records are sorted by it
+    pub fn header(&self, idx: usize) -> String {
+        let name = self.schema.cols[idx].name();
+        match &self.view.sort {
+            Some(sort) if sort.column == name && sort.desc => format!("{name} ▼"),
+            Some(sort) if sort.column == name => format!("{name} ▲"),
+            _ => name.to_string(),
+        }
+    }
+
+    pub fn col_width(&self, idx: usize) -> usize {
+        let header_len = self.header(idx).chars().count();
+        self.max_len(&self.schema.cols[idx], 4).max(header_len)
+    }
+
+    /// The columns that fit in the width, pinned columns first. The columns
+    /// are scrolled so that the focused one is shown.
+    pub fn visible_columns(&mut self, width: u16) -> Vec<usize> {
+        let width = width as usize;
+        // columns are separated by a space
+        let col_width = |idx: usize| self.col_width(idx) + 1;
+        let (pinned, scrolled): (Vec<_>, Vec<_>) = (0..self.schema.cols.len())
+            .filter(|idx| !self.is_hidden(*idx))
+            .partition(|idx| self.is_pinned(*idx));
+        let pinned_width: usize = pinned.iter().map(|idx| col_width(*idx)).sum();
+        let avail = width.saturating_sub(pinned_width);
+
+        let mut left = scrolled
+            .iter()
+            .position(|idx| *idx >= self.view.left)
+            .unwrap_or(0);
+        if let Some(pos) = scrolled.iter().position(|idx| *idx == self.view.pos) {
+            left = left.min(pos);
+            while left < pos
+                && scrolled[left..=pos]
+                    .iter()
+                    .map(|i| col_width(*i))
+                    .sum::<usize>()
+                    > avail
+            {
+                left += 1;
+            }
+        }
+        // scroll back when there is room, e.g. after hiding columns
+        let fits = |from: usize| {
+            scrolled[from..]
+                .iter()
+                .map(|i| col_width(*i))
+                .sum::<usize>()
+                <= avail
+        };
+        while left > 0 && fits(left - 1) {
+            left -= 1;
+        }
+        let mut used = 0;
+        let shown = scrolled[left.min(scrolled.len())..]
+            .iter()
+            .take_while(|idx| {
+                used += col_width(**idx);
+                // always show at least one column
+                used <= avail || used == col_width(**idx)
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        self.view.left = scrolled.get(left).copied().unwrap_or(0);
+        pinned.into_iter().chain(shown).collect()
+    }
+
     pub fn name<'a>(&'a self) -> &'a str {
         return &self.schema.name;
     }
@@ -167,6 +464,14 @@ impl DbTable {
         self.pager.prev();
     }
 
+    pub fn page_down(&mut self, rows: usize) {
+        self.pager.page_down(rows);
+    }
+
+    pub fn page_up(&mut self, rows: usize) {
+        self.pager.page_up(rows);
+    }
+
     pub fn select_first(&mut self) {
         self.pager.select(0);
     }
@@ -175,3 +480,108 @@ impl DbTable {
         self.pager.unselect();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference() -> Result<()> {
+        let dao = BlockingDao::new(DbType::Memory, Tz::UTC)?;
+        dao.execute("create table owner (id INTEGER PRIMARY KEY, name TEXT, UNIQUE (id, name))")?;
+        dao.execute(
+            "create table pet (
+                name TEXT,
+                owner_id INTEGER REFERENCES owner,
+                owner_name TEXT,
+                FOREIGN KEY (owner_id, owner_name) REFERENCES owner(id, name)
+            )",
+        )?;
+        dao.execute("insert into owner (id, name) values (1, 'collin c'), (2, 'other')")?;
+        dao.execute(
+            "insert into pet values ('rex', 1, 'collin c'), ('spot', 2, null), ('stray', null, null)",
+        )?;
+        let reference = |search: &str, column: &str| -> Result<Option<Reference>> {
+            let mut table = DbTable::new(dao.clone(), "pet".to_string(), Some(search.into()))?;
+            table.set_viewport_rows(10);
+            table.select_first();
+            table.records()?;
+            assert!(table.select_column(column));
+            table.reference()
+        };
+
+        // keys are listed last to first
+        assert_eq!(
+            Some(Reference {
+                table: "owner".to_string(),
+                search: r#"id=1 name="collin c""#.to_string(),
+            }),
+            reference("name:rex", "owner_id")?
+        );
+        // the key to the primary key is used while the other is null
+        assert_eq!(
+            Some(Reference {
+                table: "owner".to_string(),
+                search: "id=2".to_string(),
+            }),
+            reference("name:spot", "owner_id")?
+        );
+        assert_eq!(None, reference("name:spot", "owner_name")?);
+        assert_eq!(None, reference("name:rex", "name")?);
+        assert_eq!(None, reference("name:stray", "owner_id")?);
+
+        // the referenced row is found by the search
+        let search = reference("name:rex", "owner_name")?.unwrap().search;
+        let owner = DbTable::new(dao.clone(), "owner".to_string(), Some(search))?;
+        assert_eq!(1, owner.count);
+        Ok(())
+    }
+
+    #[test]
+    fn test_columns() -> Result<()> {
+        let dao = BlockingDao::new(DbType::Memory, Tz::UTC)?;
+        dao.execute("create table foo (aaaaaaaa TEXT, bbbbbbbb TEXT, cccccccc TEXT, n INTEGER)")?;
+        dao.execute(
+            "insert into foo values ('a', 'b', 'c', 2), ('a', 'b', 'c', 1), ('z', 'b', 'c', 1)",
+        )?;
+        let mut table = DbTable::new(dao.clone(), "foo".to_string(), None)?;
+        table.set_viewport_rows(10);
+        table.select_first();
+        let names = |table: &DbTable, cols: Vec<usize>| {
+            cols.into_iter()
+                .map(|idx| table.schema.cols[idx].name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // the rowid stays while scrolling to the focused column
+        let cols = table.visible_columns(25);
+        assert_eq!(vec!["rowid", "aaaaaaaa", "bbbbbbbb"], names(&table, cols));
+        assert!(table.next_column() && table.next_column() && table.next_column());
+        let cols = table.visible_columns(25);
+        assert_eq!(vec!["rowid", "bbbbbbbb", "cccccccc"], names(&table, cols));
+        assert!(table.next_column());
+        assert!(!table.next_column());
+
+        table.hide_column();
+        assert_eq!("cccccccc", table.focused_column().unwrap().name());
+        table.hide_column();
+        let cols = table.visible_columns(80);
+        assert_eq!(vec!["rowid", "aaaaaaaa", "bbbbbbbb"], names(&table, cols));
+        table.show_columns();
+        assert_eq!(0, table.num_hidden());
+
+        // sorted descending, ties are broken by the rowid
+        table.select_column("n");
+        table.cycle_sort();
+        table.cycle_sort();
+        assert_eq!("n ▼", table.header(4));
+        let (records, _) = table.records()?;
+        let rowids = records.iter().map(|r| r.rowid()).collect::<Vec<_>>();
+        assert_eq!(vec![Some(1), Some(2), Some(3)], rowids);
+        table.select_column("aaaaaaaa");
+        table.cycle_sort();
+        assert_eq!("n", table.header(4));
+        assert_eq!(Some(Sort::asc("aaaaaaaa")), table.view.sort);
+        Ok(())
+    }
+}
@@ -1,22 +1,27 @@
+use crate::dao::SchemaObject;
 use ratatui::widgets::ListState;
 
-/// Represents a list of tables.
+/// Represents a list of tables, views, and triggers.
 pub struct DbTables {
-    pub names: Vec<String>,
+    pub objects: Vec<SchemaObject>,
     pub state: ListState,
 }
 
 impl DbTables {
-    pub fn new(names: Vec<String>) -> Self {
+    pub fn new(objects: Vec<SchemaObject>) -> Self {
         let mut state = ListState::default();
-        if !names.is_empty() {
+        if !objects.is_empty() {
             state.select(Some(0));
         }
-        Self { names, state }
+        Self { objects, state }
     }
 
     pub fn max_len(&self) -> u16 {
-        self.names.iter().map(|s| s.len() as u16).max().unwrap_or(0)
+        self.objects
+            .iter()
+            .map(|o| o.label().len() as u16)
+            .max()
+            .unwrap_or(0)
     }
 
     /// next selects the subsequent table in the list, returning whether it
@@ -25,7 +30,13 @@ impl DbTables {
         let i = self
             .state
             .selected()
-            .map(|i| if i >= self.names.len() - 1 { 0 } else { i + 1 })
+            .map(|i| {
+                if i >= self.objects.len() - 1 {
+                    0
+                } else {
+                    i + 1
+                }
+            })
             .unwrap_or(0);
         let changed = !self.state.selected().is_some_and(|last| last == i);
         self.state.select(Some(i));
@@ -39,7 +50,13 @@ impl DbTables {
         let i = self
             .state
             .selected()
-            .map(|i| if i == 0 { self.names.len() - 1 } else { i - 1 })
+            .map(|i| {
+                if i == 0 {
+                    self.objects.len() - 1
+                } else {
+                    i - 1
+                }
+            })
             .unwrap_or(0);
         let changed = !self.state.selected().is_some_and(|last| last == i);
         self.state.select(Some(i));
@@ -48,10 +65,11 @@ impl DbTables {
     }
 
     pub fn selected(&self) -> Option<String> {
-        self.state
-            .selected()
-            .map(|i| self.names.get(i).map(|s| s.clone()))
-            .flatten()
+        self.selected_object().map(|o| o.name.clone())
+    }
+
+    pub fn selected_object(&self) -> Option<&SchemaObject> {
+        self.state.selected().and_then(|i| self.objects.get(i))
     }
 
     pub fn unselect(&mut self) {
@@ -7,6 +7,9 @@ use std::{
 type AsyncFuture = Box<dyn Future<Output = ()> + Send + 'static>;
 type WrappedFuture = Arc<Mutex<Option<Pin<AsyncFuture>>>>;
 
+/// Makes a new command for each run of a periodic task.
+pub(crate) type Factory = Box<dyn Fn() -> Command + Send + Sync + 'static>;
+
 /// Command wraps futures to be executed by the scheduler.
 pub(crate) struct Command(WrappedFuture);
 
@@ -1,13 +1,20 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
 use crate::{
-    command::Command,
+    command::{Command, Factory},
     hooks::{self, Callback},
-    rules::Rules,
-    scheduler::{Request, Response, TaskRequest, WaitRequest},
+    rules::{Missed, Rules},
+    scheduler::{CancelRequest, EveryRequest, Request, Response, TaskRequest, WaitRequest},
     task,
 };
-use tokio::sync::mpsc;
+use rand::Rng;
+use tokio::{
+    sync::mpsc,
+    time::{sleep_until, Instant},
+};
 
 /// Control is the main synchronization point for running tasks. It receives requests from the
 /// scheduler on a channel and then decides what to do with those requests.
@@ -18,6 +25,7 @@ pub(crate) struct Control {
     hooks: hooks::Hooks,
     rules: Rules,
     running: HashMap<task::Type, usize>,
+    periodic: HashMap<u64, Periodic>,
 }
 
 impl Control {
@@ -30,6 +38,7 @@ impl Control {
             hooks,
             rules,
             running: HashMap::default(),
+            periodic: HashMap::default(),
         }
     }
     /// The main loop of the Controller.
@@ -42,6 +51,8 @@ impl Control {
                 let wr = wait.take().unwrap();
                 let _ = wr.tx.send(Response::Accepted);
             }
+            // the next time a periodic task is due, if there are any.
+            let next_due = self.periodic.values().map(|p| p.due).min();
             // After we're done with bookkeeping, enter the select.
             tokio::select! {
                 Some(res) = self.res_rx.recv() => {
@@ -54,9 +65,14 @@ impl Control {
                             if let Err(e) = hook_res {
                                 println!("Error in hook.on_task_complete: {e:?}");
                             }
+                            // there is room for a periodic run that is still owed.
+                            self.run_owed(&typ, wait.is_some()).await;
                         }
                     }
                 }
+                () = sleep_until(next_due.unwrap_or_else(Instant::now)), if next_due.is_some() => {
+                    self.tick(wait.is_some()).await;
+                }
                 Some(req) = self.rx.recv() => {
                     match req {
                         Request::Task(TaskRequest{typ, cmd, tx}) => {
@@ -71,21 +87,7 @@ impl Control {
                                 let _ = tx.send(Response::Rejected);
                                 continue;
                             }
-                            let res_tx = self.res_tx.clone();
-                            let task_typ = typ.clone();
-
-                            // if we accepted, invoke the hook if it exists. we will block
-                            // the scheduler until the hook is completed so that we can
-                            // ensure consistency.
-                            let hook_res = &self.hooks.on_task_start(&typ).await;
-                            if let Err(e) = hook_res {
-                                println!("Error in hook: {e:?}");
-                            }
-                            // finally, spawn the task and send the accepted response.
-                            tokio::spawn(async move {
-                                let mut runner = Runner::new(task_typ, cmd, res_tx);
-                                runner.run().await;
-                            });
+                            self.start(typ, cmd).await;
                             let _ = tx.send(Response::Accepted);
                         }
                         Request::Wait(wr) => {
@@ -95,6 +97,31 @@ impl Control {
                                 wait = Some(wr);
                             }
                         }
+                        Request::Every(EveryRequest{id, typ, make, tx}) => {
+                            // only task types with an interval can be run periodically. a zero
+                            // one would be due again as soon as it ran.
+                            let every = self.rules.get(&typ).run_every;
+                            let Some(every) = every.filter(|e| !e.is_zero()) else {
+                                let _ = tx.send(Response::Rejected);
+                                continue;
+                            };
+                            if wait.is_some() {
+                                let _ = tx.send(Response::Rejected);
+                                continue;
+                            }
+                            let next = Instant::now() + every;
+                            let due = next + jitter(self.rules.get(&typ).jitter);
+                            let periodic = Periodic { typ, make, next, due, owed: 0 };
+                            self.periodic.insert(id, periodic);
+                            let _ = tx.send(Response::Accepted);
+                        }
+                        Request::Cancel(CancelRequest{id, tx}) => {
+                            let res = match self.periodic.remove(&id) {
+                                Some(_) => Response::Accepted,
+                                None => Response::Rejected,
+                            };
+                            let _ = tx.send(res);
+                        }
                     }
                 }
             }
@@ -126,6 +153,87 @@ impl Control {
         self.running.insert(typ.clone(), *count + 1);
         true
     }
+    /// Starts a task that `try_run` has already counted as running.
+    async fn start(&mut self, typ: task::Type, cmd: Command) {
+        let res_tx = self.res_tx.clone();
+
+        // invoke the hook if it exists. we will block the scheduler until the hook is completed
+        // so that we can ensure consistency.
+        let hook_res = &self.hooks.on_task_start(&typ).await;
+        if let Err(e) = hook_res {
+            println!("Error in hook: {e:?}");
+        }
+        // finally, spawn the task.
+        tokio::spawn(async move {
+            let mut runner = Runner::new(typ, cmd, res_tx);
+            runner.run().await;
+        });
+    }
+    /// Owes a run to each periodic task that is due, and starts as many of them as the rules
+    /// allow.
+    async fn tick(&mut self, waiting: bool) {
+        let now = Instant::now();
+        let mut due = HashSet::new();
+        for p in self.periodic.values_mut().filter(|p| p.due <= now) {
+            let rule = self.rules.get(&p.typ);
+            let every = rule.run_every.expect("periodic task without run_every");
+            // more than one tick has passed if the scheduler was busy for longer than the
+            // interval.
+            let missed = (now - p.next).as_nanos() / every.as_nanos();
+            let ticks = u32::try_from(missed).unwrap_or(u32::MAX).saturating_add(1);
+            p.next += every * ticks;
+            p.due = p.next + jitter(rule.jitter);
+            p.owed += match rule.missed {
+                Missed::Skip => 1,
+                Missed::CatchUp => ticks as usize,
+            };
+            due.insert(p.typ.clone());
+        }
+        for typ in due {
+            self.run_owed(&typ, waiting).await;
+        }
+    }
+    /// Starts the runs owed to periodic tasks of this type while there is room for them. Runs
+    /// that cannot start are dropped, unless the rule catches up on them.
+    async fn run_owed(&mut self, typ: &task::Type, waiting: bool) {
+        let ids: Vec<u64> = self
+            .periodic
+            .iter()
+            .filter(|(_, p)| &p.typ == typ && p.owed > 0)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            while !waiting && self.periodic[&id].owed > 0 && self.try_run(typ) {
+                let p = self.periodic.get_mut(&id).unwrap();
+                p.owed -= 1;
+                let cmd = (p.make)();
+                self.start(typ.clone(), cmd).await;
+            }
+            if self.rules.get(typ).missed == Missed::Skip {
+                self.periodic.get_mut(&id).unwrap().owed = 0;
+            }
+        }
+    }
+}
+
+/// A task that is run every `run_every` of its rule until it is cancelled.
+struct Periodic {
+    typ: task::Type,
+    make: Factory,
+    /// when the next run is due, before jitter.
+    next: Instant,
+    /// when the next run is due.
+    due: Instant,
+    /// runs that were due but have not started yet.
+    owed: usize,
+}
+
+/// Returns a random delay of up to `max`.
+fn jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    rand::thread_rng().gen_range(Duration::ZERO..=max)
 }
 
 struct Runner {
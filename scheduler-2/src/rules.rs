@@ -6,6 +6,7 @@ use crate::task;
 ///
 /// Each task type can have its own rule, and there is a default rule
 /// that applies to task types that do not have a specific rule.
+#[derive(Default)]
 pub struct Rules {
     rules: HashMap<task::Type, Rule>,
     default: Rule,
@@ -24,21 +25,38 @@ impl Rules {
 
 pub struct Rule {
     pub max_running: usize,
+    /// Tasks of this type registered with `Scheduler::run_every` run this often. An interval of
+    /// zero cannot be run periodically, like no interval at all.
     pub run_every: Option<Duration>,
+    /// Each periodic run is delayed by a random amount up to this, so that tasks registered
+    /// together do not all start at once.
+    pub jitter: Duration,
+    /// What to do about periodic runs that could not start when they were due.
+    pub missed: Missed,
 }
 
-impl Default for Rules {
+impl Default for Rule {
     fn default() -> Self {
         Self {
-            rules: HashMap::default(),
-            default: Rule {
-                max_running: 1,
-                run_every: None,
-            },
+            max_running: 1,
+            run_every: None,
+            jitter: Duration::ZERO,
+            missed: Missed::default(),
         }
     }
 }
 
+/// A periodic run is missed when it is due while `max_running` tasks of its type are already
+/// running, or when the scheduler was too busy to start it on time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Missed {
+    /// Missed runs are dropped, and the task runs again at the next interval.
+    #[default]
+    Skip,
+    /// Missed runs start one after another as soon as there is room for them.
+    CatchUp,
+}
+
 #[derive(Default)]
 pub struct Builder {
     rules: Rules,
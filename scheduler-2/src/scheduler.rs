@@ -1,17 +1,25 @@
 use crate::{
-    command::Command,
+    command::{Command, Factory},
     control::Control,
     hooks::{Callback, Hooks},
     rules::Rules,
     task::Type,
 };
 use anyhow::Result;
-use std::{future::Future, sync::Arc};
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 use tokio::sync::{mpsc, oneshot};
 
 #[derive(Clone)]
 pub struct Scheduler {
     tx: Arc<mpsc::Sender<Request>>,
+    /// identifies each periodic task so that it can be cancelled
+    ids: Arc<AtomicU64>,
 }
 
 impl Scheduler {
@@ -22,7 +30,10 @@ impl Scheduler {
             let mut ctrl = Control::new(rx, hooks, rules);
             ctrl.run().await;
         });
-        Self { tx: tx.into() }
+        Self {
+            tx: tx.into(),
+            ids: Arc::default(),
+        }
     }
 
     #[must_use]
@@ -64,6 +75,62 @@ impl Scheduler {
         self.tx.send(req).await?;
         Ok(rx.await?)
     }
+
+    /// Runs a task of this type every `run_every` of its rule, starting one interval from now.
+    /// Each run is a new future from `f`, and like any other task it only starts if fewer than
+    /// `max_running` tasks of the type are running. Returns `None` if the rule has no
+    /// `run_every` or a zero one, or if the scheduler is waiting.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the scheduler has been shut down.
+    pub async fn run_every<T: Into<Type>, F, Fut>(&self, typ: T, f: F) -> Result<Option<Periodic>>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let id = self.ids.fetch_add(1, Ordering::Relaxed);
+        let make: Factory = Box::new(move || Command::new(f()));
+        let (tx, rx) = oneshot::channel();
+        let req = EveryRequest {
+            id,
+            typ: typ.into(),
+            make,
+            tx,
+        };
+        self.tx.send(Request::Every(req)).await?;
+        let periodic = match rx.await? {
+            Response::Accepted => Some(Periodic {
+                id,
+                tx: self.tx.clone(),
+            }),
+            Response::Rejected => None,
+        };
+        Ok(periodic)
+    }
+}
+
+/// A task that the scheduler runs every so often, until it is cancelled. Dropping this does not
+/// cancel it.
+#[must_use]
+pub struct Periodic {
+    id: u64,
+    tx: Arc<mpsc::Sender<Request>>,
+}
+
+impl Periodic {
+    /// Stops the task from running again, including any runs it is owed. Runs that have started
+    /// are not interrupted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the scheduler has been shut down.
+    pub async fn cancel(self) -> Result<Response> {
+        let (tx, rx) = oneshot::channel();
+        let req = CancelRequest { id: self.id, tx };
+        self.tx.send(Request::Cancel(req)).await?;
+        Ok(rx.await?)
+    }
 }
 
 pub struct Builder {
@@ -106,6 +173,8 @@ impl From<Box<dyn Callback + Send + Sync + 'static>> for Hooks {
 pub(crate) enum Request {
     Task(TaskRequest),
     Wait(WaitRequest),
+    Every(EveryRequest),
+    Cancel(CancelRequest),
 }
 
 /// Instructs the scheduler to wait for all currently running tasks to complete. Any other requests
@@ -133,6 +202,20 @@ impl TaskRequest {
     }
 }
 
+/// A request to run a task of a type every so often, as its rule says.
+pub(crate) struct EveryRequest {
+    pub id: u64,
+    pub typ: Type,
+    pub make: Factory,
+    pub tx: oneshot::Sender<Response>,
+}
+
+/// A request to stop running a periodic task. It is rejected if there is no such task.
+pub(crate) struct CancelRequest {
+    pub id: u64,
+    pub tx: oneshot::Sender<Response>,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Response {
     Accepted,
@@ -2,13 +2,13 @@ use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use crate::hooks::HookResult;
-use crate::rules::{Rule, Rules};
-use crate::scheduler::Response;
+use crate::rules::{Missed, Rule, Rules};
+use crate::scheduler::{Periodic, Response};
 use crate::task::Type;
 use crate::{hooks::Callback, scheduler::Scheduler};
 use anyhow::Result;
 use async_trait::async_trait;
-use tokio::time::sleep;
+use tokio::time::{sleep, Instant};
 
 #[tokio::test]
 async fn test_scheduler() -> Result<()> {
@@ -71,14 +71,14 @@ async fn test_scheduler_rules() -> Result<()> {
             "foo",
             Rule {
                 max_running: count,
-                run_every: None,
+                ..Rule::default()
             },
         )
         .rule(
             "bar",
             Rule {
                 max_running: 5,
-                run_every: None,
+                ..Rule::default()
             },
         )
         .build();
@@ -103,6 +103,143 @@ async fn test_scheduler_rules() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(start_paused = true)]
+async fn test_scheduler_run_every() -> Result<()> {
+    let rules = Rules::builder()
+        .rule("tick", every(10, 1, Missed::Skip))
+        .build();
+    let sched = Scheduler::builder().rules(rules).build();
+    let (periodic, starts) = record_starts(&sched, "tick", 0).await?;
+
+    sleep(Duration::from_secs(35)).await;
+    assert_eq!(secs(&starts), vec![10, 20, 30]);
+
+    // no more runs once it is cancelled.
+    assert_eq!(periodic.cancel().await?, Response::Accepted);
+    sleep(Duration::from_secs(30)).await;
+    assert_eq!(secs(&starts), vec![10, 20, 30]);
+
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_scheduler_run_every_without_rule() -> Result<()> {
+    let sched = Scheduler::builder().build();
+    let periodic = sched.run_every("task", || async {}).await?;
+    assert!(periodic.is_none());
+
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_scheduler_run_every_zero() -> Result<()> {
+    let rules = Rules::builder()
+        .rule("task", every(0, 1, Missed::Skip))
+        .build();
+    let sched = Scheduler::builder().rules(rules).build();
+    let periodic = sched.run_every("task", || async {}).await?;
+    assert!(periodic.is_none());
+
+    // the scheduler is still running.
+    let res = sched.run_task("task", async {}).await?;
+    assert_eq!(res, Response::Accepted);
+
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_scheduler_run_every_skip() -> Result<()> {
+    let rules = Rules::builder()
+        .rule("slow", every(10, 1, Missed::Skip))
+        .build();
+    let sched = Scheduler::builder().rules(rules).build();
+    // each run outlasts two ticks, which are dropped as max_running is 1.
+    let (_periodic, starts) = record_starts(&sched, "slow", 25).await?;
+
+    sleep(Duration::from_secs(69)).await;
+    assert_eq!(secs(&starts), vec![10, 40]);
+
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_scheduler_run_every_catch_up() -> Result<()> {
+    let rules = Rules::builder()
+        .rule("slow", every(10, 1, Missed::CatchUp))
+        .build();
+    let sched = Scheduler::builder().rules(rules).build();
+    // the missed runs start as soon as the one before finishes.
+    let (_periodic, starts) = record_starts(&sched, "slow", 25).await?;
+
+    sleep(Duration::from_secs(69)).await;
+    assert_eq!(secs(&starts), vec![10, 35, 60]);
+
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_scheduler_run_every_jitter() -> Result<()> {
+    let rules = Rules::builder()
+        .rule(
+            "tick",
+            Rule {
+                jitter: Duration::from_secs(2),
+                ..every(10, 1, Missed::Skip)
+            },
+        )
+        .build();
+    let sched = Scheduler::builder().rules(rules).build();
+    let (_periodic, starts) = record_starts(&sched, "tick", 0).await?;
+
+    sleep(Duration::from_secs(99)).await;
+    let starts = starts.lock().unwrap().clone();
+    assert_eq!(starts.len(), 9);
+    for (tick, start) in (1..).zip(starts) {
+        // jitter delays each run without pushing back the ones after it.
+        let due = Duration::from_secs(10 * tick);
+        assert!(start >= due && start <= due + Duration::from_secs(2));
+    }
+
+    Ok(())
+}
+
+fn every(secs: u64, max_running: usize, missed: Missed) -> Rule {
+    Rule {
+        max_running,
+        run_every: Some(Duration::from_secs(secs)),
+        missed,
+        ..Rule::default()
+    }
+}
+
+type Starts = Arc<Mutex<Vec<Duration>>>;
+
+/// Runs a periodic task that takes `takes` seconds, and records when each run starts.
+async fn record_starts(sched: &Scheduler, typ: &str, takes: u64) -> Result<(Periodic, Starts)> {
+    let begin = Instant::now();
+    let starts = Starts::default();
+    let rec = starts.clone();
+    let periodic = sched
+        .run_every(typ, move || {
+            let rec = rec.clone();
+            async move {
+                rec.lock().unwrap().push(begin.elapsed());
+                sleep(Duration::from_secs(takes)).await;
+            }
+        })
+        .await?;
+    Ok((periodic.expect("rule has run_every"), starts))
+}
+
+fn secs(starts: &Starts) -> Vec<u64> {
+    starts
+        .lock()
+        .unwrap()
+        .iter()
+        .map(Duration::as_secs)
+        .collect()
+}
+
 #[derive(Clone)]
 struct TestHooks {
     count: Arc<Mutex<usize>>,